@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ratatui::{prelude::*, widgets::*};
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::client::ask;
+use crate::dns::header::RequestFlags;
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::{QClass, QType};
+use crate::layout::Grid;
+use crate::{BORDER_SIZE, VERSION};
+
+const CHART_HEIGHT: u16 = 14;
+const REDRAW: Duration = Duration::from_millis(100);
+const COLORS: [Color; 6] = [
+    Color::Green,
+    Color::Yellow,
+    Color::Cyan,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+// the names the queries ask for
+pub enum Names {
+    List(Vec<Name>),
+    // a fresh random label under the zone for every query, no cache can have seen them before
+    Random(Name),
+}
+
+impl Names {
+    fn pick(&self, seq: usize) -> Result<Name> {
+        match self {
+            Names::List(names) => Ok(names[seq % names.len()].clone()),
+            Names::Random(zone) => {
+                let label = format!("{:08x}", rand::random::<u32>()).into_bytes();
+                let labels = std::iter::once(label)
+                    .chain(zone.labels().iter().cloned())
+                    .collect();
+                Name::from_labels(labels)
+            }
+        }
+    }
+}
+
+// what the queries look like and how fast they go out
+pub struct Plan {
+    pub names: Names,
+    pub qtype: QType,
+    pub qclass: QClass,
+    pub flags: RequestFlags,
+    pub count: usize,
+    pub concurrency: usize,
+    pub rate: Option<u32>,
+}
+
+// one query, lost when it got no answer
+#[derive(Debug, Clone)]
+struct Sample {
+    seq: usize,
+    latency: Option<Duration>,
+    rcode: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Latency {
+    min: Duration,
+    mean: Duration,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+// the nearest rank percentile of sorted latencies
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Summary {
+    sent: usize,
+    received: usize,
+    latency: Option<Latency>,
+    rcodes: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    fn new(samples: &[Sample]) -> Summary {
+        let mut sorted = samples.iter().filter_map(|s| s.latency).collect::<Vec<_>>();
+        sorted.sort();
+        let latency = (!sorted.is_empty()).then(|| Latency {
+            min: sorted[0],
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            p99: percentile(&sorted, 99),
+            max: sorted[sorted.len() - 1],
+        });
+        let mut rcodes = BTreeMap::new();
+        for rcode in samples.iter().filter_map(|s| s.rcode.as_ref()) {
+            *rcodes.entry(rcode.clone()).or_insert(0) += 1;
+        }
+        Summary {
+            sent: samples.len(),
+            received: sorted.len(),
+            latency,
+            rcodes,
+        }
+    }
+
+    // the share of queries that got no answer, in percent
+    fn loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        (self.sent - self.received) as f64 * 100.0 / self.sent as f64
+    }
+
+    fn rcodes(&self) -> String {
+        self.rcodes
+            .iter()
+            .map(|(rcode, count)| format!("{} {}", rcode, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn cells(&self, server: SocketAddr) -> Vec<String> {
+        let ms = |d: Duration| format!("{:.1}", d.as_secs_f64() * 1000.0);
+        let mut cells = vec![
+            server.to_string(),
+            self.sent.to_string(),
+            format!("{:.1}%", self.loss()),
+        ];
+        match &self.latency {
+            Some(l) => cells.extend([l.min, l.mean, l.p50, l.p90, l.p99, l.max].map(ms)),
+            None => cells.extend(std::iter::repeat_n("-".to_owned(), 6)),
+        }
+        cells.push(self.rcodes());
+        cells
+    }
+
+    fn json(&self, server: SocketAddr) -> Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        json!({
+            "server": server.to_string(),
+            "sent": self.sent,
+            "received": self.received,
+            "loss": self.loss(),
+            "latency_ms": self.latency.as_ref().map(|l| json!({
+                "min": ms(l.min),
+                "mean": ms(l.mean),
+                "p50": ms(l.p50),
+                "p90": ms(l.p90),
+                "p99": ms(l.p99),
+                "max": ms(l.max),
+            })),
+            "rcodes": self.rcodes,
+        })
+    }
+}
+
+// sends the queries of the plan to a server, every answer or loss goes to `samples`
+async fn fire(
+    plan: Arc<Plan>,
+    index: usize,
+    server: SocketAddr,
+    samples: mpsc::UnboundedSender<(usize, Sample)>,
+) -> Result<()> {
+    let permits = Arc::new(Semaphore::new(plan.concurrency));
+    let mut pace = plan.rate.map(|rate| {
+        let mut pace = interval(Duration::from_secs(1) / rate);
+        pace.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        pace
+    });
+    for seq in 0..plan.count {
+        if let Some(pace) = pace.as_mut() {
+            pace.tick().await;
+        }
+        let permit = permits.clone().acquire_owned().await?;
+        let request = Message::query(
+            plan.names.pick(seq)?,
+            plan.qtype.clone(),
+            plan.qclass.clone(),
+        )
+        .with_flags(&plan.flags);
+        let samples = samples.clone();
+        tokio::spawn(async move {
+            let answer = ask(&request, server).await;
+            drop(permit);
+            let sample = match answer {
+                Ok((message, elapsed)) => Sample {
+                    seq,
+                    latency: Some(elapsed),
+                    rcode: Some(message.header.r_code.to_string()),
+                },
+                Err(_) => Sample {
+                    seq,
+                    latency: None,
+                    rcode: None,
+                },
+            };
+            let _ = samples.send((index, sample));
+        });
+    }
+    Ok(())
+}
+
+fn title(plan: &Plan, servers: &[SocketAddr]) -> String {
+    let pace = match plan.rate {
+        Some(rate) => format!("{}/s", rate),
+        None => format!("{} at a time", plan.concurrency),
+    };
+    format!(
+        "== Who are you? == v{} == bench {} x {} {} against {} server(s) ==",
+        VERSION,
+        plan.count,
+        plan.qtype,
+        pace,
+        servers.len()
+    )
+}
+
+fn results(servers: &[SocketAddr], samples: &[Vec<Sample>], width: u16) -> Grid {
+    let header = [
+        "Server", "Queries", "Loss", "Min", "Mean", "p50", "p90", "p99", "Max", "RCodes",
+    ]
+    .map(String::from)
+    .to_vec();
+    let rows = servers
+        .iter()
+        .zip(samples)
+        .map(|(server, samples)| Summary::new(samples).cells(*server));
+    Grid::new(
+        std::iter::once(header).chain(rows).collect(),
+        width.saturating_sub(BORDER_SIZE),
+        &[],
+    )
+}
+
+fn render(
+    frame: &mut Frame,
+    title: &str,
+    plan: &Plan,
+    servers: &[SocketAddr],
+    samples: &[Vec<Sample>],
+) {
+    let results = results(servers, samples, frame.size().width);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Length(1),
+            Constraint::Length(CHART_HEIGHT),
+            Constraint::Length(results.height() + BORDER_SIZE),
+        ])
+        .split(frame.size());
+
+    frame.render_widget(Paragraph::new(title.to_owned()).fg(Color::White), rows[0]);
+
+    // Latency of every answer by the order the queries went out in
+    let points = samples
+        .iter()
+        .map(|samples| {
+            samples
+                .iter()
+                .filter_map(|s| Some((s.seq as f64, s.latency?.as_secs_f64() * 1000.0)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let top = points
+        .iter()
+        .flatten()
+        .map(|(_, ms)| *ms)
+        .fold(1.0, f64::max)
+        * 1.1;
+    let datasets = points
+        .iter()
+        .zip(servers)
+        .enumerate()
+        .map(|(i, (points, server))| {
+            Dataset::default()
+                .name(server.to_string())
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(COLORS[i % COLORS.len()]))
+                .data(points)
+        })
+        .collect::<Vec<_>>();
+    let chart = Chart::new(datasets)
+        .block(
+            Block::new()
+                .title("Latency (ms)")
+                .borders(Borders::ALL)
+                .fg(Color::Green),
+        )
+        .x_axis(
+            Axis::default()
+                .bounds([0.0, plan.count as f64])
+                .labels(vec!["0".into(), plan.count.to_string().into()])
+                .style(Style::default().fg(Color::White)),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, top])
+                .labels(vec!["0".into(), format!("{:.0}", top).into()])
+                .style(Style::default().fg(Color::White)),
+        );
+    frame.render_widget(chart, rows[1]);
+
+    let mut table_rows = results.rows().into_iter();
+    let header = table_rows
+        .next()
+        .map(|row| row.add_modifier(Modifier::BOLD));
+    let table_rows = table_rows
+        .enumerate()
+        .map(|(i, row)| row.fg(COLORS[i % COLORS.len()]));
+    let mut table = Table::new(table_rows)
+        .block(
+            Block::new()
+                .title("Results")
+                .borders(Borders::ALL)
+                .fg(Color::Green),
+        )
+        .widths(results.widths());
+    if let Some(header) = header {
+        table = table.header(header);
+    }
+    frame.render_widget(table, rows[2]);
+}
+
+// Sends the queries of the plan to every server at the same time and reports the latencies,
+// live in the terminal or as json once every query is answered or lost.
+pub async fn run(plan: Plan, servers: Vec<SocketAddr>, as_json: bool) -> Result<()> {
+    let plan = Arc::new(plan);
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    for (index, server) in servers.iter().enumerate() {
+        tokio::spawn(fire(plan.clone(), index, *server, sender.clone()));
+    }
+    drop(sender);
+
+    let mut samples = vec![Vec::with_capacity(plan.count); servers.len()];
+    if as_json {
+        while let Some((index, sample)) = receiver.recv().await {
+            samples[index].push(sample);
+        }
+        let report = servers
+            .iter()
+            .zip(&samples)
+            .map(|(server, samples)| Summary::new(samples).json(*server))
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "servers": report }))?
+        );
+        return Ok(());
+    }
+
+    let title = title(&plan, &servers);
+    let height = 1 + CHART_HEIGHT + servers.len() as u16 + 1 + BORDER_SIZE;
+    let mut terminal = crate::setup_terminal(height).context("setup failed")?;
+    // the bench may run for a while, ctrl-c has to keep working
+    crossterm::terminal::disable_raw_mode().context("failed to disable raw mode")?;
+    let mut redraw = interval(REDRAW);
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Some((index, sample)) => samples[index].push(sample),
+                None => break,
+            },
+            _ = redraw.tick() => {
+                terminal.draw(|f| render(f, &title, &plan, &servers, &samples))?;
+            }
+        }
+    }
+    terminal.draw(|f| render(f, &title, &plan, &servers, &samples))?;
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn summarize_latencies() {
+        let mut samples = (1..=10)
+            .map(|ms| Sample {
+                seq: ms,
+                latency: Some(Duration::from_millis(ms as u64)),
+                rcode: Some(if ms == 3 { "ServerFailure" } else { "NoError" }.to_owned()),
+            })
+            .collect::<Vec<_>>();
+        samples.extend((0..10).map(|seq| Sample {
+            seq,
+            latency: None,
+            rcode: None,
+        }));
+
+        let summary = Summary::new(&samples);
+        let ms = Duration::from_millis;
+        assert_eq!(
+            Some(Latency {
+                min: ms(1),
+                mean: Duration::from_micros(5500),
+                p50: ms(5),
+                p90: ms(9),
+                p99: ms(10),
+                max: ms(10),
+            }),
+            summary.latency
+        );
+        assert_eq!(50.0, summary.loss());
+        assert_eq!("NoError 9, ServerFailure 1", summary.rcodes());
+        assert_eq!(None, Summary::new(&[]).latency);
+    }
+}
@@ -0,0 +1,164 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+use crate::dns::message::Message;
+use crate::dns::Serialize;
+
+pub const DEFAULT_SERVER: &str = "1.1.1.1:53";
+const DNS_PORT: u16 = 53;
+const TIMEOUT: Duration = Duration::from_secs(5);
+pub(crate) const MAX_UDP_SIZE: usize = 65535;
+
+// parses a server given as `@host`, `@host:port`, `@ipv6` or `@[ipv6]:port`, the leading `@` is
+// optional. The host is kept as is and resolved when the query is sent.
+pub fn parse_server(value: &str) -> Result<String, String> {
+    let value = value.strip_prefix('@').unwrap_or(value);
+    if value.is_empty() {
+        return Err("server can not be empty".to_owned());
+    }
+
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DNS_PORT).to_string());
+    }
+    if value.parse::<SocketAddr>().is_ok() {
+        return Ok(value.to_owned());
+    }
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => {
+            port.parse::<u16>()
+                .map_err(|_| format!("invalid port in server: {}", value))?;
+            Ok(value.to_owned())
+        }
+        Some(_) => Err(format!("invalid server: {}", value)),
+        None => Ok(format!("{}:{}", value, DNS_PORT)),
+    }
+}
+
+pub async fn resolve_server(server: &str) -> Result<SocketAddr> {
+    lookup_host(server)
+        .await
+        .with_context(|| format!("could not resolve server: {}", server))?
+        .next()
+        .ok_or_else(|| anyhow!("no address found for server: {}", server))
+}
+
+fn bind_address(server: SocketAddr) -> &'static str {
+    if server.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    }
+}
+
+// sends a single request over udp and waits for the response from the same server
+pub async fn send_udp(request: &[u8], server: SocketAddr) -> Result<Vec<u8>> {
+    let sock = UdpSocket::bind(bind_address(server))
+        .await
+        .context("could not bind")?;
+    sock.connect(server).await.context("could not connect")?;
+    sock.send(request).await?;
+
+    let mut buffer = vec![0; MAX_UDP_SIZE];
+    let length = timeout(TIMEOUT, sock.recv(&mut buffer))
+        .await
+        .map_err(|_| anyhow!("no response from {} within {:?}", server, TIMEOUT))??;
+    buffer.truncate(length);
+    Ok(buffer)
+}
+
+// a tcp connection to a server, every message is prefixed with a two byte length field
+// (rfc 1035 4.2.2). Zone transfers read several responses over the same connection.
+pub struct TcpConnection {
+    stream: TcpStream,
+}
+
+impl TcpConnection {
+    pub async fn connect(server: SocketAddr) -> Result<TcpConnection> {
+        let stream = timeout(TIMEOUT, TcpStream::connect(server))
+            .await
+            .map_err(|_| anyhow!("could not connect to {} within {:?}", server, TIMEOUT))?
+            .with_context(|| format!("could not connect to {}", server))?;
+        Ok(TcpConnection { stream })
+    }
+
+    pub async fn send(&mut self, request: &[u8]) -> Result<()> {
+        let length: u16 = request
+            .len()
+            .try_into()
+            .context("request too large for tcp")?;
+        let mut bytes = Vec::with_capacity(request.len() + 2);
+        bytes.extend(length.to_be_bytes());
+        bytes.extend(request);
+        self.stream.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    pub async fn recv(&mut self) -> Result<Vec<u8>> {
+        let length = timeout(TIMEOUT, self.stream.read_u16())
+            .await
+            .map_err(|_| anyhow!("no response within {:?}", TIMEOUT))?
+            .context("connection closed by server")?;
+        let mut buffer = vec![0; length as usize];
+        timeout(TIMEOUT, self.stream.read_exact(&mut buffer))
+            .await
+            .map_err(|_| anyhow!("no response within {:?}", TIMEOUT))??;
+        Ok(buffer)
+    }
+}
+
+// sends a single request over tcp and reads one response
+pub async fn send_tcp(request: &[u8], server: SocketAddr) -> Result<Vec<u8>> {
+    let mut connection = TcpConnection::connect(server).await?;
+    connection.send(request).await?;
+    connection.recv().await
+}
+
+// sends a request over udp and retries over tcp when the response came back truncated, returns
+// the raw response
+pub async fn exchange(request: &[u8], server: SocketAddr) -> Result<Vec<u8>> {
+    let response = send_udp(request, server).await?;
+    // the TC bit is the second bit of the third header octet
+    if response.len() > 2 && response[2] & 0x02 != 0 {
+        return send_tcp(request, server).await;
+    }
+    Ok(response)
+}
+
+// sends a request as it is and returns the response with the time it took
+pub async fn ask(request: &Message, server: SocketAddr) -> Result<(Message, Duration)> {
+    let request = request.serialize().context("Failed to serialize request")?;
+    let start = Instant::now();
+    let response = exchange(&request, server).await?;
+    let elapsed = start.elapsed();
+    let message =
+        Message::try_from(response.as_slice()).context("Failed to deserialize response")?;
+    Ok((message, elapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_server_formats() {
+        assert_eq!(Ok("8.8.8.8:53".to_owned()), parse_server("@8.8.8.8"));
+        assert_eq!(
+            Ok("127.0.0.1:5353".to_owned()),
+            parse_server("@127.0.0.1:5353")
+        );
+        assert_eq!(Ok("[::1]:53".to_owned()), parse_server("@::1"));
+        assert_eq!(Ok("[::1]:5353".to_owned()), parse_server("[::1]:5353"));
+        assert_eq!(
+            Ok("ns1.example.com:53".to_owned()),
+            parse_server("@ns1.example.com")
+        );
+        assert!(parse_server("@ns1.example.com:dns").is_err());
+        assert!(parse_server("@").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use rand::random;
+
+use crate::dns::edns::EdnsOption;
+use crate::dns::header::ResponseCode;
+use crate::dns::message::Message;
+use crate::dns::serialize_utils::to_hex;
+
+// what became of the cookie we sent, shown in the Statistics block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieStatus {
+    // cookies were not sent
+    Disabled,
+    // the server answered without a cookie, it does not support them
+    Unsupported,
+    // the server returned our client cookie and its first server cookie
+    New,
+    // the server cookie we sent was accepted
+    Valid,
+    // the server rejected our server cookie even after a retry with a fresh one
+    Bad,
+    // the client cookie in the response is not ours, the response may be forged
+    Mismatch,
+}
+
+impl Display for CookieStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            CookieStatus::Disabled => "disabled",
+            CookieStatus::Unsupported => "not supported by server",
+            CookieStatus::New => "new server cookie",
+            CookieStatus::Valid => "valid",
+            CookieStatus::Bad => "rejected (BADCOOKIE)",
+            CookieStatus::Mismatch => "client cookie mismatch",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cookie {
+    client: Vec<u8>,
+    server: Vec<u8>,
+}
+
+// DNS cookies (rfc 7873) per server address. The client cookie is random and kept per server so
+// it can not be used to track us across servers, the server cookie is whatever the server last
+// gave us. The jar lives in a small state file, one server per line:
+//
+//     <address> <client cookie hex> [<server cookie hex>]
+//
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    path: Option<PathBuf>,
+    cookies: BTreeMap<String, Cookie>,
+}
+
+fn default_path() -> Option<PathBuf> {
+    let state = match std::env::var_os("XDG_STATE_HOME") {
+        Some(state) if !state.is_empty() => PathBuf::from(state),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(state.join("who").join("cookies"))
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl CookieJar {
+    // loads the jar from the default state file, a missing or broken file is an empty jar
+    pub fn load() -> CookieJar {
+        match default_path() {
+            Some(path) => CookieJar::load_from(&path),
+            None => CookieJar::default(),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> CookieJar {
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        let cookies = contents
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let server = fields.next()?.to_owned();
+                let client = from_hex(fields.next()?).filter(|c| c.len() == 8)?;
+                let server_cookie = fields.next().map_or(Some(Vec::new()), from_hex)?;
+                Some((
+                    server,
+                    Cookie {
+                        client,
+                        server: server_cookie,
+                    },
+                ))
+            })
+            .collect();
+        CookieJar {
+            path: Some(path.to_owned()),
+            cookies,
+        }
+    }
+
+    // writes the jar back, cookies are an optimisation so failing to store them is not an error
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let contents = self
+            .cookies
+            .iter()
+            .map(|(server, cookie)| {
+                format!(
+                    "{} {} {}\n",
+                    server,
+                    to_hex(&cookie.client),
+                    to_hex(&cookie.server)
+                )
+            })
+            .collect::<String>();
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = fs::write(path, contents);
+    }
+
+    // the COOKIE option to send to the server, a client cookie is made up on first contact
+    pub fn option(&mut self, server: SocketAddr) -> EdnsOption {
+        let cookie = self
+            .cookies
+            .entry(server.to_string())
+            .or_insert_with(|| Cookie {
+                client: random::<[u8; 8]>().to_vec(),
+                server: Vec::new(),
+            });
+        EdnsOption::Cookie {
+            client: cookie.client.clone(),
+            server: cookie.server.clone(),
+        }
+    }
+
+    // remembers the server cookie of a response that returned our client cookie
+    pub fn receive(&mut self, server: SocketAddr, response: &Message) -> CookieStatus {
+        let Some(cookie) = self.cookies.get_mut(&server.to_string()) else {
+            return CookieStatus::Disabled;
+        };
+        let edns = response.edns();
+        let Some((client, server_cookie)) = edns.as_ref().and_then(|e| e.cookie()) else {
+            return CookieStatus::Unsupported;
+        };
+        if client != cookie.client {
+            return CookieStatus::Mismatch;
+        }
+
+        let sent = !cookie.server.is_empty();
+        if !server_cookie.is_empty() {
+            cookie.server = server_cookie.to_vec();
+        }
+        match response.header.r_code {
+            ResponseCode::BadCookie => CookieStatus::Bad,
+            _ if sent => CookieStatus::Valid,
+            _ => CookieStatus::New,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::edns::Edns;
+    use pretty_assertions::assert_eq;
+
+    fn response(request: &Message, r_code: ResponseCode, server_cookie: &[u8]) -> Message {
+        let client = match request.edns().unwrap().cookie() {
+            Some((client, _)) => client.to_vec(),
+            None => Vec::new(),
+        };
+        let mut response = request.reply(r_code);
+        let mut edns = Edns::new(1232);
+        edns.options.push(EdnsOption::Cookie {
+            client,
+            server: server_cookie.to_vec(),
+        });
+        response.additionals.push(edns.record());
+        response
+    }
+
+    fn request(jar: &mut CookieJar, server: SocketAddr) -> Message {
+        let mut request = Message::a("example.com".parse().unwrap());
+        let mut edns = Edns::new(1232);
+        edns.options.push(jar.option(server));
+        request.additionals.push(edns.record());
+        request
+    }
+
+    #[test]
+    fn server_cookie_is_remembered_and_echoed() {
+        let path = std::env::temp_dir().join(format!("who-cookies-{}", random::<u32>()));
+        let server: SocketAddr = "192.0.2.53:53".parse().unwrap();
+
+        let mut jar = CookieJar::load_from(&path);
+        let first = request(&mut jar, server);
+        let status = jar.receive(server, &response(&first, ResponseCode::NoError, &[7; 16]));
+        assert_eq!(CookieStatus::New, status);
+        jar.save();
+
+        let mut jar = CookieJar::load_from(&path);
+        let second = request(&mut jar, server);
+        let (client, server_cookie) = second
+            .edns()
+            .unwrap()
+            .cookie()
+            .map(|(c, s)| (c.to_vec(), s.to_vec()))
+            .unwrap();
+        assert_eq!(first.edns().unwrap().cookie().unwrap().0, client.as_slice());
+        assert_eq!(vec![7; 16], server_cookie);
+
+        let status = jar.receive(
+            server,
+            &response(&second, ResponseCode::BadCookie, &[8; 16]),
+        );
+        assert_eq!(CookieStatus::Bad, status);
+        assert_eq!(
+            EdnsOption::Cookie {
+                client,
+                server: vec![8; 16]
+            },
+            jar.option(server)
+        );
+        let _ = fs::remove_file(path);
+    }
+}
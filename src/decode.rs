@@ -0,0 +1,480 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use nom::bytes::complete::take;
+use nom::number::complete::{be_u16, be_u32};
+
+use crate::dns::header::Header;
+use crate::dns::message::Message;
+use crate::dns::name::parse_name;
+use crate::dns::parse_utils::{is_ptr, parse_qclass, parse_qtype, VResult};
+use crate::dns::record::parse_record;
+use crate::dns::{Buffer, DeSerialize, QType};
+
+// octets on a line of the annotation and of the hexdump
+const ANNOTATION_WIDTH: usize = 8;
+const DUMP_WIDTH: usize = 16;
+
+// A field of a message as it is on the wire: where it starts, its octets and what they mean.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field<'a> {
+    offset: usize,
+    octets: &'a [u8],
+    name: &'static str,
+    value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Section<'a> {
+    title: String,
+    fields: Vec<Field<'a>>,
+}
+
+// Walks a message field by field with the parsers Message::deserialize uses. Where a field does
+// not parse the walk stops, Err holds the offset it stopped at.
+struct Walk<'a> {
+    source: &'a [u8],
+    current: &'a [u8],
+    sections: Vec<Section<'a>>,
+}
+
+impl<'a> Walk<'a> {
+    fn offset(&self) -> usize {
+        self.source.len() - self.current.len()
+    }
+
+    fn section(&mut self, title: String) {
+        self.sections.push(Section {
+            title,
+            fields: Vec::new(),
+        });
+    }
+
+    // parses the next field and adds it to the last section, described by `describe`
+    fn field<T>(
+        &mut self,
+        name: &'static str,
+        parser: impl Fn(&'a [u8]) -> VResult<&'a [u8], T>,
+        describe: impl Fn(&T) -> String,
+    ) -> Result<T, usize> {
+        let offset = self.offset();
+        let (rest, value) = parser(self.current).map_err(|_| offset)?;
+        let field = Field {
+            offset,
+            octets: &self.current[..self.current.len() - rest.len()],
+            name,
+            value: describe(&value),
+        };
+        if let Some(section) = self.sections.last_mut() {
+            section.fields.push(field);
+        }
+        self.current = rest;
+        Ok(value)
+    }
+
+    fn name(&mut self) -> Result<(), usize> {
+        let source = self.source;
+        let offset = self.offset();
+        self.field(
+            "name",
+            |buffer| parse_name(buffer, source),
+            |name| match pointer(&source[offset..]) {
+                Some(target) => format!("{} (pointer to {:04x})", name, target),
+                None => name.to_string(),
+            },
+        )
+        .map(|_| ())
+    }
+
+    fn header(&mut self) -> Result<[u16; 4], usize> {
+        self.section("header".to_owned());
+        let mut buffer = Buffer {
+            current: self.source,
+            source: self.source,
+        };
+        // the flags are only described when the whole header is there
+        let header = Header::deserialize(&mut buffer)
+            .map(|(_, header)| header)
+            .ok();
+        self.field("id", be_u16, |id| id.to_string())?;
+        self.field("flags", be_u16, |flags| match &header {
+            Some(header) => format!(
+                "{}{}opcode {}, rcode {}",
+                header.flags().join(" "),
+                if header.flags().is_empty() { "" } else { ", " },
+                header.opcode,
+                header.r_code
+            ),
+            None => format!("{:016b}", flags),
+        })?;
+        let mut counts = [0; 4];
+        for (count, name) in counts
+            .iter_mut()
+            .zip(["qdcount", "ancount", "nscount", "arcount"])
+        {
+            *count = self.field(name, be_u16, |n| n.to_string())?;
+        }
+        Ok(counts)
+    }
+
+    fn question(&mut self, n: u16) -> Result<(), usize> {
+        self.section(format!("question {}", n));
+        self.name()?;
+        self.field("type", parse_qtype, |t| t.to_string())?;
+        self.field("class", parse_qclass, |c| c.to_string())?;
+        Ok(())
+    }
+
+    // The record is parsed as a whole first, the way the message parses it, and then split in
+    // its fields. The class and ttl of an OPT record carry the EDNS payload size and flags.
+    fn record(&mut self, title: &str, n: u16) -> Result<(), usize> {
+        self.section(format!("{} {}", title, n));
+        let (_, record) = parse_record(self.current, self.source).map_err(|_| self.offset())?;
+        self.name()?;
+        self.field("type", parse_qtype, |t| t.to_string())?;
+        if record.qtype == QType::OPT {
+            self.field("class", be_u16, |size| format!("udp payload size {}", size))?;
+            self.field("ttl", be_u32, |ttl| {
+                format!(
+                    "extended rcode {}, version {}{}",
+                    ttl >> 24,
+                    ttl >> 16 & 0xff,
+                    if ttl & 0x8000 != 0 { ", do" } else { "" }
+                )
+            })?;
+        } else {
+            self.field("class", parse_qclass, |c| c.to_string())?;
+            self.field("ttl", be_u32, |ttl| format!("{} seconds", ttl))?;
+        }
+        let length = self.field("rdlength", be_u16, |l| format!("{} octets", l))?;
+        if length > 0 {
+            self.field("rdata", take(length), |_| record.rdata.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn message(&mut self) -> Result<(), usize> {
+        let [questions, answers, authorities, additionals] = self.header()?;
+        for n in 1..=questions {
+            self.question(n)?;
+        }
+        for (title, count) in [
+            ("answer", answers),
+            ("authority", authorities),
+            ("additional", additionals),
+        ] {
+            for n in 1..=count {
+                self.record(title, n)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// where the name at the start of `octets` continues when it ends in a compression pointer
+fn pointer(octets: &[u8]) -> Option<usize> {
+    let mut at = 0;
+    loop {
+        let length = *octets.get(at)?;
+        if length == 0 {
+            return None;
+        }
+        if is_ptr(length) {
+            let low = *octets.get(at + 1)?;
+            return Some(((length as usize & 0x3f) << 8) | low as usize);
+        }
+        at += length as usize + 1;
+    }
+}
+
+// the sections of a message with every field, and the offset the walk stopped at if it did not
+// get through the whole message
+fn annotate(octets: &[u8]) -> (Vec<Section<'_>>, Option<usize>) {
+    let mut walk = Walk {
+        source: octets,
+        current: octets,
+        sections: Vec::new(),
+    };
+    let stopped = walk.message().err();
+    if stopped.is_none() && !walk.current.is_empty() {
+        let trailing = walk.current.len();
+        walk.section("trailing".to_owned());
+        let _ = walk.field("octets", take(trailing), |_| {
+            "not part of the message".to_owned()
+        });
+    }
+    (walk.sections, stopped)
+}
+
+// octets as hex in groups of two, the way the annotated dumps in the sources show them
+fn groups(octets: &[u8]) -> String {
+    octets
+        .chunks(2)
+        .map(|pair| {
+            pair.iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn text(sections: &[Section]) -> String {
+    let hex_width = ANNOTATION_WIDTH * 2 + ANNOTATION_WIDTH / 2 - 1;
+    let mut text = String::new();
+    for section in sections {
+        text.push_str(&format!(";; {}\n", section.title));
+        for field in &section.fields {
+            for (i, line) in field.octets.chunks(ANNOTATION_WIDTH).enumerate() {
+                let offset = field.offset + i * ANNOTATION_WIDTH;
+                let line = if i == 0 {
+                    format!(
+                        "{:04x}  {:<hex_width$}  {:<8}  {}",
+                        offset,
+                        groups(line),
+                        field.name,
+                        field.value
+                    )
+                } else {
+                    format!("{:04x}  {}", offset, groups(line))
+                };
+                text.push_str(line.trim_end());
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+// the octets the way xxd shows them, offset, hex and the printable characters
+pub fn hexdump(octets: &[u8]) -> String {
+    let hex_width = DUMP_WIDTH * 2 + DUMP_WIDTH / 2 - 1;
+    octets
+        .chunks(DUMP_WIDTH)
+        .enumerate()
+        .map(|(i, line)| {
+            let printable = line
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect::<String>();
+            format!(
+                "{:04x}: {:<hex_width$}  {}\n",
+                i * DUMP_WIDTH,
+                groups(line),
+                printable
+            )
+        })
+        .collect()
+}
+
+// the octets of a hexdump line, Wireshark's "copy as hex dump" and xxd start with an offset
+// and end with the printable characters. Both show at most 16 octets on a line.
+fn dump_line(line: &str) -> Option<Vec<u8>> {
+    let (offset, rest) = line.split_once(|c: char| c == ':' || c.is_whitespace())?;
+    let is_offset = offset.len() >= 4 && offset.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_offset || !(rest.starts_with(' ') || line.as_bytes()[offset.len()] == b':') {
+        return None;
+    }
+    let mut octets = Vec::new();
+    for token in rest.split_whitespace() {
+        match hex(token) {
+            Some(bytes) if octets.len() + bytes.len() <= DUMP_WIDTH => octets.extend(bytes),
+            _ => break,
+        }
+    }
+    (!octets.is_empty()).then_some(octets)
+}
+
+fn hex(text: &str) -> Option<Vec<u8>> {
+    if text.is_empty()
+        || !text.len().is_multiple_of(2)
+        || !text.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+// Reads the octets of a message from text: a hexdump, hex with or without spaces, 0x prefixes
+// and commas, or base64 in either alphabet with or without padding (DoH uses base64url).
+fn from_text(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let lines = text.lines().map(dump_line).collect::<Option<Vec<_>>>();
+    if let Some(lines) = lines {
+        return Some(lines.concat());
+    }
+    let stripped = text
+        .replace("0x", "")
+        .replace("0X", "")
+        .replace("\\x", "")
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, ',' | ':'))
+        .collect::<String>();
+    hex(&stripped).or_else(|| {
+        let compact = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        [STANDARD, URL_SAFE, STANDARD_NO_PAD, URL_SAFE_NO_PAD]
+            .iter()
+            .find_map(|engine| engine.decode(&compact).ok())
+    })
+}
+
+// The octets to decode. The argument is hex or base64, or a file with a binary message or text
+// in one of those, - reads standard input.
+pub fn input(argument: &str) -> Result<Vec<u8>> {
+    let (contents, from_file) = if argument == "-" {
+        let mut contents = Vec::new();
+        io::stdin().read_to_end(&mut contents)?;
+        (contents, true)
+    } else if Path::new(argument).is_file() {
+        let contents =
+            std::fs::read(argument).with_context(|| format!("could not read {}", argument))?;
+        (contents, true)
+    } else {
+        (argument.as_bytes().to_vec(), false)
+    };
+    match std::str::from_utf8(&contents).ok().and_then(from_text) {
+        Some(octets) => Ok(octets),
+        None if from_file => Ok(contents),
+        None => Err(anyhow!("{} is not hex, base64 or a file", argument)),
+    }
+}
+
+// Prints every field of the message with its offset and octets, then whether the message as a
+// whole could be read.
+pub fn run(octets: &[u8]) -> Result<()> {
+    let (sections, stopped) = annotate(octets);
+    print!("{}", text(&sections));
+    let message = Message::try_from(octets);
+    match (message, stopped) {
+        (Ok(_), _) => {
+            println!(";; {} octets", octets.len());
+            Ok(())
+        }
+        (Err(e), Some(offset)) => Err(e.context(format!(
+            "the message does not parse at offset {:04x}",
+            offset
+        ))),
+        (Err(e), None) => Err(e.context("the message does not parse")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::edns::Edns;
+    use crate::dns::header::ResponseCode;
+    use crate::dns::record::{RData, Record};
+    use crate::dns::{QClass, Serialize};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn annotate_every_field() {
+        let mut query = Message::query("blog.example".parse().unwrap(), QType::A, QClass::IN);
+        query.header.id = 0xa4c9;
+        let mut response = query.reply(ResponseCode::NoError);
+        response.records.push(Record::new(
+            "blog.example".parse().unwrap(),
+            QType::A,
+            QClass::IN,
+            Duration::from_secs(3600),
+            4,
+            RData::A("192.0.2.7".parse().unwrap()),
+        ));
+        response.additionals.push(Edns::new(1232).record());
+        let mut octets = response.serialize().unwrap();
+        // compress the name of the answer to the one of the question
+        octets.splice(30..44, [0xc0, 0x0c]);
+
+        assert_eq!(
+            ";; header
+0000  a4c9                 id        42185
+0002  8100                 flags     qr rd, opcode Query, rcode NoError
+0004  0001                 qdcount   1
+0006  0001                 ancount   1
+0008  0000                 nscount   0
+000a  0001                 arcount   1
+;; question 1
+000c  0462 6c6f 6707 6578  name      blog.example
+0014  616d 706c 6500
+001a  0001                 type      A
+001c  0001                 class     IN
+;; answer 1
+001e  c00c                 name      blog.example (pointer to 000c)
+0020  0001                 type      A
+0022  0001                 class     IN
+0024  0000 0e10            ttl       3600 seconds
+0028  0004                 rdlength  4 octets
+002a  c000 0207            rdata     192.0.2.7
+;; additional 1
+002e  00                   name      .
+002f  0029                 type      OPT
+0031  04d0                 class     udp payload size 1232
+0033  0000 0000            ttl       extended rcode 0, version 0
+0037  0000                 rdlength  0 octets
+",
+            text(&annotate(&octets).0)
+        );
+
+        let (_, stopped) = annotate(&octets[..40]);
+        assert_eq!(Some(0x1e), stopped);
+    }
+
+    #[test]
+    fn annotate_types_without_a_case() {
+        let mut query = Message::query(
+            "7.2.0.192.in-addr.arpa".parse().unwrap(),
+            QType::PTR,
+            QClass::IN,
+        );
+        query.header.id = 1;
+        let mut response = query.reply(ResponseCode::NoError);
+        response.records.push(Record::new(
+            "7.2.0.192.in-addr.arpa".parse().unwrap(),
+            QType::Unknown(65280),
+            QClass::IN,
+            Duration::from_secs(300),
+            2,
+            RData::Unknown(vec![0xab, 0xcd]),
+        ));
+        let octets = response.serialize().unwrap();
+
+        let (sections, stopped) = annotate(&octets);
+        assert_eq!(None, stopped);
+        let text = text(&sections);
+        assert!(text.contains("type      PTR\n"), "{}", text);
+        assert!(text.contains("type      TYPE65280\n"), "{}", text);
+        assert!(text.contains("rdata     \\# 2 ABCD\n"), "{}", text);
+        let parsed = Message::try_from(octets.as_slice()).unwrap();
+        assert_eq!(response.records, parsed.records);
+    }
+
+    #[test]
+    fn read_hex_and_base64() {
+        let octets = vec![0xa4, 0xc9, 0x81, 0x80, 0x00, 0x01];
+        for text in [
+            "a4c9818000 01",
+            "0xa4, 0xc9, 0x81, 0x80, 0x00, 0x01",
+            "a4:c9:81:80:00:01",
+            "0000   a4 c9 81 80 00 01                                 ......",
+            "00000000: a4c9 8180 0001                           ......",
+            "pMmBgAAB",
+        ] {
+            assert_eq!(Some(octets.clone()), from_text(text), "{}", text);
+        }
+        assert_eq!(
+            hexdump(&octets),
+            "0000: a4c9 8180 0001                           ......\n"
+        );
+        assert_eq!(None, from_text("not a message"));
+    }
+}
@@ -0,0 +1,306 @@
+use std::cmp::Ordering;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::client::ask;
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::record::Record;
+use crate::dns::QType;
+use crate::json;
+
+// how many questions are out at the same time, a long list of names would otherwise open a
+// socket for every one of them at once
+const CONCURRENT: usize = 32;
+
+// what a server answered for a name and type, or why it did not
+type Answer = Result<Message, String>;
+
+// How the answers of the two servers for one name and type differ. Missing records are only on
+// the first server, extra records only on the second.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    name: Name,
+    qtype: QType,
+    status: Option<(String, String)>,
+    missing: Vec<Record>,
+    extra: Vec<Record>,
+    ttl: Vec<(Record, Record)>,
+}
+
+// the same record as far as the comparison goes: names compare without case and the ttl, that
+// is only compared when asked for, is left out
+fn same(a: &Record, b: &Record) -> bool {
+    a.name == b.name && a.qtype == b.qtype && a.qclass == b.qclass && a.rdata == b.rdata
+}
+
+// the records of an answer in a stable order, servers hand out RRsets in any order they like
+fn sorted(answer: &Answer) -> Vec<Record> {
+    let mut records = answer
+        .as_ref()
+        .map(|m| m.records.clone())
+        .unwrap_or_default();
+    records.sort_by(|a, b| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| a.qtype.to_string().cmp(&b.qtype.to_string()))
+            .then_with(|| compare_data(a, b))
+    });
+    records
+}
+
+fn compare_data(a: &Record, b: &Record) -> Ordering {
+    a.rdata
+        .to_string()
+        .to_lowercase()
+        .cmp(&b.rdata.to_string().to_lowercase())
+}
+
+fn status(answer: &Answer) -> String {
+    match answer {
+        Ok(message) => message.header.r_code.to_string(),
+        Err(error) => format!("error: {}", error),
+    }
+}
+
+// compares what the two servers answered, None when they agree
+fn compare(name: &Name, qtype: &QType, a: &Answer, b: &Answer, ttl: bool) -> Option<Difference> {
+    let (status_a, status_b) = (status(a), status(b));
+    let failed = a.is_err() || b.is_err();
+    let (a, b) = (sorted(a), sorted(b));
+    let difference = Difference {
+        name: name.clone(),
+        qtype: qtype.clone(),
+        status: (failed || status_a != status_b).then_some((status_a, status_b)),
+        missing: a
+            .iter()
+            .filter(|r| !b.iter().any(|o| same(r, o)))
+            .cloned()
+            .collect(),
+        extra: b
+            .iter()
+            .filter(|r| !a.iter().any(|o| same(r, o)))
+            .cloned()
+            .collect(),
+        ttl: a
+            .iter()
+            .filter_map(|r| {
+                b.iter()
+                    .find(|o| same(r, o))
+                    .map(|o| (r.clone(), o.clone()))
+            })
+            .filter(|(r, o)| ttl && r.ttl != o.ttl)
+            .collect(),
+    };
+    if difference.status.is_none()
+        && difference.missing.is_empty()
+        && difference.extra.is_empty()
+        && difference.ttl.is_empty()
+    {
+        return None;
+    }
+    Some(difference)
+}
+
+fn line(record: &Record) -> String {
+    format!(
+        "{} {} {} {} {}",
+        record.name,
+        record.ttl.as_secs(),
+        record.qclass,
+        record.qtype,
+        record.rdata
+    )
+}
+
+impl Difference {
+    // the difference the way diff shows it, - for the first server and + for the second
+    fn text(&self) -> String {
+        let mut text = format!("{} {}\n", self.name, self.qtype);
+        if let Some((a, b)) = &self.status {
+            text.push_str(&format!("  ! {} <> {}\n", a, b));
+        }
+        for record in &self.missing {
+            text.push_str(&format!("  - {}\n", line(record)));
+        }
+        for record in &self.extra {
+            text.push_str(&format!("  + {}\n", line(record)));
+        }
+        for (a, b) in &self.ttl {
+            text.push_str(&format!(
+                "  ~ {} {} {} {} ttl {} <> {}\n",
+                a.name,
+                a.qclass,
+                a.qtype,
+                a.rdata,
+                a.ttl.as_secs(),
+                b.ttl.as_secs()
+            ));
+        }
+        text
+    }
+
+    fn json(&self) -> Value {
+        json!({
+            "name": self.name.to_string(),
+            "type": self.qtype.to_string(),
+            "status": self.status.as_ref().map(|(a, b)| json!([a, b])),
+            "missing": json::records(&self.missing),
+            "extra": json::records(&self.extra),
+            "ttl": self.ttl.iter().map(|(a, b)| json!({
+                "record": json::record(a),
+                "ttl": [a.ttl.as_secs(), b.ttl.as_secs()],
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+// asks both servers every query, a few at a time, the answers come back in the order of the
+// queries
+async fn ask_all(queries: &[Message], servers: [SocketAddr; 2]) -> Result<Vec<[Answer; 2]>> {
+    let permits = Arc::new(Semaphore::new(CONCURRENT));
+    let mut tasks = JoinSet::new();
+    for (i, query) in queries.iter().enumerate() {
+        for (side, server) in servers.into_iter().enumerate() {
+            let mut request = query.clone();
+            request.header.id = rand::random();
+            let permits = permits.clone();
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let answer = ask(&request, server).await;
+                (
+                    i,
+                    side,
+                    answer.map(|(m, _)| m).map_err(|e| format!("{:#}", e)),
+                )
+            });
+        }
+    }
+
+    let mut answers = queries
+        .iter()
+        .map(|_| [Err(String::new()), Err(String::new())])
+        .collect::<Vec<[Answer; 2]>>();
+    while let Some(joined) = tasks.join_next().await {
+        let (i, side, answer) = joined?;
+        answers[i][side] = answer;
+    }
+    Ok(answers)
+}
+
+// Asks both servers every query and reports how they differ, as text or json. Returns whether
+// they gave the same answers.
+pub async fn run(
+    queries: Vec<Message>,
+    servers: [SocketAddr; 2],
+    ttl: bool,
+    as_json: bool,
+) -> Result<bool> {
+    let answers = ask_all(&queries, servers).await?;
+    let differences = queries
+        .iter()
+        .zip(&answers)
+        .filter_map(|(query, [a, b])| {
+            let question = query.question()?;
+            compare(&question.qname, &question.qtype, a, b, ttl)
+        })
+        .collect::<Vec<_>>();
+
+    if as_json {
+        let report = json!({
+            "servers": [servers[0].to_string(), servers[1].to_string()],
+            "compared": queries.len(),
+            "differences": differences.iter().map(Difference::json).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("--- {}\n+++ {}", servers[0], servers[1]);
+        for difference in &differences {
+            print!("{}", difference.text());
+        }
+        if differences.is_empty() {
+            println!("all {} RRsets match", queries.len());
+        } else {
+            println!("{} of {} RRsets differ", differences.len(), queries.len());
+        }
+    }
+    Ok(differences.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::header::ResponseCode;
+    use crate::dns::record::RData;
+    use crate::dns::QClass;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn record(name: &str, ttl: u64, data: RData) -> Record {
+        Record::new(
+            name.parse().unwrap(),
+            QType::MX,
+            QClass::IN,
+            Duration::from_secs(ttl),
+            0,
+            data,
+        )
+    }
+
+    fn mx(preference: u16, exchange: &str) -> RData {
+        RData::MX {
+            preference,
+            exchange: exchange.parse().unwrap(),
+        }
+    }
+
+    fn answer(records: Vec<Record>) -> Answer {
+        let mut message = Message::query("example.com".parse().unwrap(), QType::MX, QClass::IN);
+        message.records = records;
+        Ok(message)
+    }
+
+    #[test]
+    fn compare_normalized_rrsets() {
+        let name = "example.com".parse().unwrap();
+        let a = answer(vec![
+            record("example.com", 300, mx(10, "mx1.example.com")),
+            record("example.com", 300, mx(20, "mx2.example.com")),
+        ]);
+        let b = answer(vec![
+            record("EXAMPLE.com", 3600, mx(20, "MX2.example.com")),
+            record("example.com", 300, mx(10, "mx1.example.com")),
+        ]);
+        assert_eq!(None, compare(&name, &QType::MX, &a, &b, false));
+
+        let difference = compare(&name, &QType::MX, &a, &b, true).unwrap();
+        assert_eq!(
+            "example.com MX\n  ~ example.com IN MX 20 mx2.example.com ttl 300 <> 3600\n",
+            difference.text()
+        );
+
+        let b = answer(vec![record("example.com", 300, mx(30, "mx3.example.net"))]);
+        let difference = compare(&name, &QType::MX, &a, &b, false).unwrap();
+        assert_eq!(
+            "example.com MX
+  - example.com 300 IN MX 10 mx1.example.com
+  - example.com 300 IN MX 20 mx2.example.com
+  + example.com 300 IN MX 30 mx3.example.net
+",
+            difference.text()
+        );
+
+        let mut nxdomain = answer(Vec::new()).unwrap();
+        nxdomain.header.r_code = ResponseCode::NameError;
+        let difference = compare(&name, &QType::MX, &answer(Vec::new()), &Ok(nxdomain), false);
+        assert_eq!(
+            Some(("NoError".to_owned(), "NameError".to_owned())),
+            difference.unwrap().status
+        );
+    }
+}
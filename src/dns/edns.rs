@@ -0,0 +1,469 @@
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::anyhow;
+
+use nom::bytes::complete::take;
+use nom::multi::many0;
+use nom::number::complete::be_u16;
+
+use super::name::Name;
+use super::parse_utils::VResult;
+use super::record::{RData, Record};
+use super::serialize_utils::{escape_character_string, to_hex};
+use super::{QClass, QType};
+
+// the udp payload size we advertise, small enough to avoid ip fragmentation (dns flag day 2020)
+pub const DEFAULT_PAYLOAD: u16 = 1232;
+
+// Options carried in the rdata of an OPT record (rfc 6891 6.1.2), each encoded as:
+//
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |                  OPTION-CODE                  |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |                 OPTION-LENGTH                 |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     /                  OPTION-DATA                  /
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdnsOption {
+    // the identifier of the name server that answered, empty in a query (code 3, rfc 5001)
+    Nsid(Vec<u8>),
+    // a client cookie of 8 octets, optionally followed by the 8 to 32 octet server cookie
+    // (code 10, rfc 7873)
+    Cookie { client: Vec<u8>, server: Vec<u8> },
+    // the network the query came from, so the answer can be tailored to it (code 8, rfc 7871)
+    ClientSubnet(ClientSubnet),
+    // why a resolver failed or answered the way it did (code 15, rfc 8914)
+    ExtendedError { info_code: u16, extra_text: String },
+    // this many zero octets, so the size of an encrypted message leaks less (code 12, rfc 7830)
+    Padding(u16),
+    // any option we do not know how to decode
+    Unknown(u16, Vec<u8>),
+}
+
+impl EdnsOption {
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::Nsid(_) => 3,
+            EdnsOption::ClientSubnet(_) => 8,
+            EdnsOption::Cookie { .. } => 10,
+            EdnsOption::Padding(_) => 12,
+            EdnsOption::ExtendedError { .. } => 15,
+            EdnsOption::Unknown(code, _) => *code,
+        }
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            EdnsOption::Nsid(id) => id.clone(),
+            EdnsOption::Cookie { client, server } => [client.as_slice(), server].concat(),
+            EdnsOption::Padding(length) => vec![0; *length as usize],
+            EdnsOption::ClientSubnet(subnet) => subnet.data(),
+            EdnsOption::ExtendedError {
+                info_code,
+                extra_text,
+            } => {
+                let mut data = info_code.to_be_bytes().to_vec();
+                data.extend(extra_text.as_bytes());
+                data
+            }
+            EdnsOption::Unknown(_, data) => data.clone(),
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let data = self.data();
+        let mut bytes = Vec::with_capacity(data.len() + 4);
+        bytes.extend(self.code().to_be_bytes());
+        bytes.extend((data.len() as u16).to_be_bytes());
+        bytes.extend(data);
+        bytes
+    }
+}
+
+// the registered INFO-CODEs of extended dns errors (rfc 8914 5.2)
+pub fn extended_error_name(info_code: u16) -> &'static str {
+    match info_code {
+        0 => "Other Error",
+        1 => "Unsupported DNSKEY Algorithm",
+        2 => "Unsupported DS Digest Type",
+        3 => "Stale Answer",
+        4 => "Forged Answer",
+        5 => "DNSSEC Indeterminate",
+        6 => "DNSSEC Bogus",
+        7 => "Signature Expired",
+        8 => "Signature Not Yet Valid",
+        9 => "DNSKEY Missing",
+        10 => "RRSIGs Missing",
+        11 => "No Zone Key Bit Set",
+        12 => "NSEC Missing",
+        13 => "Cached Error",
+        14 => "Not Ready",
+        15 => "Blocked",
+        16 => "Censored",
+        17 => "Filtered",
+        18 => "Prohibited",
+        19 => "Stale NXDomain Answer",
+        20 => "Not Authoritative",
+        21 => "Not Supported",
+        22 => "No Reachable Authority",
+        23 => "Network Error",
+        24 => "Invalid Data",
+        25 => "Signature Expired before Valid",
+        26 => "Too Early",
+        27 => "Unsupported NSEC3 Iterations Value",
+        28 => "Unable to conform to policy",
+        29 => "Synthesized",
+        30 => "Invalid Query Type",
+        _ => "Unknown",
+    }
+}
+
+impl Display for EdnsOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdnsOption::Cookie { client, server } => {
+                write!(f, "COOKIE {}{}", to_hex(client), to_hex(server))
+            }
+            EdnsOption::ClientSubnet(subnet) => write!(f, "ECS {}", subnet),
+            EdnsOption::Nsid(id) => {
+                write!(f, "NSID {} {}", to_hex(id), escape_character_string(id))
+            }
+            EdnsOption::Padding(length) => write!(f, "PADDING {} octets", length),
+            EdnsOption::ExtendedError {
+                info_code,
+                extra_text,
+            } => {
+                write!(f, "EDE {}: {}", info_code, extended_error_name(*info_code))?;
+                if !extra_text.is_empty() {
+                    write!(f, " ({})", extra_text)?;
+                }
+                Ok(())
+            }
+            EdnsOption::Unknown(code, data) => write!(f, "OPTION{} {}", code, to_hex(data)),
+        }
+    }
+}
+
+fn parse_option(buffer: &[u8]) -> VResult<&[u8], EdnsOption> {
+    let (buffer, code) = be_u16(buffer)?;
+    let (buffer, length) = be_u16(buffer)?;
+    let (buffer, data) = take(length)(buffer)?;
+
+    let option = match code {
+        3 => EdnsOption::Nsid(data.to_vec()),
+        12 => EdnsOption::Padding(length),
+        8 => match ClientSubnet::parse(data) {
+            Some(subnet) => EdnsOption::ClientSubnet(subnet),
+            None => EdnsOption::Unknown(code, data.to_vec()),
+        },
+        10 if data.len() == 8 || (16..=40).contains(&data.len()) => EdnsOption::Cookie {
+            client: data[..8].to_vec(),
+            server: data[8..].to_vec(),
+        },
+        15 if data.len() >= 2 => EdnsOption::ExtendedError {
+            info_code: u16::from_be_bytes([data[0], data[1]]),
+            extra_text: String::from_utf8_lossy(&data[2..]).into_owned(),
+        },
+        _ => EdnsOption::Unknown(code, data.to_vec()),
+    };
+    Ok((buffer, option))
+}
+
+// The client subnet as sent in a query, or echoed with the scope the answer is valid for.
+//
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |                    FAMILY                     |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |  SOURCE PREFIX-LENGTH |  SCOPE PREFIX-LENGTH  |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     /                    ADDRESS                    /
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//
+// The address is truncated to the octets covered by the source prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientSubnet {
+    pub address: IpAddr,
+    pub source_prefix: u8,
+    pub scope_prefix: u8,
+}
+
+impl ClientSubnet {
+    fn data(&self) -> Vec<u8> {
+        let (family, octets) = match self.address {
+            IpAddr::V4(address) => (1u16, address.octets().to_vec()),
+            IpAddr::V6(address) => (2u16, address.octets().to_vec()),
+        };
+        let mut data = family.to_be_bytes().to_vec();
+        data.push(self.source_prefix);
+        data.push(self.scope_prefix);
+        data.extend(&octets[..(self.source_prefix as usize).div_ceil(8)]);
+        data
+    }
+
+    fn parse(data: &[u8]) -> Option<ClientSubnet> {
+        let family = u16::from_be_bytes([*data.first()?, *data.get(1)?]);
+        let source_prefix = *data.get(2)?;
+        let scope_prefix = *data.get(3)?;
+        let octets = &data[4..];
+        let address = match family {
+            1 if octets.len() <= 4 => {
+                let mut address = [0; 4];
+                address[..octets.len()].copy_from_slice(octets);
+                IpAddr::V4(Ipv4Addr::from(address))
+            }
+            2 if octets.len() <= 16 => {
+                let mut address = [0; 16];
+                address[..octets.len()].copy_from_slice(octets);
+                IpAddr::V6(Ipv6Addr::from(address))
+            }
+            _ => return None,
+        };
+        Some(ClientSubnet {
+            address,
+            source_prefix,
+            scope_prefix,
+        })
+    }
+}
+
+// reads `address[/prefix]`, the address bits past the prefix are cleared. Without a prefix the
+// lengths recommended for privacy are used, /24 for ipv4 and /56 for ipv6 (rfc 7871 11.1).
+impl FromStr for ClientSubnet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| anyhow!("invalid subnet address: {}", address))?;
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix: u8 = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| anyhow!("invalid prefix length: {}", prefix))?,
+            None if address.is_ipv4() => 24,
+            None => 56,
+        };
+        let address = match address {
+            IpAddr::V4(address) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(address) & mask))
+            }
+            IpAddr::V6(address) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(address) & mask))
+            }
+        };
+        Ok(ClientSubnet {
+            address,
+            source_prefix: prefix,
+            scope_prefix: 0,
+        })
+    }
+}
+
+impl Display for ClientSubnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} scope /{}",
+            self.address, self.source_prefix, self.scope_prefix
+        )
+    }
+}
+
+// parses options until the end of the rdata
+pub fn parse_options(buffer: &[u8]) -> VResult<&[u8], Vec<EdnsOption>> {
+    many0(parse_option)(buffer)
+}
+
+// The contents of an OPT pseudo record (rfc 6891 6.1.3). It reuses the fields of a record: the
+// class holds the udp payload size and the ttl the extended rcode, version and flags.
+//
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |    EXTENDED-RCODE     |        VERSION        |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |DO|                    Z                       |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    pub payload: u16,
+    pub extended_rcode: u8,
+    pub version: u8,
+    pub dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+impl Edns {
+    pub fn new(payload: u16) -> Edns {
+        Edns {
+            payload,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+
+    pub fn from_record(record: &Record) -> Option<Edns> {
+        let RData::OPT(options) = &record.rdata else {
+            return None;
+        };
+        let ttl = record.ttl.as_secs() as u32;
+        Some(Edns {
+            payload: u16::from(&record.qclass),
+            extended_rcode: (ttl >> 24) as u8,
+            version: (ttl >> 16) as u8,
+            dnssec_ok: ttl & 0x8000 != 0,
+            options: options.clone(),
+        })
+    }
+
+    pub fn record(&self) -> Record {
+        let ttl = (self.extended_rcode as u32) << 24
+            | (self.version as u32) << 16
+            | (self.dnssec_ok as u32) << 15;
+        let options = self
+            .options
+            .iter()
+            .map(|o| o.serialize().len())
+            .sum::<usize>();
+        Record::new(
+            Name::root(),
+            QType::OPT,
+            QClass::from(self.payload),
+            Duration::from_secs(ttl.into()),
+            options as u16,
+            RData::OPT(self.options.clone()),
+        )
+    }
+
+    pub fn cookie(&self) -> Option<(&[u8], &[u8])> {
+        self.options.iter().find_map(|o| match o {
+            EdnsOption::Cookie { client, server } => Some((client.as_slice(), server.as_slice())),
+            _ => None,
+        })
+    }
+
+    pub fn nsid(&self) -> Option<&EdnsOption> {
+        self.options
+            .iter()
+            .find(|o| matches!(o, EdnsOption::Nsid(id) if !id.is_empty()))
+    }
+
+    // grows the padding option so the message, currently this long, fills whole blocks
+    // (rfc 8467 4.1)
+    pub fn pad(&mut self, length: usize, block: u16) {
+        let block = block.max(1) as usize;
+        let missing = (block - length % block) % block;
+        for option in self.options.iter_mut() {
+            if let EdnsOption::Padding(padding) = option {
+                *padding += missing as u16;
+            }
+        }
+    }
+
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.options.iter().find_map(|o| match o {
+            EdnsOption::ClientSubnet(subnet) => Some(subnet),
+            _ => None,
+        })
+    }
+
+    pub fn extended_errors(&self) -> impl Iterator<Item = &EdnsOption> {
+        self.options
+            .iter()
+            .filter(|o| matches!(o, EdnsOption::ExtendedError { .. }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::Serialize;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn opt_record_roundtrip() {
+        let mut edns = Edns::new(DEFAULT_PAYLOAD);
+        edns.extended_rcode = 1;
+        edns.dnssec_ok = true;
+        edns.options.push(EdnsOption::ExtendedError {
+            info_code: 18,
+            extra_text: "not allowed".to_owned(),
+        });
+
+        let raw = edns.record().serialize().unwrap();
+        assert_eq!(
+            vec![
+                0x00, // root
+                0x00, 0x29, // OPT
+                0x04, 0xd0, // payload 1232
+                0x01, 0x00, 0x80, 0x00, // extended rcode 1, version 0, DO
+                0x00, 0x11, // rdlength
+                0x00, 0x0f, 0x00, 0x0d, 0x00, 0x12, // EDE 18
+            ],
+            raw[..17]
+        );
+
+        let (_, options) = parse_options(&raw[11..]).unwrap();
+        assert_eq!(edns.options, options);
+        assert_eq!("EDE 18: Prohibited (not allowed)", options[0].to_string());
+    }
+
+    #[test]
+    fn client_subnet_option() {
+        let subnet: ClientSubnet = "203.0.113.77/20".parse().unwrap();
+        assert_eq!("203.0.112.0/20 scope /0", subnet.to_string());
+
+        let option = EdnsOption::ClientSubnet(subnet);
+        let raw = option.serialize();
+        assert_eq!(
+            vec![0x00, 0x08, 0x00, 0x07, 0x00, 0x01, 0x14, 0x00, 0xcb, 0x00, 0x70],
+            raw
+        );
+        assert_eq!(vec![option], parse_options(&raw).unwrap().1);
+
+        let subnet: ClientSubnet = "2001:db8:abcd:12ff::1".parse().unwrap();
+        assert_eq!("2001:db8:abcd:1200::/56 scope /0", subnet.to_string());
+        assert!("192.0.2.1/33".parse::<ClientSubnet>().is_err());
+    }
+
+    #[test]
+    fn nsid_and_padding() {
+        let raw = [
+            0x00, 0x03, 0x00, 0x04, 0x61, 0x6d, 0x73, 0x31, // NSID "ams1"
+            0x00, 0x0c, 0x00, 0x03, 0x00, 0x00, 0x00, // 3 octets of padding
+        ];
+
+        let (_, options) = parse_options(&raw).unwrap();
+        assert_eq!(
+            vec![EdnsOption::Nsid(b"ams1".to_vec()), EdnsOption::Padding(3)],
+            options
+        );
+        assert_eq!("NSID 616D7331 \"ams1\"", options[0].to_string());
+        assert_eq!(
+            raw.to_vec(),
+            options
+                .iter()
+                .flat_map(|o| o.serialize())
+                .collect::<Vec<_>>()
+        );
+
+        let mut edns = Edns::new(DEFAULT_PAYLOAD);
+        edns.options.push(EdnsOption::Padding(0));
+        edns.pad(100, 128);
+        assert_eq!(vec![EdnsOption::Padding(28)], edns.options);
+    }
+}
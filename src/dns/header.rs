@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 use nom::{bits, combinator::map, complete::take, error::Error, sequence::tuple, Finish, IResult};
 
@@ -11,7 +12,7 @@ use super::{Buffer, DeSerialize, Serialize};
 //  +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 //  |                      ID                       |
 //  +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-//  |QR|   Opcode  |AA|TC|RD|RA|   Z    |   RCODE   |
+//  |QR|   Opcode  |AA|TC|RD|RA| Z|AD|CD|   RCODE   |
 //  +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 //  |                    QDCOUNT                    |
 //  +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
@@ -50,6 +51,15 @@ pub struct Header {
     // Z Reserved for future use.  Must be zero in all queries and responses.
     pub z: u8,
 
+    // Authentic Data - set by a validating resolver when all data in the answer and authority
+    // sections has been verified with DNSSEC. In a query it asks for the bit in the response
+    // (rfc 4035 3.2.3, rfc 6840 5.7).
+    pub ad: bool,
+
+    // Checking Disabled - the resolver should not do DNSSEC validation for this query
+    // (rfc 4035 3.2.2).
+    pub cd: bool,
+
     // Response code - this 4 bit field is set as part of responses.
     pub r_code: ResponseCode,
 
@@ -67,6 +77,7 @@ pub struct Header {
 }
 
 impl Header {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: u16,
         qr: bool,
@@ -90,6 +101,8 @@ impl Header {
             rd,
             ra,
             z: 0x00,
+            ad: false,
+            cd: false,
             r_code,
             qd_count,
             an_count,
@@ -98,21 +111,42 @@ impl Header {
         }
     }
 
-    // Default header when making a plain request
-    pub(crate) fn request(id: u16) -> Header {
-        Header::new(
+    // Header of a request, the flags decide what we ask of the server
+    pub(crate) fn request(id: u16, flags: &RequestFlags) -> Header {
+        let mut header = Header::new(
             id,
             false,
             Opcode::Query,
+            flags.aa,
             false,
-            false,
-            true,
+            flags.rd,
             false,
             ResponseCode::NoError,
             1,
             0,
             0,
-            1,
+            0,
+        );
+        header.ad = flags.ad;
+        header.cd = flags.cd;
+        header
+    }
+
+    // Header of the answer to a request, the id, opcode and recursion desired bit are copied
+    pub(crate) fn response(request: &Header, r_code: ResponseCode) -> Header {
+        Header::new(
+            request.id,
+            true,
+            request.opcode.clone(),
+            false,
+            false,
+            request.rd,
+            false,
+            r_code,
+            request.qd_count,
+            0,
+            0,
+            0,
         )
     }
 }
@@ -120,24 +154,16 @@ impl Header {
 impl Serialize for Header {
     fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
         let flags_upper: u8 = (self.qr as u8) << 7;
-        let flags_upper = match self.opcode {
-            Opcode::Query => flags_upper | (Opcode::Query as u8) << 3,
-            Opcode::IQuery => flags_upper | ((Opcode::IQuery as u8) << 3),
-            Opcode::Status => flags_upper | ((Opcode::Status as u8) << 3),
-            _ => flags_upper,
-        };
+        let flags_upper = flags_upper | (u8::from(&self.opcode) & 0x0f) << 3;
 
         let flags_upper = flags_upper | (self.aa as u8) << 2 | (self.tc as u8) << 1 | self.rd as u8;
-        let flags_lower = (self.ra as u8) << 7 | self.z << 4;
-
-        let flags_lower = match self.r_code {
-            ResponseCode::NoError => flags_lower,
-            ResponseCode::FormatError => flags_lower | ResponseCode::FormatError as u8,
-            ResponseCode::ServerFailure => flags_lower | ResponseCode::ServerFailure as u8,
-            ResponseCode::NameError => flags_lower | ResponseCode::NameError as u8,
-            ResponseCode::NotImplemented => flags_lower | ResponseCode::NotImplemented as u8,
-            ResponseCode::Refused => flags_lower | ResponseCode::Refused as u8,
-        };
+        let flags_lower = (self.ra as u8) << 7
+            | (self.z & 0x01) << 6
+            | (self.ad as u8) << 5
+            | (self.cd as u8) << 4;
+
+        // only the lower 4 bits fit in the header, the rest belongs in an OPT record
+        let flags_lower = flags_lower | (u16::from(&self.r_code) & 0x0f) as u8;
 
         Ok(vec![
             (self.id >> 8) as u8,
@@ -161,7 +187,7 @@ type BitInput<'a> = (&'a [u8], usize);
 fn parse_header(input: BitInput) -> IResult<BitInput, Header> {
     let (
         input,
-        (id, qr, opcode, aa, rc, rd, ra, _, r_code, qd_count, an_count, ns_count, ar_count),
+        (id, qr, opcode, aa, rc, rd, ra, z, ad, cd, r_code, qd_count, an_count, ns_count, ar_count),
     ) = tuple((
         parse_u16,
         parse_bool,
@@ -170,7 +196,9 @@ fn parse_header(input: BitInput) -> IResult<BitInput, Header> {
         parse_bool,
         parse_bool,
         parse_bool,
-        skip3,
+        parse_z,
+        parse_bool,
+        parse_bool,
         parse_rcode,
         parse_u16,
         parse_u16,
@@ -178,12 +206,13 @@ fn parse_header(input: BitInput) -> IResult<BitInput, Header> {
         parse_u16,
     ))(input)?;
 
-    Ok((
-        input,
-        Header::new(
-            id, qr, opcode, aa, rc, rd, ra, r_code, qd_count, an_count, ns_count, ar_count,
-        ),
-    ))
+    let mut header = Header::new(
+        id, qr, opcode, aa, rc, rd, ra, r_code, qd_count, an_count, ns_count, ar_count,
+    );
+    header.z = z;
+    header.ad = ad;
+    header.cd = cd;
+    Ok((input, header))
 }
 
 fn parse_u16(i: BitInput) -> IResult<BitInput, u16> {
@@ -199,25 +228,18 @@ fn parse_opcode(i: BitInput) -> IResult<BitInput, Opcode> {
         0 => Opcode::Query,
         1 => Opcode::IQuery,
         2 => Opcode::Status,
-        3 => Opcode::Reserved,
-        _ => panic!("Illegal OpCode value: {:#02x}", bit),
+        4 => Opcode::Notify,
+        5 => Opcode::Update,
+        value => Opcode::Reserved(value),
     })(i)
 }
 
-fn skip3(i: BitInput) -> IResult<BitInput, ()> {
-    map(take(3usize), |_bits: u8| ())(i)
+fn parse_z(i: BitInput) -> IResult<BitInput, u8> {
+    take(1usize)(i)
 }
 
 fn parse_rcode(i: BitInput) -> IResult<BitInput, ResponseCode> {
-    map(take(4usize), |bit: u8| match bit {
-        0 => ResponseCode::NoError,
-        1 => ResponseCode::FormatError,
-        2 => ResponseCode::ServerFailure,
-        3 => ResponseCode::NameError,
-        4 => ResponseCode::NotImplemented,
-        5 => ResponseCode::Refused,
-        _ => panic!("Illegal ResponseCode value: {:#02x}", bit),
-    })(i)
+    map(take(4usize), |bit: u8| ResponseCode::from(bit as u16))(i)
 }
 
 impl<'a> DeSerialize<'a> for Header {
@@ -236,30 +258,86 @@ impl<'a> DeSerialize<'a> for Header {
     }
 }
 
+impl Header {
+    // the names of the flags that are set, in the order they appear on the wire
+    pub(crate) fn flags(&self) -> Vec<&'static str> {
+        [
+            (self.qr, "qr"),
+            (self.aa, "aa"),
+            (self.tc, "tc"),
+            (self.rd, "rd"),
+            (self.ra, "ra"),
+            (self.z != 0, "z"),
+            (self.ad, "ad"),
+            (self.cd, "cd"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+}
+
 impl Display for Header {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "OPCODE: {}, STATUS: {} id: {}\n{}, {}, {},\nQUERY: {}, ANSWERS: {}, AUTHORITY: {}, ADDITIONAL: {}\n",
-            self.opcode, self.r_code, self.id, if self.qr { "qr"} else {""}, if self.rd {"rd"} else {""}, if self.ra {"ra"} else {""}, self.qd_count, self.an_count, self.ns_count, self.ar_count
+            "OPCODE: {}, STATUS: {} id: {}\nflags: {}\nQUERY: {}, ANSWERS: {}, AUTHORITY: {}, ADDITIONAL: {}\n",
+            self.opcode, self.r_code, self.id, self.flags().join(" "), self.qd_count, self.an_count, self.ns_count, self.ar_count
         )
     }
 }
 
+// The flags a client can set on a request, by default only recursion is desired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestFlags {
+    pub rd: bool,
+    pub aa: bool,
+    pub ad: bool,
+    pub cd: bool,
+}
+
+impl Default for RequestFlags {
+    fn default() -> Self {
+        RequestFlags {
+            rd: true,
+            aa: false,
+            ad: false,
+            cd: false,
+        }
+    }
+}
+
 // OPCODE
 //
 // A four bit field that specifies kind of query in this message. This value is set by the
 // originator of a query and copied into the response.  The values are:
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Opcode {
-    // a standard query (QUERY)
-    Query = 0,
-    // an inverse query (IQUERY)
-    IQuery = 1,
-    // a server status request (STATUS)
-    Status = 2,
-    // reserved for future use (value 3-15)
-    Reserved,
+    // a standard query (QUERY), 0
+    Query,
+    // an inverse query (IQUERY), 1
+    IQuery,
+    // a server status request (STATUS), 2
+    Status,
+    // a zone change notification (NOTIFY, rfc 1996), 4
+    Notify,
+    // a dynamic update (UPDATE, rfc 2136), 5
+    Update,
+    // reserved for future use (value 3, 6-15), kept so a response can copy it back
+    Reserved(u8),
+}
+
+impl From<&Opcode> for u8 {
+    fn from(opcode: &Opcode) -> Self {
+        match opcode {
+            Opcode::Query => 0,
+            Opcode::IQuery => 1,
+            Opcode::Status => 2,
+            Opcode::Notify => 4,
+            Opcode::Update => 5,
+            Opcode::Reserved(value) => *value,
+        }
+    }
 }
 
 impl Display for Opcode {
@@ -268,38 +346,179 @@ impl Display for Opcode {
     }
 }
 
-// RCODE Response code - this 4 bit field is set as part of responses.  The values have the following interpretation:
+// RCODE Response code - this 4 bit field is set as part of responses. Together with the upper 8
+// bits carried in an OPT record it forms a 12 bit extended RCODE (rfc 6891 6.1.3). The values
+// are registered in rfc 6895 2.3 and have the following interpretation:
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResponseCode {
-    // No error condition
-    NoError = 0,
+    // 0 No error condition
+    NoError,
+
+    // 1 Format error: The name server was unable to interpret the query.
+    FormatError,
+
+    // 2 Server failure: The name server was unable to process this query due to a problem with the name server.
+    ServerFailure,
+
+    // 3 Name Error: This code signifies that the domain name referenced in the query does not exist.
+    NameError,
+
+    // 4 Not Implemented: The name server does not support the requested kind of query.
+    NotImplemented,
+
+    // 5 Refused: The name server refuses to perform the specified operation for policy reasons.
+    Refused,
+
+    // 6 YX Domain: Some name that ought not to exist, does exist (rfc 2136).
+    YXDomain,
 
-    // Format error: The name server was unable to interpret the query.
-    FormatError = 1,
+    // 7 YX RR Set: Some RRset that ought not to exist, does exist (rfc 2136).
+    YXRRSet,
 
-    // Server failure: The name server was unable to process this query due to a problem with the name server.
-    ServerFailure = 2,
+    // 8 NX RR Set: Some RRset that ought to exist, does not exist (rfc 2136).
+    NXRRSet,
 
-    // Name Error: This code signifies that the domain name referenced in the query does not exist.
-    NameError = 3,
+    // 9 Not Authorized: The server is not authoritative for the zone, or the TSIG signature of the
+    // request could not be verified (rfc 2136, rfc 8945).
+    NotAuth,
 
-    // Not Implemented: The name server does not support the requested kind of query.
-    NotImplemented = 4,
+    // 10 Not Zone: A name used in the prerequisite or update section is not within the zone (rfc 2136).
+    NotZone,
 
-    // Refused: The name server refuses to perform the specified operation for policy reasons.
-    Refused = 5,
+    // 11 DSO-TYPE Not Implemented: The DSO type is unknown to the server (rfc 8490).
+    DsoTypeNI,
+
+    // 16 Bad OPT Version: The EDNS version of the request is not supported (rfc 6891). Inside a
+    // TSIG record the same value means BADSIG.
+    BadVers,
+
+    // 17 Key not recognized (rfc 8945).
+    BadKey,
+
+    // 18 Signature out of time window (rfc 8945).
+    BadTime,
+
+    // 19 Bad TKEY Mode (rfc 2930).
+    BadMode,
+
+    // 20 Duplicate key name (rfc 2930).
+    BadName,
+
+    // 21 Algorithm not supported (rfc 2930).
+    BadAlg,
+
+    // 22 Bad Truncation (rfc 8945).
+    BadTrunc,
+
+    // 23 Bad/missing Server Cookie (rfc 7873).
+    BadCookie,
+
+    // any value that is unassigned or reserved
+    Unknown(u16),
+}
+
+impl From<u16> for ResponseCode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => ResponseCode::NoError,
+            1 => ResponseCode::FormatError,
+            2 => ResponseCode::ServerFailure,
+            3 => ResponseCode::NameError,
+            4 => ResponseCode::NotImplemented,
+            5 => ResponseCode::Refused,
+            6 => ResponseCode::YXDomain,
+            7 => ResponseCode::YXRRSet,
+            8 => ResponseCode::NXRRSet,
+            9 => ResponseCode::NotAuth,
+            10 => ResponseCode::NotZone,
+            11 => ResponseCode::DsoTypeNI,
+            16 => ResponseCode::BadVers,
+            17 => ResponseCode::BadKey,
+            18 => ResponseCode::BadTime,
+            19 => ResponseCode::BadMode,
+            20 => ResponseCode::BadName,
+            21 => ResponseCode::BadAlg,
+            22 => ResponseCode::BadTrunc,
+            23 => ResponseCode::BadCookie,
+            _ => ResponseCode::Unknown(value),
+        }
+    }
+}
+
+impl From<&ResponseCode> for u16 {
+    fn from(value: &ResponseCode) -> Self {
+        match value {
+            ResponseCode::NoError => 0,
+            ResponseCode::FormatError => 1,
+            ResponseCode::ServerFailure => 2,
+            ResponseCode::NameError => 3,
+            ResponseCode::NotImplemented => 4,
+            ResponseCode::Refused => 5,
+            ResponseCode::YXDomain => 6,
+            ResponseCode::YXRRSet => 7,
+            ResponseCode::NXRRSet => 8,
+            ResponseCode::NotAuth => 9,
+            ResponseCode::NotZone => 10,
+            ResponseCode::DsoTypeNI => 11,
+            ResponseCode::BadVers => 16,
+            ResponseCode::BadKey => 17,
+            ResponseCode::BadTime => 18,
+            ResponseCode::BadMode => 19,
+            ResponseCode::BadName => 20,
+            ResponseCode::BadAlg => 21,
+            ResponseCode::BadTrunc => 22,
+            ResponseCode::BadCookie => 23,
+            ResponseCode::Unknown(value) => *value,
+        }
+    }
+}
+
+impl ResponseCode {
+    // the code with the upper 8 bits from an OPT record added to the 4 bits of the header
+    pub fn extend(&self, upper: u8) -> ResponseCode {
+        ResponseCode::from((upper as u16) << 4 | (u16::from(self) & 0x0f))
+    }
 }
 
 impl Display for ResponseCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            ResponseCode::Unknown(value) => write!(f, "RCODE{}", value),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+// A code by the name it is shown with, by the mnemonic of rfc 6895 (NXDOMAIN, SERVFAIL, ...) or
+// by its number, case does not matter.
+impl FromStr for ResponseCode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(value) = s.parse::<u16>() {
+            return Ok(ResponseCode::from(value & 0x0fff));
+        }
+        let mnemonic = match s.to_ascii_uppercase().as_str() {
+            "FORMERR" => Some(ResponseCode::FormatError),
+            "SERVFAIL" => Some(ResponseCode::ServerFailure),
+            "NXDOMAIN" => Some(ResponseCode::NameError),
+            "NOTIMP" => Some(ResponseCode::NotImplemented),
+            _ => None,
+        };
+        mnemonic
+            .or_else(|| {
+                (0..=23)
+                    .map(ResponseCode::from)
+                    .find(|code| code.to_string().eq_ignore_ascii_case(s))
+            })
+            .ok_or_else(|| anyhow::anyhow!("unknown response code {}", s))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Header, Opcode, ResponseCode};
-    use crate::dns::{Buffer, DeSerialize};
+    use crate::dns::{Buffer, DeSerialize, Serialize};
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -331,4 +550,39 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn ad_cd_and_z_flags() {
+        let raw = vec![
+            0x00, 0x02, 0x85, 0x70, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let mut buffer = Buffer {
+            current: &raw,
+            source: &raw,
+        };
+        let (_, header) = Header::deserialize(&mut buffer).unwrap();
+
+        assert!(header.aa && header.ad && header.cd);
+        assert_eq!(1, header.z);
+        assert_eq!(vec!["qr", "aa", "rd", "z", "ad", "cd"], header.flags());
+        assert_eq!(raw, header.serialize().unwrap());
+    }
+
+    #[test]
+    fn reserved_opcode_is_kept() {
+        // opcode 3 with rd set
+        let raw = vec![
+            0x00, 0x02, 0x19, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let mut buffer = Buffer {
+            current: &raw,
+            source: &raw,
+        };
+        let (_, header) = Header::deserialize(&mut buffer).unwrap();
+
+        assert_eq!(Opcode::Reserved(3), header.opcode);
+        assert_eq!(raw, header.serialize().unwrap());
+    }
 }
@@ -1,93 +1,305 @@
 #![allow(dead_code)]
 
+use std::time::Duration;
+
 use anyhow::{Context, Ok};
 use rand::random;
 
 use super::{
-    header::Header, question::Question, record::Record, Buffer, DeSerialize, QClass, QType,
-    Serialize,
+    edns::Edns,
+    header::{self, Header, RequestFlags},
+    name::Name,
+    question::Question,
+    record::{RData, Record},
+    Buffer, DeSerialize, QClass, QType, Serialize,
 };
 
 #[derive(Debug, Clone)]
 pub struct Message {
     pub header: Header,
-    pub question: Question,
+    pub questions: Vec<Question>,
+    // the answer section
     pub records: Vec<Record>,
+    pub authorities: Vec<Record>,
+    pub additionals: Vec<Record>,
 }
 
 impl Serialize for Message {
     fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
-        let mut h = self.header.serialize().context("serializing header")?;
-        let mut b = self.question.serialize().context("serializing body")?;
-
-        h.append(&mut b);
+        // the section counts always follow what is actually in the message
+        let mut header = self.header.clone();
+        header.qd_count = self.questions.len().try_into()?;
+        header.an_count = self.records.len().try_into()?;
+        header.ns_count = self.authorities.len().try_into()?;
+        header.ar_count = self.additionals.len().try_into()?;
+
+        let mut h = header.serialize().context("serializing header")?;
+        for question in &self.questions {
+            let mut b = question.serialize().context("serializing question")?;
+            h.append(&mut b);
+        }
+        for record in self
+            .records
+            .iter()
+            .chain(&self.authorities)
+            .chain(&self.additionals)
+        {
+            let mut r = record.serialize().context("serializing record")?;
+            h.append(&mut r);
+        }
         Ok(h)
     }
 }
 
+fn deserialize_records<'a>(
+    buffer: &'a mut Buffer<'a>,
+    count: u16,
+) -> Result<(&'a mut Buffer<'a>, Vec<Record>), anyhow::Error> {
+    let mut records = Vec::with_capacity(count as usize);
+    let mut buf = buffer;
+    for _ in 0..count {
+        let (buffer, record) = Record::deserialize(buf)?;
+        records.push(record);
+        buf = buffer;
+    }
+    Ok((buf, records))
+}
+
 impl<'a> DeSerialize<'a> for Message {
     type Item = (&'a mut Buffer<'a>, Message);
 
     fn deserialize(buffer: &'a mut Buffer<'a>) -> Result<Self::Item, anyhow::Error> {
         let (buffer, header) = Header::deserialize(buffer)?;
-        let (buffer, question) = Question::deserialize(buffer)?;
 
-        let mut records = Vec::with_capacity(header.an_count as usize);
+        let mut questions = Vec::with_capacity(header.qd_count as usize);
         let mut buf = buffer;
-        for _ in 0..header.an_count {
-            let (buffer, record) = Record::deserialize(buf)?;
-            records.push(record);
+        for _ in 0..header.qd_count {
+            let (buffer, question) = Question::deserialize(buf)?;
+            questions.push(question);
             buf = buffer;
         }
 
-        Ok((
-            buf,
-            Message {
-                header,
-                question,
-                records,
-            },
-        ))
+        let (buf, records) = deserialize_records(buf, header.an_count)?;
+        let (buf, authorities) = deserialize_records(buf, header.ns_count)?;
+        let (buf, additionals) = deserialize_records(buf, header.ar_count)?;
+
+        let mut message = Message {
+            header,
+            questions,
+            records,
+            authorities,
+            additionals,
+        };
+        // the header only carries the lower 4 bits of the rcode, the upper 8 are in the OPT record
+        if let Some(edns) = message.edns() {
+            message.header.r_code = message.header.r_code.extend(edns.extended_rcode);
+        }
+        Ok((buf, message))
+    }
+}
+
+impl TryFrom<&[u8]> for Message {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let mut buffer = Buffer {
+            current: value,
+            source: value,
+        };
+        let (_, message) = Message::deserialize(&mut buffer)?;
+        Ok(message)
     }
 }
 
 impl Message {
-    pub(crate) fn new(name: impl Into<String>, qtype: QType) -> Message {
+    pub(crate) fn new(name: Name, qtype: QType) -> Message {
+        Message::query(name, qtype, QClass::IN)
+    }
+
+    pub(crate) fn query(name: Name, qtype: QType, qclass: QClass) -> Message {
         let id = random::<u16>();
         Self {
-            header: Header::request(id),
-            question: Question::new(name, qtype, QClass::IN),
+            header: Header::request(id, &RequestFlags::default()),
+            questions: vec![Question::new(name, qtype, qclass)],
             records: Vec::with_capacity(0),
+            authorities: Vec::with_capacity(0),
+            additionals: Vec::with_capacity(0),
         }
     }
 
-    pub fn a(name: impl Into<String>) -> Message {
+    // an incremental zone transfer carries the serial the client already has as an SOA record
+    // in the authority section (rfc 1995)
+    pub fn ixfr(zone: Name, serial: u32) -> Message {
+        let mut message = Message::new(zone.clone(), QType::IXFR);
+        message.authorities.push(Record::new(
+            zone,
+            QType::SOA,
+            QClass::IN,
+            Duration::ZERO,
+            0,
+            RData::SOA {
+                mname: Name::root(),
+                rname: Name::root(),
+                serial,
+                refresh: 0,
+                retry: 0,
+                expire: 0,
+                minimum: 0,
+            },
+        ));
+        message
+    }
+
+    // replaces the flags of a request, the id and sections are kept
+    pub fn with_flags(mut self, flags: &RequestFlags) -> Message {
+        self.header = Header::request(self.header.id, flags);
+        self
+    }
+
+    // a dynamic update (rfc 2136 2), the sections are reused under different names: the question
+    // holds the zone, the answers the prerequisites and the authorities the updates.
+    pub fn update(zone: Name, qclass: QClass) -> Message {
+        let mut message = Message::query(zone, QType::SOA, qclass);
+        message.header.opcode = header::Opcode::Update;
+        message.header.rd = false;
+        message
+    }
+
+    // a zone change notification (rfc 1996 3), sent by an authoritative server so it carries the
+    // AA bit, optionally with the new SOA of the zone as a hint in the answer section.
+    pub fn notify(zone: Name, soa: Option<Record>) -> Message {
+        let mut message = Message::query(zone, QType::SOA, QClass::IN);
+        message.header.opcode = header::Opcode::Notify;
+        message.header.aa = true;
+        message.header.rd = false;
+        message.records.extend(soa);
+        message
+    }
+
+    // an empty answer to this message that echoes its questions
+    pub fn reply(&self, r_code: header::ResponseCode) -> Message {
+        Self {
+            header: Header::response(&self.header, r_code),
+            questions: self.questions.clone(),
+            records: Vec::with_capacity(0),
+            authorities: Vec::with_capacity(0),
+            additionals: Vec::with_capacity(0),
+        }
+    }
+
+    // the EDNS information of the OPT record in the additional section, if there is one
+    pub fn edns(&self) -> Option<Edns> {
+        self.additionals.iter().find_map(Edns::from_record)
+    }
+
+    // the first question, every message we send and nearly every response carries exactly one
+    pub fn question(&self) -> Option<&Question> {
+        self.questions.first()
+    }
+
+    pub fn a(name: Name) -> Message {
         Message::new(name, QType::A)
     }
 
-    pub fn aaaa(name: impl Into<String>) -> Message {
+    pub fn aaaa(name: Name) -> Message {
         Message::new(name, QType::AAAA)
     }
 
-    pub fn ns(name: impl Into<String>) -> Message {
+    pub fn ns(name: Name) -> Message {
         Message::new(name, QType::NS)
     }
 
-    pub fn txt(name: impl Into<String>) -> Message {
+    pub fn txt(name: Name) -> Message {
         Message::new(name, QType::TXT)
     }
 
-    pub fn cname(name: impl Into<String>) -> Message {
+    pub fn cname(name: Name) -> Message {
         Message::new(name, QType::CNAME)
     }
 
-    pub fn mx(name: impl Into<String>) -> Message {
+    pub fn mx(name: Name) -> Message {
         Message::new(name, QType::MX)
     }
 
-    pub fn soa(name: impl Into<String>) -> Message {
+    pub fn soa(name: Name) -> Message {
         Message::new(name, QType::SOA)
     }
+
+    pub fn srv(name: Name) -> Message {
+        Message::new(name, QType::SRV)
+    }
+
+    pub fn caa(name: Name) -> Message {
+        Message::new(name, QType::CAA)
+    }
+
+    pub fn https(name: Name) -> Message {
+        Message::new(name, QType::HTTPS)
+    }
+
+    pub fn svcb(name: Name) -> Message {
+        Message::new(name, QType::SVCB)
+    }
+
+    pub fn tlsa(name: Name) -> Message {
+        Message::new(name, QType::TLSA)
+    }
+
+    pub fn sshfp(name: Name) -> Message {
+        Message::new(name, QType::SSHFP)
+    }
+
+    pub fn axfr(name: Name) -> Message {
+        Message::new(name, QType::AXFR)
+    }
+
+    pub fn naptr(name: Name) -> Message {
+        Message::new(name, QType::NAPTR)
+    }
+
+    pub fn spf(name: Name) -> Message {
+        Message::new(name, QType::SPF)
+    }
+
+    pub fn hinfo(name: Name) -> Message {
+        Message::new(name, QType::HINFO)
+    }
+
+    pub fn minfo(name: Name) -> Message {
+        Message::new(name, QType::MINFO)
+    }
+
+    pub fn mb(name: Name) -> Message {
+        Message::new(name, QType::MB)
+    }
+
+    pub fn mg(name: Name) -> Message {
+        Message::new(name, QType::MG)
+    }
+
+    pub fn mr(name: Name) -> Message {
+        Message::new(name, QType::MR)
+    }
+
+    pub fn null(name: Name) -> Message {
+        Message::new(name, QType::NULL)
+    }
+
+    pub fn wks(name: Name) -> Message {
+        Message::new(name, QType::WKS)
+    }
+
+    pub fn rp(name: Name) -> Message {
+        Message::new(name, QType::RP)
+    }
+
+    pub fn afsdb(name: Name) -> Message {
+        Message::new(name, QType::AFSDB)
+    }
+
+    pub fn loc(name: Name) -> Message {
+        Message::new(name, QType::LOC)
+    }
 }
 
 // OPCODE
@@ -137,24 +349,39 @@ mod test {
     #[test]
     fn serilize_header() {
         let query: &[u8] = &[
-            0x00, 0x02, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x02, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         ];
 
-        let q = Message::a("foobar");
+        let q = Message::a("foobar".parse().unwrap());
         let q = q.header;
         let bytes = q.serialize().unwrap();
 
         assert_eq!(&query[2..], &bytes[2..]);
     }
 
+    #[test]
+    fn extended_rcode_from_opt() {
+        let mut response =
+            Message::a("example.com".parse().unwrap()).reply(header::ResponseCode::from(7));
+        let mut edns = Edns::new(1232);
+        edns.extended_rcode = 1;
+        response.additionals.push(edns.record());
+
+        let raw = response.serialize().unwrap();
+        let message = Message::try_from(raw.as_slice()).unwrap();
+
+        assert_eq!(header::ResponseCode::BadCookie, message.header.r_code);
+        assert_eq!("BadCookie", message.header.r_code.to_string());
+    }
+
     #[test]
     fn serilize_query() {
         let query: &[u8] = &[
-            0x00, 0x02, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x06, 0x67,
+            0x00, 0x02, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x67,
             0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01,
         ];
 
-        let q = Message::a("google.com");
+        let q = Message::a("google.com".parse().unwrap());
         let bytes = q.serialize().unwrap();
 
         assert_eq!(&query[2..], &bytes[2..]);
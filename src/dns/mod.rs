@@ -1,13 +1,22 @@
 #![allow(dead_code)]
 
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 
-pub mod bit_parsers;
+use anyhow::anyhow;
+
+pub mod edns;
 pub mod header;
 pub mod message;
+pub mod name;
 pub mod parse_utils;
+pub mod presentation;
 pub mod question;
 pub mod record;
+pub mod serialize_utils;
+pub mod svcb;
+pub mod tsig;
+pub mod zone;
 
 #[derive(Debug)]
 pub struct Buffer<'a> {
@@ -24,50 +33,279 @@ pub trait DeSerialize<'a> {
     fn deserialize(buffer: &'a mut Buffer<'a>) -> Result<Self::Item, anyhow::Error>;
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QType {
-    A = 1,       // 1 a host address
-    NS = 2,      // 2 an authoritative name server
-    MD = 3,      // 3 a mail destination (Obsolete - use MX)
-    MF = 4,      // 4 a mail forwarder (Obsolete - use MX)
-    CNAME = 5,   // 5 the canonical name for an alias
-    SOA = 6,     // 6 marks the start of a zone of authority
-    MB = 7,      // 7 a mailbox domain name (EXPERIMENTAL)
-    MG = 8,      // 8 a mail group member (EXPERIMENTAL)
-    MR = 9,      // 9 a mail rename domain name (EXPERIMENTAL)
-    NULL = 10,   // 10 a null RR (EXPERIMENTAL)
-    WKS = 11,    // 11 a well known service description
-    PTR = 12,    // 12 a domain name pointer
-    HINFO = 13,  // 13 host information
-    MINFO = 14,  // 14 mailbox or mail list information
-    MX = 15,     // 15 mail exchange
-    TXT = 16,    // 16 text strings
-    AAAA = 28,   // 28 ipv6 address
-    AXFR = 252,  // 252 A request for a transfer of an entire zone
-    MAILB = 253, // 253 A request for mailbox-related records (MB, MG or MR)
-    MAILA = 254, // 254 A request for mail agent RRs (Obsolete - see MX)
-    STAR = 255,  // 255 A request for all records
+    A,            // 1 a host address
+    NS,           // 2 an authoritative name server
+    MD,           // 3 a mail destination (Obsolete - use MX)
+    MF,           // 4 a mail forwarder (Obsolete - use MX)
+    CNAME,        // 5 the canonical name for an alias
+    SOA,          // 6 marks the start of a zone of authority
+    MB,           // 7 a mailbox domain name (EXPERIMENTAL)
+    MG,           // 8 a mail group member (EXPERIMENTAL)
+    MR,           // 9 a mail rename domain name (EXPERIMENTAL)
+    NULL,         // 10 a null RR (EXPERIMENTAL)
+    WKS,          // 11 a well known service description
+    PTR,          // 12 a domain name pointer
+    HINFO,        // 13 host information
+    MINFO,        // 14 mailbox or mail list information
+    MX,           // 15 mail exchange
+    TXT,          // 16 text strings
+    RP,           // 17 responsible person (rfc 1183)
+    AFSDB,        // 18 AFS database location (rfc 1183)
+    AAAA,         // 28 ipv6 address
+    LOC,          // 29 location information (rfc 1876)
+    SRV,          // 33 server selection (rfc 2782)
+    NAPTR,        // 35 naming authority pointer (rfc 3403)
+    OPT,          // 41 EDNS pseudo record (rfc 6891)
+    SSHFP,        // 44 ssh key fingerprint (rfc 4255)
+    TLSA,         // 52 TLSA certificate association (rfc 6698)
+    SVCB,         // 64 general purpose service binding (rfc 9460)
+    HTTPS,        // 65 service binding for https (rfc 9460)
+    SPF,          // 99 sender policy framework (rfc 7208, obsoleted by TXT)
+    TSIG,         // 250 transaction signature (rfc 8945)
+    IXFR,         // 251 A request for an incremental transfer of a zone (rfc 1995)
+    AXFR,         // 252 A request for a transfer of an entire zone
+    MAILB,        // 253 A request for mailbox-related records (MB, MG or MR)
+    MAILA,        // 254 A request for mail agent RRs (Obsolete - see MX)
+    STAR,         // 255 A request for all records
+    CAA,          // 257 certification authority authorization (rfc 8659)
+    Unknown(u16), // any other type, presented as TYPE<n> (rfc 3597)
+}
+
+impl From<u16> for QType {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => QType::A,
+            2 => QType::NS,
+            3 => QType::MD,
+            4 => QType::MF,
+            5 => QType::CNAME,
+            6 => QType::SOA,
+            7 => QType::MB,
+            8 => QType::MG,
+            9 => QType::MR,
+            10 => QType::NULL,
+            11 => QType::WKS,
+            12 => QType::PTR,
+            13 => QType::HINFO,
+            14 => QType::MINFO,
+            15 => QType::MX,
+            16 => QType::TXT,
+            17 => QType::RP,
+            18 => QType::AFSDB,
+            28 => QType::AAAA,
+            29 => QType::LOC,
+            33 => QType::SRV,
+            35 => QType::NAPTR,
+            41 => QType::OPT,
+            44 => QType::SSHFP,
+            52 => QType::TLSA,
+            64 => QType::SVCB,
+            65 => QType::HTTPS,
+            99 => QType::SPF,
+            250 => QType::TSIG,
+            251 => QType::IXFR,
+            252 => QType::AXFR,
+            253 => QType::MAILB,
+            254 => QType::MAILA,
+            255 => QType::STAR,
+            257 => QType::CAA,
+            _ => QType::Unknown(value),
+        }
+    }
+}
+
+impl From<&QType> for u16 {
+    fn from(value: &QType) -> Self {
+        match value {
+            QType::A => 1,
+            QType::NS => 2,
+            QType::MD => 3,
+            QType::MF => 4,
+            QType::CNAME => 5,
+            QType::SOA => 6,
+            QType::MB => 7,
+            QType::MG => 8,
+            QType::MR => 9,
+            QType::NULL => 10,
+            QType::WKS => 11,
+            QType::PTR => 12,
+            QType::HINFO => 13,
+            QType::MINFO => 14,
+            QType::MX => 15,
+            QType::TXT => 16,
+            QType::RP => 17,
+            QType::AFSDB => 18,
+            QType::AAAA => 28,
+            QType::LOC => 29,
+            QType::SRV => 33,
+            QType::NAPTR => 35,
+            QType::OPT => 41,
+            QType::SSHFP => 44,
+            QType::TLSA => 52,
+            QType::SVCB => 64,
+            QType::HTTPS => 65,
+            QType::SPF => 99,
+            QType::TSIG => 250,
+            QType::IXFR => 251,
+            QType::AXFR => 252,
+            QType::MAILB => 253,
+            QType::MAILA => 254,
+            QType::STAR => 255,
+            QType::CAA => 257,
+            QType::Unknown(value) => *value,
+        }
+    }
 }
 
 impl Display for QType {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            QType::Unknown(value) => write!(f, "TYPE{}", value),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl FromStr for QType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let qtype = match s.to_ascii_uppercase().as_str() {
+            "A" => QType::A,
+            "NS" => QType::NS,
+            "MD" => QType::MD,
+            "MF" => QType::MF,
+            "CNAME" => QType::CNAME,
+            "SOA" => QType::SOA,
+            "MB" => QType::MB,
+            "MG" => QType::MG,
+            "MR" => QType::MR,
+            "NULL" => QType::NULL,
+            "WKS" => QType::WKS,
+            "PTR" => QType::PTR,
+            "HINFO" => QType::HINFO,
+            "MINFO" => QType::MINFO,
+            "MX" => QType::MX,
+            "TXT" => QType::TXT,
+            "RP" => QType::RP,
+            "AFSDB" => QType::AFSDB,
+            "AAAA" => QType::AAAA,
+            "LOC" => QType::LOC,
+            "SRV" => QType::SRV,
+            "NAPTR" => QType::NAPTR,
+            "OPT" => QType::OPT,
+            "SSHFP" => QType::SSHFP,
+            "TLSA" => QType::TLSA,
+            "SVCB" => QType::SVCB,
+            "HTTPS" => QType::HTTPS,
+            "SPF" => QType::SPF,
+            "TSIG" => QType::TSIG,
+            "IXFR" => QType::IXFR,
+            "AXFR" => QType::AXFR,
+            "MAILB" => QType::MAILB,
+            "MAILA" => QType::MAILA,
+            "ANY" | "STAR" | "*" => QType::STAR,
+            "CAA" => QType::CAA,
+            other => match other.strip_prefix("TYPE").map(str::parse::<u16>) {
+                Some(Ok(value)) => QType::from(value),
+                _ => return Err(anyhow!("unknown type: {}", s)),
+            },
+        };
+        Ok(qtype)
     }
 }
 
 // CLASS fields appear in resource records.  The following CLASS mnemonics
 // and values are defined:
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QClass {
-    IN = 1,     // 1 the Internet
-    CS = 2,     // 2 the CSNET class (obsolete)
-    CH = 3,     // 3 the CHAOS class
-    HS = 4,     // 4 Hesiod [Dyer 87]
-    STAR = 255, // 255 any class
+    IN,           // 1 the Internet
+    CS,           // 2 the CSNET class (obsolete)
+    CH,           // 3 the CHAOS class
+    HS,           // 4 Hesiod [Dyer 87]
+    NONE,         // 254 none, used by dynamic updates to delete records (rfc 2136)
+    ANY,          // 255 any class
+    Unknown(u16), // any other class, presented as CLASS<n> (rfc 3597)
+}
+
+impl From<u16> for QClass {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => QClass::IN,
+            2 => QClass::CS,
+            3 => QClass::CH,
+            4 => QClass::HS,
+            254 => QClass::NONE,
+            255 => QClass::ANY,
+            _ => QClass::Unknown(value),
+        }
+    }
+}
+
+impl From<&QClass> for u16 {
+    fn from(value: &QClass) -> Self {
+        match value {
+            QClass::IN => 1,
+            QClass::CS => 2,
+            QClass::CH => 3,
+            QClass::HS => 4,
+            QClass::NONE => 254,
+            QClass::ANY => 255,
+            QClass::Unknown(value) => *value,
+        }
+    }
+}
+
+impl FromStr for QClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let class = match s.to_ascii_uppercase().as_str() {
+            "IN" => QClass::IN,
+            "CS" => QClass::CS,
+            "CH" | "CHAOS" => QClass::CH,
+            "HS" | "HESIOD" => QClass::HS,
+            "NONE" => QClass::NONE,
+            "ANY" | "*" => QClass::ANY,
+            other => match other.strip_prefix("CLASS").map(str::parse::<u16>) {
+                Some(Ok(value)) => QClass::from(value),
+                _ => return Err(anyhow!("unknown class: {}", s)),
+            },
+        };
+        Ok(class)
+    }
 }
 
 impl Display for QClass {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            QClass::Unknown(value) => write!(f, "CLASS{}", value),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QClass;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn class_from_str() {
+        assert_eq!(QClass::CH, "ch".parse().unwrap());
+        assert_eq!(QClass::ANY, "ANY".parse().unwrap());
+        assert_eq!(QClass::Unknown(42), "CLASS42".parse().unwrap());
+        assert_eq!(QClass::IN, "class1".parse().unwrap());
+        assert!("foo".parse::<QClass>().is_err());
+    }
+
+    #[test]
+    fn unknown_class_display() {
+        assert_eq!("CLASS42", QClass::from(42).to_string());
+        assert_eq!(255, u16::from(&QClass::ANY));
     }
 }
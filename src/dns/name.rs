@@ -0,0 +1,426 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use nom::bytes::complete::take;
+use nom::error::{Error, ErrorKind, ParseError};
+use nom::number::complete::u8;
+use nom::Err;
+
+use super::parse_utils::{is_ptr, ptr_value, VResult};
+use super::Serialize;
+
+const MAX_LABEL_LENGTH: usize = 63;
+const MAX_NAME_LENGTH: usize = 255;
+// a name of at most 255 octets can not need more pointers than this, more means a loop
+const MAX_POINTERS: usize = 128;
+
+// A domain name, kept as the raw labels it is made of so labels containing dots, spaces or any
+// other octet survive (rfc 2181 11). Names compare case insensitively and order canonically
+// (rfc 4034 6.1), the case they were received in is kept for display.
+//
+// The presentation format escapes special characters with a backslash and non printable octets
+// as three decimal digits (rfc 1035 5.1), the root label is left out except for the root itself
+// which is shown as a single dot:
+//
+// [b"a.b", b"example", b"com"] -> a\.b.example.com
+// [b"my host", b"com"]         -> my\032host.com
+//
+#[derive(Debug, Clone, Default)]
+pub struct Name {
+    labels: Vec<Vec<u8>>,
+}
+
+impl Name {
+    pub fn root() -> Name {
+        Name::default()
+    }
+
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> Result<Name, anyhow::Error> {
+        for label in &labels {
+            if label.is_empty() {
+                return Err(anyhow!("empty label in name"));
+            }
+            if label.len() > MAX_LABEL_LENGTH {
+                return Err(anyhow!(
+                    "label: {} exceeds maximum length of 63",
+                    escape_label(label)
+                ));
+            }
+        }
+        let name = Name { labels };
+        if name.wire_length() > MAX_NAME_LENGTH {
+            return Err(anyhow!(
+                "name: {} exceeds maximum length of 255 octets",
+                name
+            ));
+        }
+        Ok(name)
+    }
+
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    // the length of the uncompressed name on the wire, every label is prefixed by a length octet
+    // and the name ends with the empty root label
+    pub fn wire_length(&self) -> usize {
+        self.labels
+            .iter()
+            .map(|label| label.len() + 1)
+            .sum::<usize>()
+            + 1
+    }
+
+    // the same name in lower case, the canonical form used in signatures (rfc 4034 6.2)
+    pub fn to_lowercase(&self) -> Name {
+        Name {
+            labels: self
+                .labels
+                .iter()
+                .map(|label| label.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+}
+
+fn escape_label(label: &[u8]) -> String {
+    let mut s = String::with_capacity(label.len());
+    for b in label {
+        match b {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                s.push('\\');
+                s.push(*b as char);
+            }
+            0x21..=0x7e => s.push(*b as char),
+            _ => s.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    s
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_root() {
+            return write!(f, ".");
+        }
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| escape_label(label))
+            .collect::<Vec<_>>();
+        write!(f, "{}", labels.join("."))
+    }
+}
+
+// parses a name in presentation format, a trailing dot is optional as names are always taken to
+// be fully qualified. An empty string or a single dot is the root.
+impl FromStr for Name {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s == "." {
+            return Ok(Name::root());
+        }
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        let mut bytes = s.bytes();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'.' => {
+                    if label.is_empty() {
+                        return Err(anyhow!("name: {} contains an empty label", s));
+                    }
+                    labels.push(std::mem::take(&mut label));
+                }
+                b'\\' => match bytes.next() {
+                    Some(d) if d.is_ascii_digit() => {
+                        let digits = [Some(d), bytes.next(), bytes.next()];
+                        let value = digits
+                            .iter()
+                            .try_fold(0u16, |value, digit| match digit {
+                                Some(d) if d.is_ascii_digit() => {
+                                    Some(value * 10 + (d - b'0') as u16)
+                                }
+                                _ => None,
+                            })
+                            .and_then(|value| u8::try_from(value).ok())
+                            .ok_or_else(|| anyhow!("name: {} has an invalid \\DDD escape", s))?;
+                        label.push(value);
+                    }
+                    Some(c) => label.push(c),
+                    None => return Err(anyhow!("name: {} ends with a backslash", s)),
+                },
+                _ => label.push(b),
+            }
+        }
+        if !label.is_empty() {
+            labels.push(label);
+        }
+        Name::from_labels(labels)
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(&other.labels)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for Name {}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        other.parse::<Name>().is_ok_and(|other| *self == other)
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<Name> for &str {
+    fn eq(&self, other: &Name) -> bool {
+        other == self
+    }
+}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.labels.len().hash(state);
+        for label in &self.labels {
+            label.to_ascii_lowercase().hash(state);
+        }
+    }
+}
+
+// canonical order compares the labels from the root down, each label as lower cased octets where
+// a shorter label sorts before a longer one it is a prefix of (rfc 4034 6.1)
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ours = self.labels.iter().rev();
+        let theirs = other.labels.iter().rev();
+        for (a, b) in ours.zip(theirs) {
+            let order = a
+                .iter()
+                .map(u8::to_ascii_lowercase)
+                .cmp(b.iter().map(u8::to_ascii_lowercase));
+            if order != Ordering::Equal {
+                return order;
+            }
+        }
+        self.labels.len().cmp(&other.labels.len())
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// serializes a name into its wire format, each label prefixed by its length and the whole name
+// ending with a null termination. Names are written uncompressed. Example:
+//
+// google.com -> 6google3com0
+//
+impl Serialize for Name {
+    fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut bytes = Vec::with_capacity(self.wire_length());
+        for label in &self.labels {
+            bytes.push(label.len() as u8);
+            bytes.extend(label);
+        }
+        bytes.push(0);
+        Ok(bytes)
+    }
+}
+
+fn malformed(input: &[u8], kind: ErrorKind) -> Err<Error<&[u8]>> {
+    Err::Error(Error::from_error_kind(input, kind))
+}
+
+// deserializes a name, labels are prefixed by their length and the name ends with a null
+// termination, or with a pointer to where the rest of the name is found earlier in the message
+// (rfc 1035 4.1.4). The returned buffer continues after the name as it appears in the buffer,
+// that is after the first pointer.
+//
+// 0x03 0x6e 0x73 0x31 0xc0 0x02 -> ns1 + the name at offset 2 in source
+//
+pub fn parse_name<'a>(buffer: &'a [u8], source: &'a [u8]) -> VResult<&'a [u8], Name> {
+    let mut labels = Vec::new();
+    let mut length = 1;
+    let mut pointers = 0;
+    let mut rest = None;
+    let mut current = buffer;
+    loop {
+        let (after, octet) = u8(current)?;
+        if octet == 0 {
+            current = after;
+            break;
+        }
+        if is_ptr(octet) {
+            let (after, offset) = ptr_value(current)?;
+            pointers += 1;
+            if pointers > MAX_POINTERS || offset >= source.len() {
+                return Err(malformed(current, ErrorKind::Verify));
+            }
+            rest.get_or_insert(after);
+            current = &source[offset..];
+            continue;
+        }
+        // the other label types of rfc 6891 are not in use
+        if octet as usize > MAX_LABEL_LENGTH {
+            return Err(malformed(current, ErrorKind::Verify));
+        }
+        let (after, label) = take(octet)(after)?;
+        length += label.len() + 1;
+        if length > MAX_NAME_LENGTH {
+            return Err(malformed(current, ErrorKind::TooLarge));
+        }
+        labels.push(label.to_vec());
+        current = after;
+    }
+    Ok((rest.unwrap_or(current), Name { labels }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn name(s: &str) -> Name {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn serialize_name_to_labels() {
+        let expected = vec![
+            0x06, 0x67, 0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
+        ];
+        assert_eq!(expected, name("google.com").serialize().unwrap());
+        assert_eq!(vec![0x00], Name::root().serialize().unwrap());
+    }
+
+    #[test]
+    fn test_name_parsing() {
+        let buffer = vec![
+            0x07, 0x74, 0x77, 0x69, 0x74, 0x74, 0x65, 0x72, 0x03, 0x63, 0x6f, 0x6d, 0x00,
+        ];
+
+        let (_, actual) = parse_name(&buffer, &buffer).unwrap();
+
+        assert_eq!("twitter.com", actual.to_string())
+    }
+
+    #[test]
+    fn parse_name_multiple_tokens() {
+        let buffer = vec![
+            0x04, 0x62, 0x6c, 0x6f, 0x67, 0x0c, 0x74, 0x6f, 0x65, 0x72, 0x6b, 0x74, 0x75, 0x6d,
+            0x6c, 0x61, 0x72, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
+        ];
+
+        let (buffer, actual) = parse_name(&buffer, &buffer).unwrap();
+
+        assert_eq!("blog.toerktumlare.com", actual.to_string());
+        assert_eq!(0, buffer.len());
+    }
+
+    #[test]
+    fn parse_string_with_pointers() {
+        let source = vec![
+            0x00, 0x00, 0x06, 0x67, 0x6f, 0x6f, 0x67, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00,
+            0x00, 0x00, 0x03, 0x6e, 0x73, 0x31, 0xc0, 0x02, 0xff,
+        ];
+
+        let (rest, actual) = parse_name(&source[16..], &source).unwrap();
+        assert_eq!("ns1.google.com", actual.to_string());
+        assert_eq!(vec![0xff], rest);
+    }
+
+    #[test]
+    fn reject_pointer_loops_and_long_names() {
+        let source = vec![0xc0, 0x00];
+        assert!(parse_name(&source, &source).is_err());
+
+        let mut source = Vec::new();
+        for _ in 0..5 {
+            source.push(63);
+            source.extend([b'a'; 63]);
+        }
+        source.push(0);
+        assert!(parse_name(&source, &source).is_err());
+        let label = "a".repeat(63);
+        assert!(Name::from_str(&[&*label, &label, &label, &label].join(".")).is_err());
+        assert!(Name::from_str(&"a".repeat(64)).is_err());
+        let longest = [&label[2..], &label, &label, &label].join(".");
+        assert_eq!(255, name(&longest).wire_length());
+    }
+
+    #[test]
+    fn presentation_escapes() {
+        let escaped = name("a\\.b.my\\032host.example.com.");
+        assert_eq!(
+            &[b"a.b".to_vec(), b"my host".to_vec()],
+            &escaped.labels()[..2]
+        );
+        assert_eq!("a\\.b.my\\032host.example.com", escaped.to_string());
+        assert_eq!(escaped, name(&escaped.to_string()));
+        assert_eq!(".", Name::root().to_string());
+        assert_eq!(Name::root(), name("."));
+        assert!(Name::from_str("a..b").is_err());
+        assert!(Name::from_str("a\\256").is_err());
+
+        let raw = escaped.serialize().unwrap();
+        let (_, actual) = parse_name(&raw, &raw).unwrap();
+        assert_eq!(escaped.labels(), actual.labels());
+    }
+
+    #[test]
+    fn compare_case_insensitively_in_canonical_order() {
+        assert_eq!(name("Example.COM"), name("example.com"));
+        assert!(name("example.com") == "EXAMPLE.com.");
+
+        // the example of rfc 4034 6.1
+        let mut names = vec![
+            name("\\200.z.example"),
+            name("zABC.a.EXAMPLE"),
+            name("z.example"),
+            name("\\001.z.example"),
+            name("*.z.example"),
+            name("a.example"),
+            name("Z.a.example"),
+            name("yljkjljk.a.example"),
+            name("example"),
+        ];
+        names.sort();
+        let sorted = names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "example",
+                "a.example",
+                "yljkjljk.a.example",
+                "Z.a.example",
+                "zABC.a.EXAMPLE",
+                "z.example",
+                "\\001.z.example",
+                "*.z.example",
+                "\\200.z.example",
+            ],
+            sorted
+        );
+    }
+}
@@ -0,0 +1,664 @@
+use std::net::Ipv4Addr;
+use std::str;
+
+use anyhow::{anyhow, Context, Result};
+
+use super::name::Name;
+use super::record::{rdata_from_wire, RData};
+use super::serialize_utils::{escape_character_string, to_hex};
+use super::svcb::SvcParam;
+use super::zone::parse_ttl;
+use super::{QType, Serialize};
+
+// what LOC leaves out defaults to (rfc 1876 3): a size of 1m, 10km horizontal and 10m vertical
+// precision, in centimeters
+const LOC_SIZE: u64 = 100;
+const LOC_HORIZONTAL: u64 = 1_000_000;
+const LOC_VERTICAL: u64 = 1_000;
+
+// splits a line of presentation format (rfc 1035 5.1) into its tokens. Tokens are separated by
+// white space, a quoted token may contain white space and `;` starts a comment that runs until
+// the end of the line. Escapes are kept as they are, what they mean depends on the field: in a
+// name `\.` is a dot within a label, everywhere else it is just a dot. `unescape` resolves them.
+pub fn tokenize(line: &str) -> Result<Vec<Vec<u8>>> {
+    let mut tokens = Vec::new();
+    let mut bytes = line.bytes().peekable();
+    while let Some(b) = bytes.peek().copied() {
+        match b {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                bytes.next();
+            }
+            b';' => break,
+            _ => {
+                let quoted = b == b'"';
+                if quoted {
+                    bytes.next();
+                }
+                let mut token = Vec::new();
+                let mut closed = !quoted;
+                while let Some(b) = bytes.next() {
+                    match b {
+                        b'"' if quoted => {
+                            closed = true;
+                            break;
+                        }
+                        b';' if !quoted => {
+                            // a comment ends both the token and the line
+                            tokens.push(token);
+                            return Ok(tokens);
+                        }
+                        b' ' | b'\t' | b'\r' | b'\n' if !quoted => break,
+                        b'\\' => {
+                            let escaped = bytes.next().ok_or_else(|| anyhow!("dangling escape"))?;
+                            token.extend([b, escaped]);
+                        }
+                        _ => token.push(b),
+                    }
+                }
+                if !closed {
+                    return Err(anyhow!("unterminated quoted string in: {}", line));
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// resolves the escapes of a token, `\X` is the character X and `\DDD` the octet with that
+// decimal value
+pub fn unescape(token: &[u8]) -> Result<Vec<u8>> {
+    let mut value = Vec::with_capacity(token.len());
+    let mut bytes = token.iter().copied();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => value.push(unescape_one(&mut bytes)?),
+            _ => value.push(b),
+        }
+    }
+    Ok(value)
+}
+
+fn unescape_one(bytes: &mut impl Iterator<Item = u8>) -> Result<u8> {
+    let first = bytes.next().ok_or_else(|| anyhow!("dangling escape"))?;
+    if !first.is_ascii_digit() {
+        return Ok(first);
+    }
+    let digits = [Some(first), bytes.next(), bytes.next()];
+    let mut value: u16 = 0;
+    for digit in digits {
+        match digit {
+            Some(d) if d.is_ascii_digit() => value = value * 10 + (d - b'0') as u16,
+            _ => return Err(anyhow!("\\DDD escape needs three digits")),
+        }
+    }
+    value
+        .try_into()
+        .map_err(|_| anyhow!("escaped value {} exceeds 255", value))
+}
+
+// Reads a name as it is written in a zone file: `@` is the origin, a name that ends in an
+// unescaped dot is fully qualified and any other name is relative to the origin (rfc 1035 5.1).
+// Without an origin every name is taken to be fully qualified.
+pub fn parse_name(token: &[u8], origin: Option<&Name>) -> Result<Name> {
+    let text = str::from_utf8(token).context("name is not valid utf-8")?;
+    let Some(origin) = origin else {
+        return text.parse();
+    };
+    if text == "@" {
+        return Ok(origin.clone());
+    }
+    let escapes = text
+        .trim_end_matches('.')
+        .bytes()
+        .rev()
+        .take_while(|b| *b == b'\\')
+        .count();
+    if text.ends_with('.') && escapes % 2 == 0 {
+        return text.parse();
+    }
+    let name: Name = text.parse()?;
+    let labels = name
+        .labels()
+        .iter()
+        .chain(origin.labels())
+        .cloned()
+        .collect();
+    Name::from_labels(labels)
+}
+
+// a cursor over the tokens of a single record's rdata
+struct Fields<'a> {
+    qtype: &'a QType,
+    tokens: std::slice::Iter<'a, Vec<u8>>,
+    origin: Option<&'a Name>,
+}
+
+impl<'a> Fields<'a> {
+    fn raw(&mut self, what: &str) -> Result<&'a [u8]> {
+        self.tokens
+            .next()
+            .map(|t| t.as_slice())
+            .ok_or_else(|| anyhow!("{} record is missing its {}", self.qtype, what))
+    }
+
+    fn next(&mut self, what: &str) -> Result<Vec<u8>> {
+        unescape(self.raw(what)?)
+    }
+
+    fn str(&mut self, what: &str) -> Result<String> {
+        let token = self.next(what)?;
+        String::from_utf8(token).with_context(|| format!("{} is not valid utf-8", what))
+    }
+
+    fn number<T: str::FromStr>(&mut self, what: &str) -> Result<T> {
+        let value = self.str(what)?;
+        value
+            .parse()
+            .map_err(|_| anyhow!("invalid {} for {} record: {}", what, self.qtype, value))
+    }
+
+    // a time in seconds, or with units as in a ttl: 3h, 1w
+    fn seconds(&mut self, what: &str) -> Result<u32> {
+        let value = self.str(what)?;
+        parse_ttl(&value)
+            .map_err(|_| anyhow!("invalid {} for {} record: {}", what, self.qtype, value))
+    }
+
+    fn name(&mut self, what: &str) -> Result<Name> {
+        let token = self.raw(what)?;
+        parse_name(token, self.origin).with_context(|| format!("invalid {}", what))
+    }
+
+    fn peek(&self) -> Option<&'a [u8]> {
+        self.tokens.clone().next().map(|t| t.as_slice())
+    }
+
+    // the rest of the tokens as one run of hex, it may be split by white space (rfc 6698 2.2)
+    fn hex(&mut self, what: &str) -> Result<Vec<u8>> {
+        let text = self.rest()?.concat();
+        if text.is_empty() {
+            return Err(anyhow!("{} record is missing its {}", self.qtype, what));
+        }
+        from_hex(&text)
+            .ok_or_else(|| anyhow!("invalid {} for {} record, not hex", what, self.qtype))
+    }
+
+    // a LOC latitude or longitude: degrees, optionally minutes and seconds, and the hemisphere
+    fn coordinate(&mut self, what: &str, positive: u8, negative: u8) -> Result<u32> {
+        let degrees: u32 = self.number(what)?;
+        let mut parts = [0.0f64; 2];
+        for part in parts.iter_mut() {
+            match self.peek() {
+                Some(token) if token.first().is_some_and(u8::is_ascii_digit) => {
+                    *part = self.number(what)?;
+                }
+                _ => break,
+            }
+        }
+        let limit = if positive == b'N' { 90 } else { 180 };
+        let milliseconds = degrees as i64 * 3_600_000
+            + (parts[0] * 60_000.0).round() as i64
+            + (parts[1] * 1000.0).round() as i64;
+        if degrees > limit || milliseconds > limit as i64 * 3_600_000 {
+            return Err(anyhow!("invalid {} for LOC record", what));
+        }
+        let hemisphere = self.str(what)?.to_ascii_uppercase();
+        let offset = match hemisphere.as_bytes() {
+            [h] if *h == positive => milliseconds,
+            [h] if *h == negative => -milliseconds,
+            _ => return Err(anyhow!("invalid hemisphere for LOC record: {}", hemisphere)),
+        };
+        Ok(((1i64 << 31) + offset) as u32)
+    }
+
+    // a LOC distance in meters, the unit can be left out
+    fn meters(&mut self, what: &str) -> Result<f64> {
+        let value = self.str(what)?;
+        value
+            .strip_suffix(['m', 'M'])
+            .unwrap_or(&value)
+            .parse()
+            .map_err(|_| anyhow!("invalid {} for LOC record: {}", what, value))
+    }
+
+    // a LOC size or precision, encoded as a mantissa and a power of ten of centimeters
+    fn precision(&mut self, what: &str, default: u64) -> Result<u8> {
+        let centimeters = match self.peek() {
+            Some(_) => (self.meters(what)? * 100.0).round(),
+            None => default as f64,
+        };
+        if !(0.0..=9e9).contains(&centimeters) {
+            return Err(anyhow!("invalid {} for LOC record", what));
+        }
+        let mut mantissa = centimeters as u64;
+        let mut exponent = 0;
+        while mantissa > 9 {
+            mantissa /= 10;
+            exponent += 1;
+        }
+        Ok((mantissa << 4 | exponent) as u8)
+    }
+
+    fn rest(&mut self) -> Result<Vec<Vec<u8>>> {
+        self.tokens.by_ref().map(|t| unescape(t)).collect()
+    }
+
+    fn svc_params(&mut self) -> Result<Vec<SvcParam>> {
+        self.tokens
+            .by_ref()
+            .map(|t| str::from_utf8(t)?.parse())
+            .collect()
+    }
+
+    fn done(&mut self) -> Result<()> {
+        match self.tokens.next() {
+            None => Ok(()),
+            Some(extra) => Err(anyhow!(
+                "unexpected trailing data in {} record: {}",
+                self.qtype,
+                String::from_utf8_lossy(extra)
+            )),
+        }
+    }
+}
+
+// builds the rdata of a record from its presentation format tokens, the inverse of the Display
+// implementation of RData.
+pub fn parse_rdata(qtype: &QType, tokens: &[Vec<u8>]) -> Result<RData> {
+    parse_rdata_in(qtype, tokens, None)
+}
+
+// the same for rdata in a zone file, where the names in it can be relative to the origin
+pub fn parse_rdata_in(qtype: &QType, tokens: &[Vec<u8>], origin: Option<&Name>) -> Result<RData> {
+    let mut fields = Fields {
+        qtype,
+        tokens: tokens.iter(),
+        origin,
+    };
+    if fields.peek() == Some(b"\\#") {
+        return parse_generic(&mut fields);
+    }
+    let rdata = match qtype {
+        QType::A => RData::A(fields.number("address")?),
+        QType::AAAA => RData::AAAA(fields.number("address")?),
+        QType::NS => RData::NS(fields.name("name server")?),
+        QType::CNAME => RData::CNAME(fields.name("canonical name")?),
+        QType::MB => RData::MB(fields.name("mailbox")?),
+        QType::MG => RData::MG(fields.name("mailbox")?),
+        QType::MR => RData::MR(fields.name("mailbox")?),
+        QType::PTR => RData::PTR(fields.name("domain name")?),
+        QType::MX => RData::MX {
+            preference: fields.number("preference")?,
+            exchange: fields.name("exchange")?,
+        },
+        QType::TXT | QType::SPF => {
+            let strings = fields.rest()?;
+            if strings.is_empty() {
+                return Err(anyhow!("{} record needs at least one string", qtype));
+            }
+            if let Some(long) = strings.iter().find(|s| s.len() > 255) {
+                return Err(anyhow!(
+                    "character-string of {} octets exceeds maximum length of 255",
+                    long.len()
+                ));
+            }
+            match qtype {
+                QType::TXT => RData::TXT(strings),
+                _ => RData::SPF(strings),
+            }
+        }
+        QType::SOA => RData::SOA {
+            mname: fields.name("primary name server")?,
+            rname: fields.name("responsible mailbox")?,
+            serial: fields.number("serial")?,
+            refresh: fields.seconds("refresh")?,
+            retry: fields.seconds("retry")?,
+            expire: fields.seconds("expire")?,
+            minimum: fields.seconds("minimum")?,
+        },
+        QType::SRV => RData::SRV {
+            priority: fields.number("priority")?,
+            weight: fields.number("weight")?,
+            port: fields.number("port")?,
+            target: fields.name("target")?,
+        },
+        QType::CAA => RData::CAA {
+            flags: fields.number("flags")?,
+            tag: fields.str("tag")?,
+            value: fields.next("value")?,
+        },
+        QType::SVCB | QType::HTTPS => {
+            let priority = fields.number("priority")?;
+            let target = fields.name("target")?;
+            let params = fields.svc_params()?;
+            match qtype {
+                QType::SVCB => RData::SVCB {
+                    priority,
+                    target,
+                    params,
+                },
+                _ => RData::HTTPS {
+                    priority,
+                    target,
+                    params,
+                },
+            }
+        }
+        QType::TLSA => RData::TLSA {
+            usage: fields.number("usage")?,
+            selector: fields.number("selector")?,
+            matching_type: fields.number("matching type")?,
+            data: fields.hex("certificate association data")?,
+        },
+        QType::SSHFP => RData::SSHFP {
+            algorithm: fields.number("algorithm")?,
+            fp_type: fields.number("fingerprint type")?,
+            fingerprint: fields.hex("fingerprint")?,
+        },
+        QType::NAPTR => RData::NAPTR {
+            order: fields.number("order")?,
+            preference: fields.number("preference")?,
+            flags: fields.next("flags")?,
+            services: fields.next("services")?,
+            regexp: fields.next("regexp")?,
+            replacement: fields.name("replacement")?,
+        },
+        QType::HINFO => RData::HINFO {
+            cpu: fields.next("cpu")?,
+            os: fields.next("os")?,
+        },
+        QType::MINFO => RData::MINFO {
+            rmailbx: fields.name("responsible mailbox")?,
+            emailbx: fields.name("error mailbox")?,
+        },
+        QType::WKS => {
+            let address: Ipv4Addr = fields.number("address")?;
+            let protocol = match fields.str("protocol")?.to_ascii_uppercase().as_str() {
+                "TCP" => 6,
+                "UDP" => 17,
+                other => other
+                    .parse()
+                    .map_err(|_| anyhow!("invalid protocol for WKS record: {}", other))?,
+            };
+            let mut bitmap = Vec::new();
+            while fields.peek().is_some() {
+                let port: u16 = fields.number("port")?;
+                let (octet, bit) = (port as usize / 8, port % 8);
+                if bitmap.len() <= octet {
+                    bitmap.resize(octet + 1, 0);
+                }
+                bitmap[octet] |= 0x80 >> bit;
+            }
+            RData::WKS {
+                address,
+                protocol,
+                bitmap,
+            }
+        }
+        QType::RP => RData::RP {
+            mbox: fields.name("mailbox")?,
+            txt: fields.name("text name")?,
+        },
+        QType::AFSDB => RData::AFSDB {
+            subtype: fields.number("subtype")?,
+            hostname: fields.name("hostname")?,
+        },
+        QType::LOC => {
+            let latitude = fields.coordinate("latitude", b'N', b'S')?;
+            let longitude = fields.coordinate("longitude", b'E', b'W')?;
+            // altitude counts centimeters from 100km below the WGS 84 spheroid
+            let altitude = (fields.meters("altitude")? * 100.0).round() + 10_000_000.0;
+            if !(0.0..=u32::MAX as f64).contains(&altitude) {
+                return Err(anyhow!("invalid altitude for LOC record"));
+            }
+            RData::LOC {
+                version: 0,
+                size: fields.precision("size", LOC_SIZE)?,
+                horiz_pre: fields.precision("horizontal precision", LOC_HORIZONTAL)?,
+                vert_pre: fields.precision("vertical precision", LOC_VERTICAL)?,
+                latitude,
+                longitude,
+                altitude: altitude as u32,
+            }
+        }
+        QType::NULL => {
+            return Err(anyhow!(
+                "NULL records can only be written as \\# LENGTH HEX"
+            ))
+        }
+        _ => return Err(anyhow!("{} records can not be read from text", qtype)),
+    };
+    fields.done()?;
+    Ok(rdata)
+}
+
+// The generic format any type can be written in, its length and octets as hex: \# 4 C0000201
+// (rfc 3597 5).
+fn parse_generic(fields: &mut Fields) -> Result<RData> {
+    fields.raw("\\#")?;
+    let length: usize = fields.number("rdata length")?;
+    let octets = match length {
+        0 => Vec::new(),
+        _ => fields.hex("rdata")?,
+    };
+    fields.done()?;
+    if octets.len() != length {
+        return Err(anyhow!(
+            "{} record says {} octets but has {}",
+            fields.qtype,
+            length,
+            octets.len()
+        ));
+    }
+    match fields.qtype {
+        QType::NULL => Ok(RData::NULL(octets)),
+        QType::OPT | QType::TSIG => Err(anyhow!(
+            "{} records can not be read from text",
+            fields.qtype
+        )),
+        qtype => rdata_from_wire(qtype, &octets),
+    }
+}
+
+fn from_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    text.chunks(2)
+        .map(|pair| u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+// a name as a zone file needs it when there is no origin to be relative to
+fn absolute(name: &Name) -> String {
+    match name.is_root() {
+        true => ".".to_owned(),
+        false => format!("{}.", name),
+    }
+}
+
+// Writes rdata the way it is read from a zone file, the same as its Display except that names
+// end in a dot so they are read back fully qualified and SOA fields are not separated by commas.
+pub fn rdata_text(rdata: &RData) -> String {
+    match rdata {
+        RData::CNAME(name)
+        | RData::NS(name)
+        | RData::MB(name)
+        | RData::MG(name)
+        | RData::MR(name)
+        | RData::PTR(name) => absolute(name),
+        RData::MX {
+            preference,
+            exchange,
+        } => format!("{} {}", preference, absolute(exchange)),
+        RData::SOA {
+            mname,
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        } => format!(
+            "{} {} {serial} {refresh} {retry} {expire} {minimum}",
+            absolute(mname),
+            absolute(rname)
+        ),
+        RData::SRV {
+            priority,
+            weight,
+            port,
+            target,
+        } => format!("{priority} {weight} {port} {}", absolute(target)),
+        RData::SVCB {
+            priority,
+            target,
+            params,
+        }
+        | RData::HTTPS {
+            priority,
+            target,
+            params,
+        } => {
+            let mut text = format!("{priority} {}", absolute(target));
+            for param in params {
+                text.push_str(&format!(" {param}"));
+            }
+            text
+        }
+        RData::NAPTR {
+            order,
+            preference,
+            flags,
+            services,
+            regexp,
+            replacement,
+        } => format!(
+            "{order} {preference} {} {} {} {}",
+            escape_character_string(flags),
+            escape_character_string(services),
+            escape_character_string(regexp),
+            absolute(replacement)
+        ),
+        RData::MINFO { rmailbx, emailbx } => {
+            format!("{} {}", absolute(rmailbx), absolute(emailbx))
+        }
+        RData::RP { mbox, txt } => format!("{} {}", absolute(mbox), absolute(txt)),
+        RData::AFSDB { subtype, hostname } => format!("{subtype} {}", absolute(hostname)),
+        RData::TLSA { data, .. }
+        | RData::SSHFP {
+            fingerprint: data, ..
+        } if data.is_empty() => {
+            // empty hex can not be read back, the generic format can
+            let octets = rdata.serialize().unwrap_or_default();
+            format!("\\# {} {}", octets.len(), to_hex(&octets))
+        }
+        _ => rdata.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn tokenize_quotes_escapes_and_comments() {
+        let tokens = tokenize(r#"www 300 TXT "hello world" a\;b "\065\"" ; comment"#).unwrap();
+        assert_eq!(
+            vec![
+                b"www".to_vec(),
+                b"300".to_vec(),
+                b"TXT".to_vec(),
+                b"hello world".to_vec(),
+                b"a\\;b".to_vec(),
+                b"\\065\\\"".to_vec(),
+            ],
+            tokens
+        );
+        assert_eq!(b"A\"".to_vec(), unescape(&tokens[5]).unwrap());
+        assert!(tokenize(r#""open"#).is_err());
+    }
+
+    #[test]
+    fn rdata_from_text() {
+        let tokens = tokenize("10 mail.example.com.").unwrap();
+        assert_eq!(
+            RData::MX {
+                preference: 10,
+                exchange: "mail.example.com".parse().unwrap()
+            },
+            parse_rdata(&QType::MX, &tokens).unwrap()
+        );
+
+        let tokens = tokenize(r"a\.b.example.com.").unwrap();
+        let rdata = parse_rdata(&QType::CNAME, &tokens).unwrap();
+        assert_eq!(r"a\.b.example.com", rdata.to_string());
+
+        let tokens = tokenize("1.2.3.4 5.6.7.8").unwrap();
+        assert!(parse_rdata(&QType::A, &tokens).is_err());
+    }
+
+    #[test]
+    fn every_type_reads_back_what_it_writes() {
+        let origin: Name = "example.com".parse().unwrap();
+        for (qtype, text, written) in [
+            (QType::NS, "ns1", "ns1.example.com."),
+            (QType::MINFO, "admin @", "admin.example.com. example.com."),
+            (
+                QType::SOA,
+                "ns1 host 1 3h 15m 1w 300",
+                "ns1.example.com. host.example.com. 1 10800 900 604800 300",
+            ),
+            (
+                QType::HTTPS,
+                r#"1 . alpn="h2,h3" port=8443"#,
+                "1 . alpn=h2,h3 port=8443",
+            ),
+            (QType::SVCB, "0 svc.example.net.", "0 svc.example.net."),
+            (QType::TLSA, "3 1 1 2bb183af 2d0c", "3 1 1 2BB183AF2D0C"),
+            (QType::SSHFP, "4 2 0123abcd", "4 2 0123ABCD"),
+            (
+                QType::NAPTR,
+                r#"100 10 "S" "SIP+D2U" "" _sip._udp"#,
+                r#"100 10 "S" "SIP+D2U" "" _sip._udp.example.com."#,
+            ),
+            (QType::HINFO, "x86 \"Linux 6\"", r#""x86" "Linux 6""#),
+            (QType::WKS, "192.0.2.1 tcp 25 22", "192.0.2.1 TCP 22 25"),
+            (QType::RP, "admin.example.com. .", "admin.example.com. ."),
+            (QType::AFSDB, "1 afs", "1 afs.example.com."),
+            (
+                QType::LOC,
+                "42 21 54 N 71 6 18 W -24m 30m",
+                "42 21 54.000 N 71 6 18.000 W -24.00m 30.00m 10000.00m 10.00m",
+            ),
+            (
+                QType::LOC,
+                "52 S 4 53.5 E 0",
+                "52 0 0.000 S 4 53 30.000 E 0.00m 1.00m 10000.00m 10.00m",
+            ),
+            (QType::NULL, r"\# 3 010203", r"\# 3 010203"),
+            (QType::A, r"\# 4 C0000201", "192.0.2.1"),
+        ] {
+            let rdata = parse_rdata_in(&qtype, &tokenize(text).unwrap(), Some(&origin)).unwrap();
+            assert_eq!(written, rdata_text(&rdata));
+            let again = parse_rdata_in(&qtype, &tokenize(written).unwrap(), None).unwrap();
+            assert_eq!(rdata, again);
+        }
+
+        for (qtype, text) in [
+            (QType::TLSA, "3 1 1 abc"),
+            (QType::LOC, "91 N 0 E 0"),
+            (QType::A, r"\# 5 C0000201"),
+            (QType::NULL, "0102"),
+            (QType::OPT, r"\# 0"),
+        ] {
+            assert!(
+                parse_rdata(&qtype, &tokenize(text).unwrap()).is_err(),
+                "{}",
+                text
+            );
+        }
+    }
+}
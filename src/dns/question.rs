@@ -2,24 +2,25 @@ use std::fmt::Display;
 
 use nom::{sequence::tuple, Finish};
 
-use crate::Buffer;
+use super::Buffer;
 
 use super::{
-    parse_utils::{parse_names, parse_qclass, parse_qtype, VResult},
+    name::{parse_name, Name},
+    parse_utils::{parse_qclass, parse_qtype, VResult},
     DeSerialize, QClass, QType, Serialize,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Question {
-    pub qname: String,
+    pub qname: Name,
     pub qtype: QType,
     pub qclass: QClass,
 }
 
 impl Question {
-    pub fn new(name: impl Into<String>, qtype: QType, qclass: QClass) -> Self {
+    pub fn new(qname: Name, qtype: QType, qclass: QClass) -> Self {
         Self {
-            qname: name.into(),
+            qname,
             qtype,
             qclass,
         }
@@ -28,58 +29,15 @@ impl Question {
 
 impl Serialize for Question {
     fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
-        let labels = self.qname.split('.');
-
-        let mut body = Vec::new();
-        for label in labels {
-            let length: u8 = label.chars().count().try_into().unwrap();
-            let label = label.as_bytes();
-            body.push(length);
-            body.extend(label);
-        }
-        body.push(0);
-        let qtype = match self.qtype {
-            QType::A => QType::A as u8,
-            QType::NS => QType::NS as u8,
-            QType::MD => unimplemented!("Obsolete, use MX command instead"),
-            QType::MF => unimplemented!("Obsolete, use MX command instead"),
-            QType::CNAME => QType::CNAME as u8,
-            QType::SOA => QType::SOA as u8,
-            QType::MB => todo!(),
-            QType::MG => todo!(),
-            QType::MR => todo!(),
-            QType::NULL => todo!(),
-            QType::WKS => todo!(),
-            QType::PTR => todo!(),
-            QType::HINFO => todo!(),
-            QType::MINFO => todo!(),
-            QType::MX => QType::MX as u8,
-            QType::TXT => QType::TXT as u8,
-            QType::AXFR => todo!(),
-            QType::MAILB => todo!(),
-            QType::MAILA => todo!(),
-            QType::STAR => todo!(),
-            QType::AAAA => QType::AAAA as u8,
-        };
-        body.push(0);
-        body.push(qtype);
-
-        let qclass = match self.qclass {
-            QClass::IN => QClass::IN as u8,
-            QClass::CS => QClass::CS as u8,
-            QClass::CH => QClass::CH as u8,
-            QClass::HS => QClass::HS as u8,
-            QClass::STAR => QClass::STAR as u8,
-        };
-        body.push(0);
-        body.push(qclass);
+        let mut body = self.qname.serialize()?;
+        body.extend(u16::from(&self.qtype).to_be_bytes());
+        body.extend(u16::from(&self.qclass).to_be_bytes());
         Ok(body)
     }
 }
 
 fn parse_question<'a>(buffer: &'a mut Buffer) -> VResult<&'a [u8], Question> {
-    let mut tokens = Vec::new();
-    let (buf, name) = parse_names(buffer.current, buffer.source, &mut tokens)?;
+    let (buf, name) = parse_name(buffer.current, buffer.source)?;
     let (buf, (qtype, qclass)) = tuple((parse_qtype, parse_qclass))(buf)?;
     buffer.current = buf;
     Ok((buf, Question::new(name, qtype, qclass)))
@@ -132,7 +90,11 @@ mod tests {
         };
         let (_, actual) = Question::deserialize(&mut buffer).unwrap();
 
-        let expected = Question::new("blog.toerktumlare.com", QType::A, QClass::IN);
+        let expected = Question::new(
+            "blog.toerktumlare.com".parse().unwrap(),
+            QType::A,
+            QClass::IN,
+        );
         assert_eq!(expected, actual)
     }
 }
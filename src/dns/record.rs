@@ -1,5 +1,10 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use nom::bytes::complete::take;
+use nom::combinator::{all_consuming, rest};
+use nom::multi::many0;
 use nom::number::complete::be_u16;
 use nom::number::complete::be_u32;
+use nom::number::complete::u8;
 use nom::sequence::tuple;
 use nom::Finish;
 use std::fmt::Display;
@@ -7,39 +12,244 @@ use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::time::Duration;
 
+use super::edns::parse_options;
+use super::edns::EdnsOption;
+use super::name::{parse_name, Name};
+use super::parse_utils::parse_character_string;
 use super::parse_utils::parse_ipv4;
 use super::parse_utils::parse_ipv6;
-use super::parse_utils::parse_names;
 use super::parse_utils::parse_qclass;
 use super::parse_utils::parse_qtype;
 use super::parse_utils::parse_rdlength;
 use super::parse_utils::parse_ttl;
-use super::parse_utils::take_token;
 use super::parse_utils::VResult;
+use super::serialize_utils::escape_character_string;
+use super::serialize_utils::serialize_character_string;
+use super::serialize_utils::to_hex;
+use super::svcb::parse_svc_params;
+use super::svcb::SvcParam;
+use super::tsig::tsig_error_name;
 use super::Buffer;
-use super::{DeSerialize, QClass, QType};
+use super::{DeSerialize, QClass, QType, Serialize};
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum RData {
     A(Ipv4Addr),
-    CNAME(String),
-    TXT(String),
+    CNAME(Name),
+    TXT(Vec<Vec<u8>>),
     AAAA(Ipv6Addr),
-    NS(String),
+    NS(Name),
     MX {
         preference: u16,
-        exchange: String,
+        exchange: Name,
     },
     SOA {
-        mname: String,
-        rname: String,
+        mname: Name,
+        rname: Name,
         serial: u32,
         refresh: u32,
         retry: u32,
         expire: u32,
         minimum: u32,
     },
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: Name,
+    },
+    CAA {
+        flags: u8,
+        tag: String,
+        value: Vec<u8>,
+    },
+    SVCB {
+        priority: u16,
+        target: Name,
+        params: Vec<SvcParam>,
+    },
+    HTTPS {
+        priority: u16,
+        target: Name,
+        params: Vec<SvcParam>,
+    },
+    TLSA {
+        usage: u8,
+        selector: u8,
+        matching_type: u8,
+        data: Vec<u8>,
+    },
+    SSHFP {
+        algorithm: u8,
+        fp_type: u8,
+        fingerprint: Vec<u8>,
+    },
+    NAPTR {
+        order: u16,
+        preference: u16,
+        flags: Vec<u8>,
+        services: Vec<u8>,
+        regexp: Vec<u8>,
+        replacement: Name,
+    },
+    SPF(Vec<Vec<u8>>),
+    HINFO {
+        cpu: Vec<u8>,
+        os: Vec<u8>,
+    },
+    MINFO {
+        rmailbx: Name,
+        emailbx: Name,
+    },
+    MB(Name),
+    MG(Name),
+    MR(Name),
+    PTR(Name),
+    NULL(Vec<u8>),
+    WKS {
+        address: Ipv4Addr,
+        protocol: u8,
+        bitmap: Vec<u8>,
+    },
+    RP {
+        mbox: Name,
+        txt: Name,
+    },
+    AFSDB {
+        subtype: u16,
+        hostname: Name,
+    },
+    LOC {
+        version: u8,
+        size: u8,
+        horiz_pre: u8,
+        vert_pre: u8,
+        latitude: u32,
+        longitude: u32,
+        altitude: u32,
+    },
+    // the options of an EDNS pseudo record, the rest of it lives in the class and ttl
+    OPT(Vec<EdnsOption>),
+    // no rdata at all, dynamic updates use this to match whole RRsets and names (rfc 2136)
+    Empty,
+    TSIG {
+        algorithm: Name,
+        time_signed: u64,
+        fudge: u16,
+        mac: Vec<u8>,
+        original_id: u16,
+        error: u16,
+        other: Vec<u8>,
+    },
+    // rdata of a type or version that is not modeled here, kept as it was received (rfc 3597)
+    Unknown(Vec<u8>),
+}
+
+// TXT and SPF data split over several character-strings is consumed as one string by for instance
+// SPF and DKIM evaluation, the alternate flag `{:#}` displays them concatenated the same way.
+fn display_character_strings(
+    f: &mut std::fmt::Formatter<'_>,
+    strings: &[Vec<u8>],
+) -> std::fmt::Result {
+    if f.alternate() {
+        return write!(f, "{}", escape_character_string(&strings.concat()));
+    }
+    let strings = strings
+        .iter()
+        .map(|s| escape_character_string(s))
+        .collect::<Vec<_>>();
+    write!(f, "{}", strings.join(" "))
+}
+
+// WKS lists the services as a bitmap where bit n says port n is served.
+fn wks_ports(bitmap: &[u8]) -> Vec<u16> {
+    let mut ports = Vec::new();
+    for (i, byte) in bitmap.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                ports.push((i * 8 + bit) as u16);
+            }
+        }
+    }
+    ports
+}
+
+// LOC latitude and longitude are thousandths of a second of arc, offset by 2^31 so that the
+// equator and the prime meridian sit in the middle of the range.
+fn display_loc_coordinate(value: u32, positive: char, negative: char) -> String {
+    let offset = value as i64 - (1 << 31);
+    let hemisphere = if offset < 0 { negative } else { positive };
+    let value = offset.unsigned_abs();
+    let degrees = value / 3_600_000;
+    let minutes = (value % 3_600_000) / 60_000;
+    let seconds = (value % 60_000) as f64 / 1000.0;
+    format!("{degrees} {minutes} {seconds:.3} {hemisphere}")
+}
+
+// size and precisions are encoded as a mantissa in the upper and a power of ten exponent in the
+// lower four bits, expressed in centimeters.
+fn display_loc_precision(value: u8) -> String {
+    let mantissa = (value >> 4) as u64;
+    let exponent = (value & 0x0f) as u32;
+    let centimeters = mantissa * 10u64.pow(exponent);
+    format!("{:.2}m", centimeters as f64 / 100.0)
+}
+
+fn display_loc(
+    f: &mut std::fmt::Formatter<'_>,
+    size: &u8,
+    horiz_pre: &u8,
+    vert_pre: &u8,
+    latitude: &u32,
+    longitude: &u32,
+    altitude: &u32,
+) -> std::fmt::Result {
+    // altitude is in centimeters from a base 100 000 meters below the WGS 84 spheroid
+    let altitude = (*altitude as i64 - 10_000_000) as f64 / 100.0;
+    write!(
+        f,
+        "{} {} {altitude:.2}m {} {} {}",
+        display_loc_coordinate(*latitude, 'N', 'S'),
+        display_loc_coordinate(*longitude, 'E', 'W'),
+        display_loc_precision(*size),
+        display_loc_precision(*horiz_pre),
+        display_loc_precision(*vert_pre),
+    )
+}
+
+fn display_svcb(
+    f: &mut std::fmt::Formatter<'_>,
+    priority: &u16,
+    target: &Name,
+    params: &[SvcParam],
+) -> std::fmt::Result {
+    write!(f, "{priority} {}", target)?;
+    for param in params {
+        write!(f, " {param}")?;
+    }
+    Ok(())
+}
+
+impl RData {
+    // the name this record points at, where following it leads to the next lookup
+    pub fn target(&self) -> Option<&Name> {
+        let target = match self {
+            RData::CNAME(name)
+            | RData::NS(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name) => name,
+            RData::MX { exchange, .. } => exchange,
+            RData::SRV { target, .. }
+            | RData::SVCB { target, .. }
+            | RData::HTTPS { target, .. } => target,
+            RData::AFSDB { hostname, .. } => hostname,
+            _ => return None,
+        };
+        // a root target means there is no service, or for SVCB the owner itself
+        Some(target).filter(|target| !target.is_root())
+    }
 }
 
 impl Display for RData {
@@ -47,7 +257,7 @@ impl Display for RData {
         match self {
             RData::A(value) => write!(f, "{value}"),
             RData::CNAME(value) => write!(f, "{value}"),
-            RData::TXT(value) => write!(f, "{value}"),
+            RData::TXT(strings) => display_character_strings(f, strings),
             RData::AAAA(value) => write!(f, "{value}"),
             RData::NS(value) => write!(f, "{value}"),
             RData::MX {
@@ -66,10 +276,296 @@ impl Display for RData {
                 f,
                 "{mname}, {rname}, {serial}, {refresh}, {retry}, {expire}, {minimum}"
             ),
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{priority} {weight} {port} {}", target),
+            RData::CAA { flags, tag, value } => {
+                write!(f, "{flags} {tag} {}", escape_character_string(value))
+            }
+            RData::SVCB {
+                priority,
+                target,
+                params,
+            } => display_svcb(f, priority, target, params),
+            RData::HTTPS {
+                priority,
+                target,
+                params,
+            } => display_svcb(f, priority, target, params),
+            RData::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => write!(f, "{usage} {selector} {matching_type} {}", to_hex(data)),
+            RData::SSHFP {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => write!(f, "{algorithm} {fp_type} {}", to_hex(fingerprint)),
+            RData::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => write!(
+                f,
+                "{order} {preference} {} {} {} {}",
+                escape_character_string(flags),
+                escape_character_string(services),
+                escape_character_string(regexp),
+                replacement
+            ),
+            RData::SPF(strings) => display_character_strings(f, strings),
+            RData::HINFO { cpu, os } => write!(
+                f,
+                "{} {}",
+                escape_character_string(cpu),
+                escape_character_string(os)
+            ),
+            RData::MINFO { rmailbx, emailbx } => {
+                write!(f, "{} {}", rmailbx, emailbx)
+            }
+            RData::MB(value) => write!(f, "{value}"),
+            RData::MG(value) => write!(f, "{value}"),
+            RData::MR(value) => write!(f, "{value}"),
+            RData::PTR(value) => write!(f, "{value}"),
+            // rfc 3597 generic presentation of opaque data
+            RData::NULL(data) | RData::Unknown(data) => {
+                write!(f, "\\# {} {}", data.len(), to_hex(data))
+            }
+            RData::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                match protocol {
+                    6 => write!(f, "{address} TCP")?,
+                    17 => write!(f, "{address} UDP")?,
+                    _ => write!(f, "{address} {protocol}")?,
+                }
+                for port in wks_ports(bitmap) {
+                    write!(f, " {port}")?;
+                }
+                Ok(())
+            }
+            RData::RP { mbox, txt } => {
+                write!(f, "{} {}", mbox, txt)
+            }
+            RData::AFSDB { subtype, hostname } => write!(f, "{subtype} {hostname}"),
+            RData::LOC {
+                version: _,
+                size,
+                horiz_pre,
+                vert_pre,
+                latitude,
+                longitude,
+                altitude,
+            } => display_loc(f, size, horiz_pre, vert_pre, latitude, longitude, altitude),
+            RData::OPT(options) => {
+                let options = options.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", options.join("; "))
+            }
+            RData::Empty => Ok(()),
+            RData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other,
+            } => write!(
+                f,
+                "{algorithm} {time_signed} {fudge} {} {} {original_id} {} {}",
+                mac.len(),
+                STANDARD.encode(mac),
+                tsig_error_name(*error),
+                other.len()
+            ),
         }
     }
 }
 
+impl Serialize for RData {
+    fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut bytes = Vec::new();
+        match self {
+            RData::A(address) => bytes.extend(address.octets()),
+            RData::CNAME(name)
+            | RData::NS(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => bytes.extend(name.serialize()?),
+            RData::TXT(strings) | RData::SPF(strings) => {
+                for string in strings {
+                    bytes.extend(serialize_character_string(string)?);
+                }
+            }
+            RData::AAAA(address) => bytes.extend(address.octets()),
+            RData::MX {
+                preference,
+                exchange,
+            } => {
+                bytes.extend(preference.to_be_bytes());
+                bytes.extend(exchange.serialize()?);
+            }
+            RData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                bytes.extend(mname.serialize()?);
+                bytes.extend(rname.serialize()?);
+                for value in [serial, refresh, retry, expire, minimum] {
+                    bytes.extend(value.to_be_bytes());
+                }
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                bytes.extend(priority.to_be_bytes());
+                bytes.extend(weight.to_be_bytes());
+                bytes.extend(port.to_be_bytes());
+                bytes.extend(target.serialize()?);
+            }
+            RData::CAA { flags, tag, value } => {
+                bytes.push(*flags);
+                bytes.extend(serialize_character_string(tag.as_bytes())?);
+                bytes.extend(value);
+            }
+            RData::SVCB {
+                priority,
+                target,
+                params,
+            }
+            | RData::HTTPS {
+                priority,
+                target,
+                params,
+            } => {
+                bytes.extend(priority.to_be_bytes());
+                bytes.extend(target.serialize()?);
+                for param in params {
+                    bytes.extend(param.serialize()?);
+                }
+            }
+            RData::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => {
+                bytes.extend([*usage, *selector, *matching_type]);
+                bytes.extend(data);
+            }
+            RData::SSHFP {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => {
+                bytes.extend([*algorithm, *fp_type]);
+                bytes.extend(fingerprint);
+            }
+            RData::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                bytes.extend(order.to_be_bytes());
+                bytes.extend(preference.to_be_bytes());
+                bytes.extend(serialize_character_string(flags)?);
+                bytes.extend(serialize_character_string(services)?);
+                bytes.extend(serialize_character_string(regexp)?);
+                bytes.extend(replacement.serialize()?);
+            }
+            RData::HINFO { cpu, os } => {
+                bytes.extend(serialize_character_string(cpu)?);
+                bytes.extend(serialize_character_string(os)?);
+            }
+            RData::MINFO { rmailbx, emailbx } => {
+                bytes.extend(rmailbx.serialize()?);
+                bytes.extend(emailbx.serialize()?);
+            }
+            RData::NULL(data) | RData::Unknown(data) => bytes.extend(data),
+            RData::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                bytes.extend(address.octets());
+                bytes.push(*protocol);
+                bytes.extend(bitmap);
+            }
+            RData::RP { mbox, txt } => {
+                bytes.extend(mbox.serialize()?);
+                bytes.extend(txt.serialize()?);
+            }
+            RData::AFSDB { subtype, hostname } => {
+                bytes.extend(subtype.to_be_bytes());
+                bytes.extend(hostname.serialize()?);
+            }
+            RData::LOC {
+                version,
+                size,
+                horiz_pre,
+                vert_pre,
+                latitude,
+                longitude,
+                altitude,
+            } => {
+                bytes.extend([*version, *size, *horiz_pre, *vert_pre]);
+                bytes.extend(latitude.to_be_bytes());
+                bytes.extend(longitude.to_be_bytes());
+                bytes.extend(altitude.to_be_bytes());
+            }
+            RData::OPT(options) => {
+                for option in options {
+                    bytes.extend(option.serialize());
+                }
+            }
+            RData::Empty => (),
+            RData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other,
+            } => {
+                bytes.extend(algorithm.serialize()?);
+                // time signed is a 48 bit field
+                bytes.extend(&time_signed.to_be_bytes()[2..]);
+                bytes.extend(fudge.to_be_bytes());
+                bytes.extend((mac.len() as u16).to_be_bytes());
+                bytes.extend(mac);
+                bytes.extend(original_id.to_be_bytes());
+                bytes.extend(error.to_be_bytes());
+                bytes.extend((other.len() as u16).to_be_bytes());
+                bytes.extend(other);
+            }
+        }
+        Ok(bytes)
+    }
+}
+
 // Resource record format
 //
 // The answer, authority, and additional sections all share the same
@@ -100,7 +596,7 @@ impl Display for RData {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Record {
     // a domain name to which this resource record pertains.
-    pub name: String,
+    pub name: Name,
 
     // two octets containing one of the RR type codes.
     // This field specifies the meaning of the data in the RDATA field.
@@ -131,7 +627,7 @@ pub struct Record {
 
 impl Record {
     pub fn new(
-        name: String,
+        name: Name,
         qtype: QType,
         qclass: QClass,
         ttl: Duration,
@@ -149,38 +645,43 @@ impl Record {
     }
 }
 
-fn parse_record<'a>(buffer: &'a [u8], source: &'a [u8]) -> VResult<&'a [u8], Record> {
-    let mut t = Vec::new();
-    let (buffer, name) = parse_names(buffer, source, &mut t)?;
-
-    let (buffer, (qtype, qclass, ttl, rd_length)) =
-        tuple((parse_qtype, parse_qclass, parse_ttl, parse_rdlength))(buffer)?;
-
+// parses the RDATA of a record, the buffer only contains the rdata itself so fields that run
+// until the end of the rdata can just consume the rest of it.
+fn parse_rdata<'a>(qtype: &QType, buffer: &'a [u8], source: &'a [u8]) -> VResult<&'a [u8], RData> {
     let (buffer, rdata) = match qtype {
         QType::A => {
             let (buffer, address) = parse_ipv4(buffer)?;
             (buffer, RData::A(address))
         }
         QType::CNAME => {
-            let mut t = Vec::new();
-            let (buffer, name) = parse_names(buffer, source, &mut t)?;
+            let (buffer, name) = parse_name(buffer, source)?;
             (buffer, RData::CNAME(name))
         }
         QType::TXT => {
-            let (buffer, txt) = take_token(buffer, rd_length.into())?;
-            (buffer, RData::TXT(txt.to_owned()))
+            let (buffer, strings) = all_consuming(many0(parse_character_string))(buffer)?;
+            (
+                buffer,
+                RData::TXT(strings.iter().map(|s| s.to_vec()).collect()),
+            )
+        }
+        QType::SPF => {
+            let (buffer, strings) = all_consuming(many0(parse_character_string))(buffer)?;
+            (
+                buffer,
+                RData::SPF(strings.iter().map(|s| s.to_vec()).collect()),
+            )
         }
         QType::AAAA => {
             let (buffer, address) = parse_ipv6(buffer)?;
             (buffer, RData::AAAA(address))
         }
         QType::NS => {
-            let (buffer, name) = parse_names(buffer, source, &mut t)?;
+            let (buffer, name) = parse_name(buffer, source)?;
             (buffer, RData::NS(name))
         }
         QType::MX => {
             let (buffer, preference) = be_u16(buffer)?;
-            let (buffer, exchange) = parse_names(buffer, source, &mut t)?;
+            let (buffer, exchange) = parse_name(buffer, source)?;
             (
                 buffer,
                 RData::MX {
@@ -190,8 +691,8 @@ fn parse_record<'a>(buffer: &'a [u8], source: &'a [u8]) -> VResult<&'a [u8], Rec
             )
         }
         QType::SOA => {
-            let (buffer, mname) = parse_names(buffer, source, &mut t)?;
-            let (buffer, rname) = parse_names(buffer, source, &mut t)?;
+            let (buffer, mname) = parse_name(buffer, source)?;
+            let (buffer, rname) = parse_name(buffer, source)?;
             let (buffer, (serial, refresh, retry, expire, minimum)) =
                 tuple((be_u32, be_u32, be_u32, be_u32, be_u32))(buffer)?;
             (
@@ -207,15 +708,259 @@ fn parse_record<'a>(buffer: &'a [u8], source: &'a [u8]) -> VResult<&'a [u8], Rec
                 },
             )
         }
-        _ => unimplemented!(),
+        QType::SRV => {
+            let (buffer, (priority, weight, port)) = tuple((be_u16, be_u16, be_u16))(buffer)?;
+            let (buffer, target) = parse_name(buffer, source)?;
+            (
+                buffer,
+                RData::SRV {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                },
+            )
+        }
+        QType::CAA => {
+            let (buffer, flags) = u8(buffer)?;
+            let (buffer, tag) = parse_character_string(buffer)?;
+            let (buffer, value) = rest(buffer)?;
+            (
+                buffer,
+                RData::CAA {
+                    flags,
+                    tag: String::from_utf8_lossy(tag).into_owned(),
+                    value: value.to_vec(),
+                },
+            )
+        }
+        QType::SVCB | QType::HTTPS => {
+            let (buffer, priority) = be_u16(buffer)?;
+            let (buffer, target) = parse_name(buffer, source)?;
+            let (buffer, params) = parse_svc_params(buffer)?;
+            let rdata = if *qtype == QType::SVCB {
+                RData::SVCB {
+                    priority,
+                    target,
+                    params,
+                }
+            } else {
+                RData::HTTPS {
+                    priority,
+                    target,
+                    params,
+                }
+            };
+            (buffer, rdata)
+        }
+        QType::TLSA => {
+            let (buffer, (usage, selector, matching_type)) = tuple((u8, u8, u8))(buffer)?;
+            let (buffer, data) = rest(buffer)?;
+            (
+                buffer,
+                RData::TLSA {
+                    usage,
+                    selector,
+                    matching_type,
+                    data: data.to_vec(),
+                },
+            )
+        }
+        QType::SSHFP => {
+            let (buffer, (algorithm, fp_type)) = tuple((u8, u8))(buffer)?;
+            let (buffer, fingerprint) = rest(buffer)?;
+            (
+                buffer,
+                RData::SSHFP {
+                    algorithm,
+                    fp_type,
+                    fingerprint: fingerprint.to_vec(),
+                },
+            )
+        }
+        QType::NAPTR => {
+            let (buffer, (order, preference)) = tuple((be_u16, be_u16))(buffer)?;
+            let (buffer, (flags, services, regexp)) = tuple((
+                parse_character_string,
+                parse_character_string,
+                parse_character_string,
+            ))(buffer)?;
+            let (buffer, replacement) = parse_name(buffer, source)?;
+            (
+                buffer,
+                RData::NAPTR {
+                    order,
+                    preference,
+                    flags: flags.to_vec(),
+                    services: services.to_vec(),
+                    regexp: regexp.to_vec(),
+                    replacement,
+                },
+            )
+        }
+        QType::HINFO => {
+            let (buffer, (cpu, os)) =
+                tuple((parse_character_string, parse_character_string))(buffer)?;
+            (
+                buffer,
+                RData::HINFO {
+                    cpu: cpu.to_vec(),
+                    os: os.to_vec(),
+                },
+            )
+        }
+        QType::MINFO => {
+            let (buffer, rmailbx) = parse_name(buffer, source)?;
+            let (buffer, emailbx) = parse_name(buffer, source)?;
+            (buffer, RData::MINFO { rmailbx, emailbx })
+        }
+        QType::PTR => {
+            let (buffer, name) = parse_name(buffer, source)?;
+            (buffer, RData::PTR(name))
+        }
+        QType::MB => {
+            let (buffer, name) = parse_name(buffer, source)?;
+            (buffer, RData::MB(name))
+        }
+        QType::MG => {
+            let (buffer, name) = parse_name(buffer, source)?;
+            (buffer, RData::MG(name))
+        }
+        QType::MR => {
+            let (buffer, name) = parse_name(buffer, source)?;
+            (buffer, RData::MR(name))
+        }
+        QType::OPT => {
+            let (buffer, options) = parse_options(buffer)?;
+            (buffer, RData::OPT(options))
+        }
+        QType::NULL => {
+            let (buffer, data) = rest(buffer)?;
+            (buffer, RData::NULL(data.to_vec()))
+        }
+        QType::WKS => {
+            let (buffer, (address, protocol)) = tuple((parse_ipv4, u8))(buffer)?;
+            let (buffer, bitmap) = rest(buffer)?;
+            (
+                buffer,
+                RData::WKS {
+                    address,
+                    protocol,
+                    bitmap: bitmap.to_vec(),
+                },
+            )
+        }
+        QType::RP => {
+            let (buffer, mbox) = parse_name(buffer, source)?;
+            let (buffer, txt) = parse_name(buffer, source)?;
+            (buffer, RData::RP { mbox, txt })
+        }
+        QType::AFSDB => {
+            let (buffer, subtype) = be_u16(buffer)?;
+            let (buffer, hostname) = parse_name(buffer, source)?;
+            (buffer, RData::AFSDB { subtype, hostname })
+        }
+        // only version 0 is defined (rfc 1876 2), the layout of any other is not known
+        QType::LOC if buffer.first().is_some_and(|version| *version != 0) => {
+            let (buffer, data) = rest(buffer)?;
+            (buffer, RData::Unknown(data.to_vec()))
+        }
+        QType::LOC => {
+            let (buffer, (version, size, horiz_pre, vert_pre)) = tuple((u8, u8, u8, u8))(buffer)?;
+            let (buffer, (latitude, longitude, altitude)) =
+                tuple((be_u32, be_u32, be_u32))(buffer)?;
+            (
+                buffer,
+                RData::LOC {
+                    version,
+                    size,
+                    horiz_pre,
+                    vert_pre,
+                    latitude,
+                    longitude,
+                    altitude,
+                },
+            )
+        }
+        QType::TSIG => {
+            let (buffer, algorithm) = parse_name(buffer, source)?;
+            let (buffer, (time_high, time_low, fudge)) = tuple((be_u16, be_u32, be_u16))(buffer)?;
+            let (buffer, mac_size) = be_u16(buffer)?;
+            let (buffer, mac) = take(mac_size)(buffer)?;
+            let (buffer, (original_id, error, other_len)) =
+                tuple((be_u16, be_u16, be_u16))(buffer)?;
+            let (buffer, other) = take(other_len)(buffer)?;
+            (
+                buffer,
+                RData::TSIG {
+                    algorithm,
+                    time_signed: (time_high as u64) << 32 | time_low as u64,
+                    fudge,
+                    mac: mac.to_vec(),
+                    original_id,
+                    error,
+                    other: other.to_vec(),
+                },
+            )
+        }
+        // any other type is kept as opaque octets (rfc 3597)
+        _ => {
+            let (buffer, data) = rest(buffer)?;
+            (buffer, RData::Unknown(data.to_vec()))
+        }
+    };
+    Ok((buffer, rdata))
+}
+
+// reads rdata that is given as its octets, as the generic presentation format does (rfc 3597 5)
+pub(crate) fn rdata_from_wire(qtype: &QType, octets: &[u8]) -> Result<RData, anyhow::Error> {
+    match parse_rdata(qtype, octets, octets).finish() {
+        Ok(([], rdata)) => Ok(rdata),
+        Ok((rest, _)) => Err(anyhow::anyhow!(
+            "{} octets left over after the {} rdata",
+            rest.len(),
+            qtype
+        )),
+        Err(_) => Err(anyhow::anyhow!("the octets are not valid {} rdata", qtype)),
+    }
+}
+
+pub(crate) fn parse_record<'a>(buffer: &'a [u8], source: &'a [u8]) -> VResult<&'a [u8], Record> {
+    let (buffer, name) = parse_name(buffer, source)?;
+
+    let (buffer, (qtype, qclass, ttl, rd_length)) =
+        tuple((parse_qtype, parse_qclass, parse_ttl, parse_rdlength))(buffer)?;
+
+    let (buffer, rdata) = take(rd_length)(buffer)?;
+    let (_, rdata) = match qclass {
+        QClass::ANY | QClass::NONE if rdata.is_empty() && qtype != QType::OPT => {
+            (rdata, RData::Empty)
+        }
+        // octets left inside the rdlength make the record invalid, as they do in `rdata_from_wire`
+        _ => all_consuming(|rdata| parse_rdata(&qtype, rdata, source))(rdata)?,
     };
 
     Ok((
         buffer,
-        Record::new(name.clone(), qtype, qclass, ttl, rd_length, rdata),
+        Record::new(name, qtype, qclass, ttl, rd_length, rdata),
     ))
 }
 
+impl Serialize for Record {
+    fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let rdata = self.rdata.serialize()?;
+        let rd_length: u16 = rdata.len().try_into()?;
+
+        let mut bytes = self.name.serialize()?;
+        bytes.extend(u16::from(&self.qtype).to_be_bytes());
+        bytes.extend(u16::from(&self.qclass).to_be_bytes());
+        bytes.extend((self.ttl.as_secs() as u32).to_be_bytes());
+        bytes.extend(rd_length.to_be_bytes());
+        bytes.extend(rdata);
+        Ok(bytes)
+    }
+}
+
 impl<'a> DeSerialize<'a> for Record {
     type Item = (&'a mut Buffer<'a>, Record);
 
@@ -232,6 +977,11 @@ impl<'a> DeSerialize<'a> for Record {
 
 impl Display for Record {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rdata = if f.alternate() {
+            format!("{:#}", self.rdata)
+        } else {
+            self.rdata.to_string()
+        };
         write!(
             f,
             "{}\t\t\t{}\t{}\t{}\t{}",
@@ -239,7 +989,7 @@ impl Display for Record {
             self.ttl.as_secs(),
             self.qclass,
             self.qtype,
-            self.rdata
+            rdata
         )
     }
 }
@@ -263,7 +1013,7 @@ mod tests {
         let (_, actual) = Record::deserialize(&mut buffer).unwrap();
 
         let expected = Record::new(
-            "google.com".to_owned(),
+            "google.com".parse().unwrap(),
             QType::A,
             QClass::IN,
             Duration::new(3600, 0),
@@ -273,4 +1023,178 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn parse_srv_record() {
+        let raw = vec![
+            0x04, 0x5f, 0x73, 0x69, 0x70, 0x04, 0x5f, 0x74, 0x63, 0x70, 0x00, 0x00, 0x21, 0x00,
+            0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x0b, 0x00, 0x0a, 0x00, 0x05, 0x13, 0xc4, 0x03,
+            0x73, 0x69, 0x70, 0x00,
+        ];
+
+        let mut buffer = Buffer {
+            current: &raw,
+            source: &raw,
+        };
+        let (_, actual) = Record::deserialize(&mut buffer).unwrap();
+
+        assert_eq!(
+            RData::SRV {
+                priority: 10,
+                weight: 5,
+                port: 5060,
+                target: "sip".parse().unwrap(),
+            },
+            actual.rdata
+        );
+        assert_eq!("10 5 5060 sip", actual.rdata.to_string());
+    }
+
+    #[test]
+    fn https_record_roundtrip() {
+        let record = Record::new(
+            "example.com".parse().unwrap(),
+            QType::HTTPS,
+            QClass::IN,
+            Duration::new(300, 0),
+            0,
+            RData::HTTPS {
+                priority: 1,
+                target: Name::root(),
+                params: vec![
+                    SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+                    SvcParam::Port(8443),
+                ],
+            },
+        );
+
+        let raw = record.serialize().unwrap();
+        let mut buffer = Buffer {
+            current: &raw,
+            source: &raw,
+        };
+        let (_, actual) = Record::deserialize(&mut buffer).unwrap();
+
+        assert_eq!(record.rdata, actual.rdata);
+        assert_eq!("1 . alpn=h2,h3 port=8443", actual.rdata.to_string());
+    }
+
+    #[test]
+    fn trailing_rdata_is_rejected() {
+        let raw = [
+            0x00, // root
+            0x00, 0x01, 0x00, 0x01, // A IN
+            0x00, 0x00, 0x01, 0x2c, // ttl 300
+            0x00, 0x06, // rdlength 6
+            0x01, 0x02, 0x03, 0x04, 0xff, 0xff,
+        ];
+        assert!(super::parse_record(&raw, &raw).is_err());
+
+        let raw = [
+            0x00, // root
+            0x00, 0x0f, 0x00, 0x01, // MX IN
+            0x00, 0x00, 0x01, 0x2c, // ttl 300
+            0x00, 0x04, // rdlength 4
+            0x00, 0x0a, 0x00, // 10 .
+            0xff,
+        ];
+        assert!(super::parse_record(&raw, &raw).is_err());
+    }
+
+    #[test]
+    fn caa_record_roundtrip() {
+        let rdata = RData::CAA {
+            flags: 0,
+            tag: "issue".to_owned(),
+            value: b"letsencrypt.org".to_vec(),
+        };
+        let raw = rdata.serialize().unwrap();
+        let (_, actual) = parse_rdata(&QType::CAA, &raw, &raw).unwrap();
+
+        assert_eq!(rdata, actual);
+        assert_eq!("0 issue \"letsencrypt.org\"", actual.to_string());
+    }
+
+    #[test]
+    fn txt_character_strings() {
+        let raw = vec![
+            0x06, 0x76, 0x3d, 0x73, 0x70, 0x66, 0x31, 0x04, 0x20, 0x2d, 0x61, 0xff,
+        ];
+        let (_, actual) = parse_rdata(&QType::TXT, &raw, &raw).unwrap();
+
+        assert_eq!(
+            RData::TXT(vec![b"v=spf1".to_vec(), vec![0x20, 0x2d, 0x61, 0xff]]),
+            actual
+        );
+        assert_eq!("\"v=spf1\" \" -a\\255\"", actual.to_string());
+        assert_eq!("\"v=spf1 -a\\255\"", format!("{:#}", actual));
+        assert_eq!(raw, actual.serialize().unwrap());
+
+        // the last string says 4 octets but has 3
+        let cut = &raw[..raw.len() - 1];
+        assert!(parse_rdata(&QType::TXT, cut, cut).is_err());
+    }
+
+    #[test]
+    fn loc_record_display() {
+        // cambridge-net.kei.com. LOC 42 21 54 N 71 06 18 W -24m 30m (rfc 1876)
+        let raw = vec![
+            0x00, 0x33, 0x16, 0x13, 0x89, 0x17, 0x2d, 0xd0, 0x70, 0xbe, 0x15, 0xf0, 0x00, 0x98,
+            0x8d, 0x20,
+        ];
+        let (_, actual) = parse_rdata(&QType::LOC, &raw, &raw).unwrap();
+
+        assert_eq!(
+            "42 21 54.000 N 71 6 18.000 W -24.00m 30.00m 10000.00m 10.00m",
+            actual.to_string()
+        );
+        assert_eq!(raw, actual.serialize().unwrap());
+
+        // a version other than 0 has no known layout, so it is shown as octets
+        let raw = vec![0x01, 0x33, 0x16, 0x13];
+        let (_, actual) = parse_rdata(&QType::LOC, &raw, &raw).unwrap();
+
+        assert_eq!(RData::Unknown(raw.clone()), actual);
+        assert_eq!("\\# 4 01331613", actual.to_string());
+        assert_eq!(raw, actual.serialize().unwrap());
+    }
+
+    #[test]
+    fn legacy_record_roundtrip() {
+        let records = vec![
+            (
+                QType::HINFO,
+                RData::HINFO {
+                    cpu: b"VAX-11/780".to_vec(),
+                    os: b"UNIX".to_vec(),
+                },
+                "\"VAX-11/780\" \"UNIX\"",
+            ),
+            (
+                QType::WKS,
+                RData::WKS {
+                    address: Ipv4Addr::new(10, 0, 0, 1),
+                    protocol: 6,
+                    bitmap: vec![0x00, 0x00, 0x00, 0x40],
+                },
+                "10.0.0.1 TCP 25",
+            ),
+            (
+                QType::AFSDB,
+                RData::AFSDB {
+                    subtype: 1,
+                    hostname: "afs.example.com".parse().unwrap(),
+                },
+                "1 afs.example.com",
+            ),
+            (QType::NULL, RData::NULL(vec![0xca, 0xfe]), "\\# 2 CAFE"),
+        ];
+
+        for (qtype, rdata, display) in records {
+            let raw = rdata.serialize().unwrap();
+            let (_, actual) = parse_rdata(&qtype, &raw, &raw).unwrap();
+            assert_eq!(rdata, actual);
+            assert_eq!(display, actual.to_string());
+        }
+    }
 }
@@ -0,0 +1,50 @@
+use anyhow::anyhow;
+
+// serializes a <character-string>, a single length octet followed by that number of octets.
+pub fn serialize_character_string(value: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let length: u8 = value
+        .len()
+        .try_into()
+        .map_err(|_| anyhow!("character-string exceeds maximum length of 255"))?;
+    let mut bytes = Vec::with_capacity(value.len() + 1);
+    bytes.push(length);
+    bytes.extend(value);
+    Ok(bytes)
+}
+
+// formats bytes as a contiguous upper case hex string, used by TLSA and SSHFP presentation.
+pub fn to_hex(value: &[u8]) -> String {
+    value.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// formats a <character-string> in presentation format, quoted with non printable characters,
+// quotes and backslashes escaped.
+pub fn escape_character_string(value: &[u8]) -> String {
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+    for b in value {
+        match b {
+            b'"' | b'\\' => {
+                s.push('\\');
+                s.push(*b as char);
+            }
+            0x20..=0x7e => s.push(*b as char),
+            _ => s.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    s.push('"');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_quotes_and_binary() {
+        assert_eq!(
+            "\"a\\\"b\\\\c\\010\"",
+            escape_character_string(b"a\"b\\c\n")
+        );
+    }
+}
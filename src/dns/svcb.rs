@@ -0,0 +1,301 @@
+use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use nom::bytes::complete::take;
+use nom::combinator::all_consuming;
+use nom::multi::many0;
+use nom::number::complete::be_u16;
+
+use super::parse_utils::{parse_ipv4, parse_ipv6, VResult};
+use super::presentation::unescape;
+use super::serialize_utils::escape_character_string;
+
+// SvcParams as defined in rfc 9460 section 14.3.2, each parameter is encoded as:
+//
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |                  SvcParamKey                  |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     |                 length                        |
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//     /                 SvcParamValue                 /
+//     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    // keys that must be understood by the client (key 0)
+    Mandatory(Vec<u16>),
+    // application layer protocol negotiation ids (key 1)
+    Alpn(Vec<Vec<u8>>),
+    // the default alpn is not supported by the endpoint (key 2)
+    NoDefaultAlpn,
+    // alternative port (key 3)
+    Port(u16),
+    // ipv4 address hints (key 4)
+    Ipv4Hint(Vec<Ipv4Addr>),
+    // encrypted client hello config list (key 5)
+    Ech(Vec<u8>),
+    // ipv6 address hints (key 6)
+    Ipv6Hint(Vec<Ipv6Addr>),
+    // any key we do not know how to decode
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown(key, _) => *key,
+        }
+    }
+
+    fn value(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let value = match self {
+            SvcParam::Mandatory(keys) => keys.iter().flat_map(|k| k.to_be_bytes()).collect(),
+            SvcParam::Alpn(ids) => {
+                let mut value = Vec::new();
+                for id in ids {
+                    let length: u8 = id
+                        .len()
+                        .try_into()
+                        .map_err(|_| anyhow!("alpn id exceeds maximum length of 255"))?;
+                    value.push(length);
+                    value.extend(id);
+                }
+                value
+            }
+            SvcParam::NoDefaultAlpn => Vec::new(),
+            SvcParam::Port(port) => port.to_be_bytes().to_vec(),
+            SvcParam::Ipv4Hint(ips) => ips.iter().flat_map(|ip| ip.octets()).collect(),
+            SvcParam::Ech(config) => config.clone(),
+            SvcParam::Ipv6Hint(ips) => ips.iter().flat_map(|ip| ip.octets()).collect(),
+            SvcParam::Unknown(_, value) => value.clone(),
+        };
+        Ok(value)
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let value = self.value()?;
+        let length: u16 = value.len().try_into().map_err(|_| {
+            anyhow!(
+                "{} value exceeds maximum length of 65535",
+                key_name(self.key())
+            )
+        })?;
+        let mut bytes = Vec::with_capacity(value.len() + 4);
+        bytes.extend(self.key().to_be_bytes());
+        bytes.extend(length.to_be_bytes());
+        bytes.extend(value);
+        Ok(bytes)
+    }
+}
+
+fn key_name(key: u16) -> String {
+    match key {
+        0 => "mandatory".to_owned(),
+        1 => "alpn".to_owned(),
+        2 => "no-default-alpn".to_owned(),
+        3 => "port".to_owned(),
+        4 => "ipv4hint".to_owned(),
+        5 => "ech".to_owned(),
+        6 => "ipv6hint".to_owned(),
+        _ => format!("key{}", key),
+    }
+}
+
+fn key_number(name: &str) -> Result<u16, anyhow::Error> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "mandatory" => 0,
+        "alpn" => 1,
+        "no-default-alpn" => 2,
+        "port" => 3,
+        "ipv4hint" => 4,
+        "ech" => 5,
+        "ipv6hint" => 6,
+        other => other
+            .strip_prefix("key")
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| anyhow!("unknown SvcParamKey: {}", name))?,
+    };
+    Ok(key)
+}
+
+fn join<T: Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Display for SvcParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = key_name(self.key());
+        match self {
+            SvcParam::Mandatory(keys) => {
+                let keys = keys.iter().map(|k| key_name(*k)).collect::<Vec<_>>();
+                write!(f, "{key}={}", keys.join(","))
+            }
+            SvcParam::Alpn(ids) => {
+                let ids = ids
+                    .iter()
+                    .map(|id| String::from_utf8_lossy(id).into_owned())
+                    .collect::<Vec<_>>();
+                write!(f, "{key}={}", ids.join(","))
+            }
+            SvcParam::NoDefaultAlpn => write!(f, "{key}"),
+            SvcParam::Port(port) => write!(f, "{key}={port}"),
+            SvcParam::Ipv4Hint(ips) => write!(f, "{key}={}", join(ips)),
+            SvcParam::Ech(config) => write!(f, "{key}={}", STANDARD.encode(config)),
+            SvcParam::Ipv6Hint(ips) => write!(f, "{key}={}", join(ips)),
+            SvcParam::Unknown(_, value) => write!(f, "{key}={}", escape_character_string(value)),
+        }
+    }
+}
+
+// parses a param in presentation format, `key=value` or just the key, the value may be quoted
+// and lists are separated by commas (rfc 9460 2.1)
+impl FromStr for SvcParam {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (s, None),
+        };
+        let key = key_number(key)?;
+        let value = value.map(|v| {
+            v.strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(v)
+        });
+        let list = || -> Result<Vec<&str>, anyhow::Error> {
+            let value = value.ok_or_else(|| anyhow!("{} needs a value", key_name(key)))?;
+            Ok(value.split(',').collect())
+        };
+        let invalid = || anyhow!("invalid value for {}: {}", key_name(key), s);
+        let param = match key {
+            0 => SvcParam::Mandatory(
+                list()?
+                    .into_iter()
+                    .map(key_number)
+                    .collect::<Result<_, _>>()?,
+            ),
+            1 => SvcParam::Alpn(
+                list()?
+                    .into_iter()
+                    .map(|id| unescape(id.as_bytes()))
+                    .collect::<Result<_, _>>()?,
+            ),
+            2 if value.is_none() => SvcParam::NoDefaultAlpn,
+            3 => SvcParam::Port(list()?.concat().parse().map_err(|_| invalid())?),
+            4 => SvcParam::Ipv4Hint(
+                list()?
+                    .into_iter()
+                    .map(|ip| ip.parse().map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()?,
+            ),
+            5 => SvcParam::Ech(
+                STANDARD
+                    .decode(list()?.concat())
+                    .map_err(|_| anyhow!("invalid value for ech, not base64: {}", s))?,
+            ),
+            6 => SvcParam::Ipv6Hint(
+                list()?
+                    .into_iter()
+                    .map(|ip| ip.parse().map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()?,
+            ),
+            2 => return Err(anyhow!("no-default-alpn takes no value: {}", s)),
+            _ => SvcParam::Unknown(key, unescape(value.unwrap_or_default().as_bytes())?),
+        };
+        Ok(param)
+    }
+}
+
+fn parse_svc_param(buffer: &[u8]) -> VResult<&[u8], SvcParam> {
+    let (buffer, key) = be_u16(buffer)?;
+    let (buffer, length) = be_u16(buffer)?;
+    let (buffer, value) = take(length)(buffer)?;
+
+    let param = match key {
+        0 => SvcParam::Mandatory(all_consuming(many0(be_u16))(value)?.1),
+        1 => {
+            let mut ids = Vec::new();
+            let mut value = value;
+            while !value.is_empty() {
+                let (rest, length) = nom::number::complete::u8(value)?;
+                let (rest, id) = take(length)(rest)?;
+                ids.push(id.to_vec());
+                value = rest;
+            }
+            SvcParam::Alpn(ids)
+        }
+        2 => SvcParam::NoDefaultAlpn,
+        3 => SvcParam::Port(be_u16(value)?.1),
+        4 => SvcParam::Ipv4Hint(all_consuming(many0(parse_ipv4))(value)?.1),
+        5 => SvcParam::Ech(value.to_vec()),
+        6 => SvcParam::Ipv6Hint(all_consuming(many0(parse_ipv6))(value)?.1),
+        _ => SvcParam::Unknown(key, value.to_vec()),
+    };
+    Ok((buffer, param))
+}
+
+// parses SvcParams until the end of the rdata, a param that is cut short fails the whole rdata
+pub fn parse_svc_params(buffer: &[u8]) -> VResult<&[u8], Vec<SvcParam>> {
+    all_consuming(many0(parse_svc_param))(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_and_display_params() {
+        let raw = vec![
+            0x00, 0x01, 0x00, 0x06, 0x02, 0x68, 0x32, 0x02, 0x68, 0x33, // alpn=h2,h3
+            0x00, 0x03, 0x00, 0x02, 0x01, 0xbb, // port=443
+            0x00, 0x04, 0x00, 0x04, 0x01, 0x02, 0x03, 0x04, // ipv4hint=1.2.3.4
+        ];
+
+        let (rest, params) = parse_svc_params(&raw).unwrap();
+        let display = params
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        assert_eq!(0, rest.len());
+        assert_eq!("alpn=h2,h3 port=443 ipv4hint=1.2.3.4", display);
+        assert_eq!(
+            params,
+            display
+                .split(' ')
+                .map(|p| p.parse().unwrap())
+                .collect::<Vec<SvcParam>>()
+        );
+        assert!("port=https".parse::<SvcParam>().is_err());
+        // the last param says 4 octets but has 3, an ipv4hint of 5 octets
+        assert!(parse_svc_params(&raw[..raw.len() - 1]).is_err());
+        assert!(parse_svc_params(&[0x00, 0x04, 0x00, 0x05, 1, 2, 3, 4, 5]).is_err());
+        assert_eq!(
+            raw,
+            params
+                .iter()
+                .flat_map(|p| p.serialize().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert!(SvcParam::Alpn(vec![vec![b'h'; 256]]).serialize().is_err());
+        assert!(SvcParam::Ech(vec![0; 65536]).serialize().is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha384, Sha512};
+
+use super::message::Message;
+use super::name::{parse_name, Name};
+use super::record::{parse_record, RData, Record};
+use super::{QClass, QType, Serialize};
+
+// allowed difference in seconds between our clock and the clock of the server
+const FUDGE: u16 = 300;
+
+// TSIG error codes carried in the error field of the TSIG record (rfc 8945 section 3)
+const BADSIG: u16 = 16;
+const BADKEY: u16 = 17;
+const BADTIME: u16 = 18;
+const BADTRUNC: u16 = 22;
+
+pub fn tsig_error_name(error: u16) -> String {
+    match error {
+        0 => "NOERROR".to_owned(),
+        BADSIG => "BADSIG".to_owned(),
+        BADKEY => "BADKEY".to_owned(),
+        BADTIME => "BADTIME".to_owned(),
+        BADTRUNC => "BADTRUNC".to_owned(),
+        _ => error.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Algorithm {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl Algorithm {
+    fn mac(&self, secret: &[u8], data: &[u8]) -> Vec<u8> {
+        // hmac accepts keys of any length so creating it can not fail
+        match self {
+            Algorithm::HmacSha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            Algorithm::HmacSha384 => {
+                let mut mac = Hmac::<Sha384>::new_from_slice(secret).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            Algorithm::HmacSha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(secret).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+}
+
+impl Algorithm {
+    // algorithms are identified by a domain name in the TSIG record (rfc 8945 6)
+    fn name(&self) -> Name {
+        // the names are valid constants
+        self.to_string().parse().unwrap()
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_end_matches('.').to_ascii_lowercase().as_str() {
+            "hmac-sha256" => Ok(Algorithm::HmacSha256),
+            "hmac-sha384" => Ok(Algorithm::HmacSha384),
+            "hmac-sha512" => Ok(Algorithm::HmacSha512),
+            _ => Err(anyhow!("unsupported TSIG algorithm: {}", s)),
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Algorithm::HmacSha256 => write!(f, "hmac-sha256"),
+            Algorithm::HmacSha384 => write!(f, "hmac-sha384"),
+            Algorithm::HmacSha512 => write!(f, "hmac-sha512"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TsigKey {
+    pub name: Name,
+    pub algorithm: Algorithm,
+    pub secret: Vec<u8>,
+}
+
+impl TsigKey {
+    fn new(name: &str, algorithm: Algorithm, secret: &str) -> Result<TsigKey, anyhow::Error> {
+        let secret = STANDARD
+            .decode(secret)
+            .with_context(|| format!("secret of key {} is not valid base64", name))?;
+        Ok(TsigKey {
+            name: name.parse()?,
+            algorithm,
+            secret,
+        })
+    }
+
+    // reads the first key of a bind style key file, as written by tsig-keygen:
+    //
+    // key "name" {
+    //     algorithm hmac-sha256;
+    //     secret "base64";
+    // };
+    pub fn from_file(path: &Path) -> Result<TsigKey, anyhow::Error> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("could not read key file: {}", path.display()))?;
+        let tokens = content
+            .split(|c: char| c.is_whitespace() || c == ';' || c == '{' || c == '}')
+            .filter(|t| !t.is_empty())
+            .map(|t| t.trim_matches('"'))
+            .collect::<Vec<_>>();
+
+        let value_of = |keyword: &str| {
+            tokens
+                .iter()
+                .position(|t| *t == keyword)
+                .and_then(|i| tokens.get(i + 1))
+                .ok_or_else(|| anyhow!("key file {} has no {}", path.display(), keyword))
+        };
+        TsigKey::new(
+            value_of("key")?,
+            value_of("algorithm")?.parse()?,
+            value_of("secret")?,
+        )
+    }
+}
+
+// parses a key given as `[algorithm:]name:secret`, the algorithm defaults to hmac-sha256
+impl FromStr for TsigKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        match parts.as_slice() {
+            [name, secret] => TsigKey::new(name, Algorithm::HmacSha256, secret),
+            [algorithm, name, secret] => TsigKey::new(name, algorithm.parse()?, secret),
+            _ => Err(anyhow!("expected a key as [algorithm:]name:secret")),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+// the TSIG variables that are digested together with the message (rfc 8945 section 4.3.3)
+fn variables(key: &TsigKey, time_signed: u64, fudge: u16, error: u16, other: &[u8]) -> Vec<u8> {
+    let mut bytes = key.name.to_lowercase().serialize().unwrap_or_default();
+    bytes.extend(u16::from(&QClass::ANY).to_be_bytes());
+    bytes.extend(0u32.to_be_bytes());
+    bytes.extend(key.algorithm.name().serialize().unwrap_or_default());
+    bytes.extend(timers(time_signed, fudge));
+    bytes.extend(error.to_be_bytes());
+    bytes.extend((other.len() as u16).to_be_bytes());
+    bytes.extend(other);
+    bytes
+}
+
+fn timers(time_signed: u64, fudge: u16) -> Vec<u8> {
+    let mut bytes = time_signed.to_be_bytes()[2..].to_vec();
+    bytes.extend(fudge.to_be_bytes());
+    bytes
+}
+
+fn with_length(mac: &[u8]) -> Vec<u8> {
+    let mut bytes = (mac.len() as u16).to_be_bytes().to_vec();
+    bytes.extend(mac);
+    bytes
+}
+
+// Signs a message by appending a TSIG record to its additional section. The returned MAC is
+// needed to verify the response.
+pub fn sign(message: &mut Message, key: &TsigKey) -> Result<Vec<u8>, anyhow::Error> {
+    let time_signed = now();
+    let mut data = message.serialize()?;
+    data.extend(variables(key, time_signed, FUDGE, 0, &[]));
+    let mac = key.algorithm.mac(&key.secret, &data);
+
+    message.additionals.push(Record::new(
+        key.name.clone(),
+        QType::TSIG,
+        QClass::ANY,
+        Duration::ZERO,
+        0,
+        RData::TSIG {
+            algorithm: key.algorithm.name(),
+            time_signed,
+            fudge: FUDGE,
+            mac: mac.clone(),
+            original_id: message.header.id,
+            error: 0,
+            other: Vec::new(),
+        },
+    ));
+    Ok(mac)
+}
+
+// Splits a signed message in the TSIG record and the message as it was before it was signed,
+// that is without the TSIG record, with the additional count decremented and the original id.
+fn strip(raw: &[u8]) -> Result<Option<(Vec<u8>, Record)>, anyhow::Error> {
+    let message = Message::try_from(raw)?;
+    let tsig = match message.additionals.last() {
+        Some(record) if record.qtype == QType::TSIG => record.clone(),
+        _ => return Ok(None),
+    };
+    let RData::TSIG { original_id, .. } = tsig.rdata else {
+        return Err(anyhow!("malformed TSIG record"));
+    };
+
+    let malformed = |_| anyhow!("malformed message");
+    let mut rest = &raw[12..];
+    for _ in 0..message.header.qd_count {
+        let (buffer, _) = parse_name(rest, raw).map_err(malformed)?;
+        rest = &buffer[4..];
+    }
+    let header = &message.header;
+    let records = header.an_count + header.ns_count + header.ar_count - 1;
+    for _ in 0..records {
+        let (buffer, _) = parse_record(rest, raw).map_err(malformed)?;
+        rest = buffer;
+    }
+
+    let mut unsigned = raw[..raw.len() - rest.len()].to_vec();
+    unsigned[0..2].copy_from_slice(&original_id.to_be_bytes());
+    unsigned[10..12].copy_from_slice(&(header.ar_count - 1).to_be_bytes());
+    Ok(Some((unsigned, tsig)))
+}
+
+// Verifies the TSIG of the responses to a signed request. A zone transfer can span many
+// messages, each signature then covers the previous MAC and every message since it, and only
+// the timers of the TSIG variables (rfc 8945 section 5.3.1).
+pub struct Verifier {
+    key: TsigKey,
+    previous_mac: Vec<u8>,
+    unsigned: Vec<u8>,
+    first: bool,
+}
+
+impl Verifier {
+    pub fn new(key: &TsigKey, request_mac: Vec<u8>) -> Verifier {
+        Verifier {
+            key: key.clone(),
+            previous_mac: request_mac,
+            unsigned: Vec::new(),
+            first: true,
+        }
+    }
+
+    pub fn verify(&mut self, raw: &[u8]) -> Result<(), anyhow::Error> {
+        let Some((message, tsig)) = strip(raw)? else {
+            if self.first {
+                return Err(anyhow!("TSIG: response is not signed"));
+            }
+            self.unsigned.extend(raw);
+            return Ok(());
+        };
+        let RData::TSIG {
+            algorithm,
+            time_signed,
+            fudge,
+            mac,
+            error,
+            other,
+            ..
+        } = &tsig.rdata
+        else {
+            return Err(anyhow!("malformed TSIG record"));
+        };
+
+        if *error != 0 {
+            return Err(anyhow!(
+                "TSIG: server rejected the request: {}",
+                tsig_error_name(*error)
+            ));
+        }
+        if tsig.name != self.key.name || *algorithm != self.key.algorithm.name() {
+            return Err(anyhow!("TSIG: response signed with an unknown key, BADKEY"));
+        }
+
+        let mut data = with_length(&self.previous_mac);
+        data.extend(&self.unsigned);
+        data.extend(message);
+        if self.first {
+            data.extend(variables(&self.key, *time_signed, *fudge, *error, other));
+        } else {
+            data.extend(timers(*time_signed, *fudge));
+        }
+        let expected = self.key.algorithm.mac(&self.key.secret, &data);
+        if expected != *mac {
+            return Err(anyhow!(
+                "TSIG: signature of response does not match, BADSIG"
+            ));
+        }
+        if now().abs_diff(*time_signed) > *fudge as u64 {
+            return Err(anyhow!(
+                "TSIG: response signed at {} is outside the fudge of {} seconds, BADTIME",
+                time_signed,
+                fudge
+            ));
+        }
+
+        self.previous_mac = mac.clone();
+        self.unsigned.clear();
+        self.first = false;
+        Ok(())
+    }
+
+    // the last message of a transfer must be signed
+    pub fn finish(&self) -> Result<(), anyhow::Error> {
+        if self.unsigned.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("TSIG: last message of the response is not signed"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn key() -> TsigKey {
+        "hmac-sha256:transfer-key:c2VjcmV0LXNlY3JldC1zZWNyZXQ="
+            .parse()
+            .unwrap()
+    }
+
+    fn response(request: &Message) -> Message {
+        let mut response = request.clone();
+        response.header.qr = true;
+        response.additionals.clear();
+        response
+    }
+
+    // signs a response the way a server would, covering the MAC and unsigned messages before it
+    fn server_sign(
+        response: &mut Message,
+        key: &TsigKey,
+        previous_mac: &[u8],
+        unsigned: &[u8],
+        first: bool,
+    ) {
+        let time_signed = now();
+        let mut data = with_length(previous_mac);
+        data.extend(unsigned);
+        data.extend(response.serialize().unwrap());
+        if first {
+            data.extend(variables(key, time_signed, FUDGE, 0, &[]));
+        } else {
+            data.extend(timers(time_signed, FUDGE));
+        }
+        let mac = key.algorithm.mac(&key.secret, &data);
+        response.additionals.push(Record::new(
+            key.name.clone(),
+            QType::TSIG,
+            QClass::ANY,
+            Duration::ZERO,
+            0,
+            RData::TSIG {
+                algorithm: key.algorithm.name(),
+                time_signed,
+                fudge: FUDGE,
+                mac,
+                original_id: response.header.id,
+                error: 0,
+                other: Vec::new(),
+            },
+        ));
+    }
+
+    fn last_mac(message: &Message) -> Vec<u8> {
+        match &message.additionals.last().unwrap().rdata {
+            RData::TSIG { mac, .. } => mac.clone(),
+            _ => panic!("not signed"),
+        }
+    }
+
+    #[test]
+    fn parse_key() {
+        let key: TsigKey = "hmac-sha512:name.:c2VjcmV0".parse().unwrap();
+        assert_eq!("name", key.name);
+        assert_eq!(Algorithm::HmacSha512, key.algorithm);
+        assert_eq!(b"secret".to_vec(), key.secret);
+        assert!("hmac-md5:name:c2VjcmV0".parse::<TsigKey>().is_err());
+    }
+
+    #[test]
+    fn verify_chained_responses() {
+        let key = key();
+        let mut request = Message::axfr("example.com".parse().unwrap());
+        let request_mac = sign(&mut request, &key).unwrap();
+
+        let mut first = response(&request);
+        server_sign(&mut first, &key, &request_mac, &[], true);
+        let unsigned = response(&request).serialize().unwrap();
+        let mut last = response(&request);
+        server_sign(&mut last, &key, &last_mac(&first), &unsigned, false);
+
+        let mut verifier = Verifier::new(&key, request_mac);
+        verifier.verify(&first.serialize().unwrap()).unwrap();
+        verifier.verify(&unsigned).unwrap();
+        assert!(verifier.finish().is_err());
+        verifier.verify(&last.serialize().unwrap()).unwrap();
+        verifier.finish().unwrap();
+    }
+
+    #[test]
+    fn reject_bad_signature() {
+        let key = key();
+        let mut request = Message::a("example.com".parse().unwrap());
+        let request_mac = sign(&mut request, &key).unwrap();
+
+        let mut response = response(&request);
+        server_sign(&mut response, &key, &[0; 32], &[], true);
+
+        let mut verifier = Verifier::new(&key, request_mac);
+        let error = verifier.verify(&response.serialize().unwrap()).unwrap_err();
+        assert!(error.to_string().contains("BADSIG"));
+    }
+}
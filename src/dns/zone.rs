@@ -0,0 +1,511 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+
+use super::name::Name;
+use super::presentation::{parse_name, parse_rdata_in, rdata_text, tokenize};
+use super::record::{RData, Record};
+use super::{QClass, QType, Serialize};
+
+// The records of a master file (rfc 1035 5) and the origin they were read with.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    pub origin: Name,
+    pub records: Vec<Record>,
+}
+
+// how deep $INCLUDE may nest, deeper is most likely a file that includes itself
+const MAX_INCLUDE_DEPTH: usize = 16;
+// the most records a single $GENERATE may make
+const MAX_GENERATE: u64 = 65_536;
+
+// Reads a ttl, in seconds or with the units BIND accepts: 1h30m, 2d, 1w.
+pub fn parse_ttl(token: &str) -> Result<u32> {
+    if let Ok(seconds) = token.parse() {
+        return Ok(seconds);
+    }
+    let mut total: u32 = 0;
+    let mut number = String::new();
+    for c in token.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 604800,
+            _ => return Err(anyhow!("invalid ttl: {}", token)),
+        };
+        let value: u32 = number
+            .parse()
+            .map_err(|_| anyhow!("invalid ttl: {}", token))?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(|| anyhow!("ttl too large: {}", token))?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(anyhow!("invalid ttl: {}", token));
+    }
+    Ok(total)
+}
+
+// Takes the parentheses out of a line, the ones outside of quotes and comments, and tells how
+// many it leaves open. A record continues on the next line as long as one is open.
+fn parentheses(line: &str) -> (String, i32) {
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut clean = String::with_capacity(line.len());
+    for c in line.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == ';' && !quoted {
+            break;
+        } else if (c == '(' || c == ')') && !quoted {
+            depth += if c == '(' { 1 } else { -1 };
+            clean.push(' ');
+            continue;
+        }
+        clean.push(c);
+    }
+    (clean, depth)
+}
+
+// A master file being read: the origin, default ttl and the owner, ttl and class of the last
+// record, which a record that leaves them out inherits. Included files are found relative to
+// the directory of the file that includes them.
+struct Reader {
+    directory: PathBuf,
+    depth: usize,
+    origin: Option<Name>,
+    default_ttl: Option<u32>,
+    last_owner: Option<Name>,
+    last_ttl: Option<u32>,
+    last_class: QClass,
+    records: Vec<Record>,
+}
+
+impl Reader {
+    fn origin(&self) -> Result<&Name> {
+        self.origin
+            .as_ref()
+            .ok_or_else(|| anyhow!("no origin, set one with $ORIGIN"))
+    }
+
+    fn name(&self, token: &[u8]) -> Result<Name> {
+        parse_name(token, Some(self.origin()?))
+    }
+
+    // a directive or a record, `inherits` when the entry starts with white space and so
+    // belongs to the owner of the last record
+    fn entry(&mut self, tokens: &[Vec<u8>], inherits: bool) -> Result<()> {
+        let text = |token: &[u8]| str::from_utf8(token).map(str::to_owned);
+        match tokens.first().map(|t| text(t)).transpose()?.as_deref() {
+            Some(directive) if directive.starts_with('$') && !inherits => {
+                return self.directive(directive, &tokens[1..]);
+            }
+            None => return Ok(()),
+            _ => {}
+        }
+
+        let mut tokens = tokens.iter();
+        let owner = if inherits {
+            self.last_owner
+                .clone()
+                .ok_or_else(|| anyhow!("no owner for the record, none came before it"))?
+        } else {
+            self.name(tokens.next().expect("the entry is not empty"))?
+        };
+
+        // the ttl and class can come in either order before the type
+        let mut ttl = None;
+        let mut class = None;
+        let qtype = loop {
+            let token = tokens
+                .next()
+                .ok_or_else(|| anyhow!("the record has no type"))?;
+            let token = text(token)?;
+            if ttl.is_none() && token.starts_with(|c: char| c.is_ascii_digit()) {
+                ttl = Some(parse_ttl(&token)?);
+            } else if let (None, Ok(c)) = (&class, token.parse::<QClass>()) {
+                class = Some(c);
+            } else {
+                break token.parse::<QType>()?;
+            }
+        };
+
+        let rdata = parse_rdata_in(&qtype, tokens.as_slice(), Some(self.origin()?))?;
+        let ttl = match (ttl, self.default_ttl, self.last_ttl, &rdata) {
+            (Some(ttl), ..) => ttl,
+            (None, Some(ttl), ..) => ttl,
+            (None, None, Some(ttl), _) => ttl,
+            // the minimum of the SOA was the default before $TTL (rfc 2308 4)
+            (None, None, None, RData::SOA { minimum, .. }) => *minimum,
+            _ => return Err(anyhow!("no ttl for the record and no $TTL before it")),
+        };
+        let class = class.unwrap_or_else(|| self.last_class.clone());
+
+        let rd_length = rdata.serialize()?.len().try_into()?;
+        self.records.push(Record::new(
+            owner.clone(),
+            qtype,
+            class.clone(),
+            Duration::from_secs(ttl.into()),
+            rd_length,
+            rdata,
+        ));
+        self.last_owner = Some(owner);
+        self.last_ttl = Some(ttl);
+        self.last_class = class;
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str, arguments: &[Vec<u8>]) -> Result<()> {
+        let argument = |i: usize| -> Result<&str> {
+            let token = arguments
+                .get(i)
+                .ok_or_else(|| anyhow!("{} needs an argument", directive))?;
+            Ok(str::from_utf8(token)?)
+        };
+        match directive.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let origin = parse_name(argument(0)?.as_bytes(), self.origin.as_ref())?;
+                self.origin = Some(origin);
+            }
+            "$TTL" => self.default_ttl = Some(parse_ttl(argument(0)?)?),
+            "$INCLUDE" => {
+                let origin = match arguments.get(1) {
+                    Some(origin) => Some(parse_name(origin, self.origin.as_ref())?),
+                    None => self.origin.clone(),
+                };
+                self.include(&self.directory.join(argument(0)?), origin)?;
+            }
+            "$GENERATE" => self.generate(argument(0)?, &arguments[1..])?,
+            _ => return Err(anyhow!("{} is not supported", directive)),
+        }
+        Ok(())
+    }
+
+    // reads a file in the middle of this one, an origin given for it only holds within the
+    // file (rfc 1035 5.1)
+    fn include(&mut self, path: &Path, origin: Option<Name>) -> Result<()> {
+        if self.depth == MAX_INCLUDE_DEPTH {
+            return Err(anyhow!("more than {} nested $INCLUDEs", MAX_INCLUDE_DEPTH));
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let origin = std::mem::replace(&mut self.origin, origin);
+        let directory = std::mem::replace(
+            &mut self.directory,
+            path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        );
+        self.depth += 1;
+        let result = self
+            .read(&text)
+            .with_context(|| format!("in {}", path.display()));
+        self.depth -= 1;
+        self.origin = origin;
+        self.directory = directory;
+        result
+    }
+
+    // Makes a record for every number in a range, `start-stop[/step]`, with `$` in the owner and
+    // rdata replaced by the number. `${offset,width,base}` adds to it and formats it in base d, o,
+    // x or X padded to the width, `\$` is a dollar sign. This is what BIND does to write out a
+    // run of PTR or A records in one line.
+    fn generate(&mut self, range: &str, template: &[Vec<u8>]) -> Result<()> {
+        let invalid = || anyhow!("invalid range for $GENERATE: {}", range);
+        let (range, step) = match range.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().map_err(|_| invalid())?),
+            None => (range, 1),
+        };
+        let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+        let (start, stop): (u64, u64) = (
+            start.parse().map_err(|_| invalid())?,
+            stop.parse().map_err(|_| invalid())?,
+        );
+        if step == 0 || start > stop || (stop - start) / step >= MAX_GENERATE {
+            return Err(invalid());
+        }
+        if template.len() < 3 {
+            return Err(anyhow!("$GENERATE needs an owner, a type and rdata"));
+        }
+        for value in (start..=stop).step_by(step as usize) {
+            let tokens = template
+                .iter()
+                .map(|token| substitute(token, value))
+                .collect::<Result<Vec<_>>>()?;
+            self.entry(&tokens, false)?;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, text: &str) -> Result<()> {
+        let mut lines = text.lines().enumerate();
+        while let Some((number, line)) = lines.next() {
+            let inherits = line.starts_with([' ', '\t']);
+            let (mut entry, mut depth) = parentheses(line);
+            while depth > 0 {
+                let (_, next) = lines
+                    .next()
+                    .ok_or_else(|| anyhow!("line {}: unclosed parenthesis", number + 1))?;
+                let (clean, open) = parentheses(next);
+                entry.push(' ');
+                entry.push_str(&clean);
+                depth += open;
+            }
+            let tokens = tokenize(&entry).with_context(|| format!("line {}", number + 1))?;
+            self.entry(&tokens, inherits)
+                .with_context(|| format!("line {}: {}", number + 1, line.trim()))?;
+        }
+        Ok(())
+    }
+}
+
+// a $GENERATE template with the number put in for every `$`
+fn substitute(token: &[u8], value: u64) -> Result<Vec<u8>> {
+    let mut result = Vec::with_capacity(token.len());
+    let mut bytes = token.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => {
+                result.push(b);
+                result.extend(bytes.next());
+            }
+            b'$' if bytes.peek() == Some(&b'{') => {
+                bytes.next();
+                let modifier = bytes
+                    .by_ref()
+                    .take_while(|b| *b != b'}')
+                    .collect::<Vec<_>>();
+                let modifier = str::from_utf8(&modifier)?;
+                let invalid = || anyhow!("invalid $GENERATE modifier: ${{{}}}", modifier);
+                let mut parts = modifier.split(',');
+                let offset: i64 = parts.next().unwrap_or("0").parse().map_err(|_| invalid())?;
+                let width: usize = match parts.next() {
+                    Some(width) => width.parse().map_err(|_| invalid())?,
+                    None => 0,
+                };
+                let number = u64::try_from(value as i64 + offset).map_err(|_| invalid())?;
+                let text = match parts.next().unwrap_or("d") {
+                    "d" => format!("{:0width$}", number),
+                    "o" => format!("{:0width$o}", number),
+                    "x" => format!("{:0width$x}", number),
+                    "X" => format!("{:0width$X}", number),
+                    _ => return Err(invalid()),
+                };
+                result.extend(text.bytes());
+            }
+            b'$' => result.extend(value.to_string().bytes()),
+            _ => result.push(b),
+        }
+    }
+    Ok(result)
+}
+
+// Reads the records of a master file. Names are relative to the origin that is given or set in
+// the file with $ORIGIN, records that leave out the owner, ttl or class take them from the record
+// before. $INCLUDE finds files relative to the current directory.
+pub fn parse(text: &str, origin: Option<Name>) -> Result<Zone> {
+    parse_in(text, origin, PathBuf::new())
+}
+
+// reads a master file from disk, $INCLUDE finds files relative to it
+pub fn read(path: &Path, origin: Option<Name>) -> Result<Zone> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read {}", path.display()))?;
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    parse_in(&text, origin, directory).with_context(|| format!("reading {}", path.display()))
+}
+
+fn parse_in(text: &str, origin: Option<Name>, directory: PathBuf) -> Result<Zone> {
+    let mut reader = Reader {
+        directory,
+        depth: 0,
+        origin,
+        default_ttl: None,
+        last_owner: None,
+        last_ttl: None,
+        last_class: QClass::IN,
+        records: Vec::new(),
+    };
+    reader.read(text)?;
+
+    let origin = reader
+        .origin
+        .ok_or_else(|| anyhow!("the zone has no origin"))?;
+    Ok(Zone {
+        origin,
+        records: reader.records,
+    })
+}
+
+// Writes the zone out in a canonical form: its origin, the SOA, then the records in the
+// canonical order of their owners (rfc 4034 6.1) and their types, one record to a line with
+// every name fully qualified.
+impl Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut records = self.records.iter().collect::<Vec<_>>();
+        records.sort_by(|a, b| {
+            (b.qtype == QType::SOA)
+                .cmp(&(a.qtype == QType::SOA))
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| u16::from(&a.qtype).cmp(&u16::from(&b.qtype)))
+        });
+        let absolute = |name: &Name| match name.is_root() {
+            true => ".".to_owned(),
+            false => format!("{}.", name),
+        };
+        writeln!(f, "$ORIGIN {}", absolute(&self.origin))?;
+        for record in records {
+            writeln!(
+                f,
+                "{}\t{}\t{}\t{}\t{}",
+                absolute(&record.name),
+                record.ttl.as_secs(),
+                record.qclass,
+                record.qtype,
+                rdata_text(&record.rdata)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_master_file() {
+        let text = r#"
+$ORIGIN example.com.
+$TTL 1h
+@   IN  SOA ns1 hostmaster (
+            2024010101 ; serial
+            3600 600 86400 300 )
+        NS  ns1
+        NS  ns.example.net.
+        MX  10 mail
+ns1 300 A   192.0.2.1
+    IN 60 AAAA 2001:db8::1
+www     CNAME @
+txt     TXT "a ( b" c
+"#;
+        let zone = parse(text, None).unwrap();
+        let lines = zone
+            .records
+            .iter()
+            .map(|r| {
+                format!(
+                    "{} {} {} {} {}",
+                    r.name,
+                    r.ttl.as_secs(),
+                    r.qclass,
+                    r.qtype,
+                    r.rdata
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "example.com 3600 IN SOA ns1.example.com, hostmaster.example.com, 2024010101, 3600, 600, 86400, 300",
+                "example.com 3600 IN NS ns1.example.com",
+                "example.com 3600 IN NS ns.example.net",
+                "example.com 3600 IN MX 10 mail.example.com",
+                "ns1.example.com 300 IN A 192.0.2.1",
+                "ns1.example.com 60 IN AAAA 2001:db8::1",
+                "www.example.com 3600 IN CNAME example.com",
+                r#"txt.example.com 3600 IN TXT "a ( b" "c""#,
+            ],
+            lines
+        );
+
+        assert_eq!(5400, parse_ttl("1h30m").unwrap());
+        assert!(parse("www A 192.0.2.1", None).is_err());
+        assert!(parse("$ORIGIN example.com.\n@ SOA ns1 host ( 1 2 3 4", None).is_err());
+    }
+
+    #[test]
+    fn include_generate_and_write() {
+        let directory = std::env::temp_dir().join(format!("who-zone-{}", rand::random::<u32>()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("hosts.db"),
+            "$GENERATE 1-5/2 host-$ A 192.0.2.$\n$GENERATE 9-10 ${1,3,x} CNAME host-\\$\n",
+        )
+        .unwrap();
+        let path = directory.join("example.db");
+        std::fs::write(
+            &path,
+            "$ORIGIN example.com.
+$TTL 300
+@       SOA ns1 hostmaster 1 2 3 4 5
+$INCLUDE hosts.db lab
+www     A   192.0.2.80
+",
+        )
+        .unwrap();
+        let zone = read(&path, None).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            "$ORIGIN example.com.
+example.com.\t300\tIN\tSOA\tns1.example.com. hostmaster.example.com. 1 2 3 4 5
+00a.lab.example.com.\t300\tIN\tCNAME\thost-\\$.lab.example.com.
+00b.lab.example.com.\t300\tIN\tCNAME\thost-\\$.lab.example.com.
+host-1.lab.example.com.\t300\tIN\tA\t192.0.2.1
+host-3.lab.example.com.\t300\tIN\tA\t192.0.2.3
+host-5.lab.example.com.\t300\tIN\tA\t192.0.2.5
+www.example.com.\t300\tIN\tA\t192.0.2.80
+",
+            zone.to_string()
+        );
+        let again = parse(&zone.to_string(), None).unwrap();
+        assert_eq!(zone.to_string(), again.to_string());
+
+        assert!(parse("$ORIGIN x.\n$GENERATE 5-1 a$ A 192.0.2.$", None).is_err());
+        assert!(parse("$ORIGIN x.\n$GENERATE 1-2 a${0,1,n} A 192.0.2.1", None).is_err());
+    }
+
+    #[test]
+    fn reverse_zone_and_generic_types() {
+        let text = r"$ORIGIN 2.0.192.in-addr.arpa.
+$TTL 1d
+@   SOA ns1.example.com. hostmaster.example.com. 1 2 3 4 5
+$GENERATE 1-3 $ PTR host-$.example.com.
+x   CLASS1 TYPE65280 \# 2 abcd
+y   TYPE1 \# 4 C0000201
+";
+        let zone = parse(text, None).unwrap();
+        assert_eq!(
+            "$ORIGIN 2.0.192.in-addr.arpa.
+2.0.192.in-addr.arpa.\t86400\tIN\tSOA\tns1.example.com. hostmaster.example.com. 1 2 3 4 5
+1.2.0.192.in-addr.arpa.\t86400\tIN\tPTR\thost-1.example.com.
+2.2.0.192.in-addr.arpa.\t86400\tIN\tPTR\thost-2.example.com.
+3.2.0.192.in-addr.arpa.\t86400\tIN\tPTR\thost-3.example.com.
+x.2.0.192.in-addr.arpa.\t86400\tIN\tTYPE65280\t\\# 2 ABCD
+y.2.0.192.in-addr.arpa.\t86400\tIN\tA\t192.0.2.1
+",
+            zone.to_string()
+        );
+        assert_eq!(
+            zone.to_string(),
+            parse(&zone.to_string(), None).unwrap().to_string()
+        );
+        assert!(parse("$ORIGIN x.\ny TYPE65280 abcd", None).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+
+const ACE_PREFIX: &str = "xn--";
+
+// converts a name typed by the user to the A-labels that go on the wire (rfc 5891), using the
+// UTS #46 mapping so `MÜNCHEN.de` and `münchen.de` end up the same. Plain ascii names are left
+// alone, the mapping would otherwise reject names like `_dmarc.example.com`.
+pub fn to_ascii(name: &str) -> Result<String> {
+    if name.is_ascii() {
+        return Ok(name.to_owned());
+    }
+    idna::Config::default()
+        .to_ascii(name)
+        .map_err(|e| anyhow!("{} is not a valid internationalized name: {:?}", name, e))
+}
+
+// the U-label form of a name that contains A-labels, None when there is nothing to convert
+pub fn to_unicode(name: &str) -> Option<String> {
+    let encoded = name
+        .split('.')
+        .any(|label| label.len() > 4 && label[..4].eq_ignore_ascii_case(ACE_PREFIX));
+    if !encoded {
+        return None;
+    }
+    match idna::domain_to_unicode(name) {
+        (unicode, Ok(())) if unicode != name => Some(unicode),
+        _ => None,
+    }
+}
+
+// the name followed by its U-labels, the way names are shown in the tui
+pub fn display(name: &str) -> String {
+    match to_unicode(name) {
+        Some(unicode) => format!("{} ({})", name, unicode),
+        None => name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn convert_both_ways() {
+        assert_eq!("xn--mnchen-3ya.de", to_ascii("München.de").unwrap());
+        assert_eq!(
+            "_dmarc.Example.com",
+            to_ascii("_dmarc.Example.com").unwrap()
+        );
+        assert_eq!(
+            Some("münchen.de".to_owned()),
+            to_unicode("xn--mnchen-3ya.de")
+        );
+        assert_eq!(None, to_unicode("www.example.com"));
+        assert_eq!(
+            "xn--mnchen-3ya.de (münchen.de)",
+            display("xn--mnchen-3ya.de")
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use std::fmt::Display;
+use std::io::{self, Stdout};
+
+use anyhow::{anyhow, Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::{prelude::*, widgets::*};
+use unicode_width::UnicodeWidthStr;
+
+use crate::client::{parse_server, resolve_server};
+use crate::cookies::CookieJar;
+use crate::dns::message::Message;
+use crate::dns::record::RData;
+use crate::dns::tsig::TsigKey;
+use crate::dns::QType;
+use crate::layout::wrap_lines;
+use crate::{
+    display_name, header_text, lookup, query_name, question_grid, record_grid, records_title,
+    statistics_text, Cli, Statistics, BORDER_SIZE, VERSION,
+};
+
+const KEYS: &str = "/ query  t type  s server  r re-run  tab pane  ↑↓ scroll  enter follow  q quit";
+const PAGE: u16 = 10;
+
+// A lookup as the user asked for it, the name is converted when it is sent so the history shows
+// what was typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub name: String,
+    pub qtype: QType,
+    pub server: String,
+}
+
+impl Query {
+    // reads a query line, `name [type] [@server]`. Whatever is left out is taken from the
+    // current query.
+    fn parse(line: &str, current: &Query) -> Result<Query> {
+        let mut query = Query {
+            name: String::new(),
+            ..current.clone()
+        };
+        for token in line.split_whitespace() {
+            if let Some(server) = token.strip_prefix('@') {
+                query.server = parse_server(server).map_err(|e| anyhow!(e))?;
+            } else if query.name.is_empty() {
+                query.name = token.to_owned();
+            } else {
+                query.qtype = token.parse()?;
+            }
+        }
+        if query.name.is_empty() {
+            return Err(anyhow!("type a name to look up"));
+        }
+        Ok(query)
+    }
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} @{}", self.name, self.qtype, self.server)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Query,
+    Type,
+    Server,
+}
+
+impl Prompt {
+    fn title(&self) -> &'static str {
+        match self {
+            Prompt::Query => "Query (name [type] [@server])",
+            Prompt::Type => "Type",
+            Prompt::Server => "Server",
+        }
+    }
+}
+
+// what the keys act on, the panes or the input line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Input(Prompt),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Header,
+    Message,
+    Records,
+    Statistics,
+}
+
+impl Pane {
+    const ALL: [Pane; 4] = [Pane::Header, Pane::Message, Pane::Records, Pane::Statistics];
+
+    fn cycle(self, forward: bool) -> Pane {
+        let index = Pane::ALL.iter().position(|p| *p == self).unwrap_or(0);
+        let next = if forward {
+            index + 1
+        } else {
+            index + Pane::ALL.len() - 1
+        };
+        Pane::ALL[next % Pane::ALL.len()]
+    }
+}
+
+struct App<'a> {
+    cli: &'a Cli,
+    tsig_key: Option<&'a TsigKey>,
+    cookies: CookieJar,
+    current: Query,
+    history: Vec<Query>,
+    // the history entry shown in the input line while going through it with up and down
+    recall: Option<usize>,
+    mode: Mode,
+    input: String,
+    focus: Pane,
+    // scroll offsets of the header, message and statistics panes
+    scroll: [u16; 3],
+    records: TableState,
+    response: Option<(Message, Statistics)>,
+    status: Result<String, String>,
+    pending: Option<Query>,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(cli: &'a Cli, query: Query, tsig_key: Option<&'a TsigKey>) -> App<'a> {
+        App {
+            cli,
+            tsig_key,
+            cookies: cli.cookie_jar(),
+            current: query,
+            history: Vec::new(),
+            recall: None,
+            mode: Mode::Browse,
+            input: String::new(),
+            focus: Pane::Records,
+            scroll: [0; 3],
+            records: TableState::default(),
+            response: None,
+            status: Ok(KEYS.to_owned()),
+            pending: None,
+            quit: false,
+        }
+    }
+
+    async fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        while !self.quit {
+            if let Some(query) = self.pending.take() {
+                self.status = Ok(format!("asking {} ...", query));
+                terminal.draw(|f| self.render(f))?;
+                self.ask(query).await;
+            }
+            terminal.draw(|f| self.render(f))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.key(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn ask(&mut self, query: Query) {
+        match self.lookup(&query).await {
+            Ok((message, stats)) => {
+                self.status = Ok(format!(
+                    "{}: {} in {} msec",
+                    query,
+                    message.header.r_code,
+                    stats.query_time.as_millis()
+                ));
+                self.records
+                    .select(Some(0).filter(|_| !message.records.is_empty()));
+                self.scroll = [0; 3];
+                self.response = Some((message, stats));
+            }
+            Err(e) => self.status = Err(format!("{}: {:#}", query, e)),
+        }
+        if self.history.last() != Some(&query) {
+            self.history.push(query.clone());
+        }
+        self.current = query;
+    }
+
+    async fn lookup(&mut self, query: &Query) -> Result<(Message, Statistics)> {
+        let name = query_name(&query.name, self.cli.idn())?;
+        let server = resolve_server(&query.server).await?;
+        let message = Message::query(name, query.qtype.clone(), self.cli.class.clone())
+            .with_flags(&self.cli.request_flags());
+        let response = lookup(self.cli, &message, server, self.tsig_key, &mut self.cookies).await;
+        self.cookies.save();
+        response
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        match self.mode {
+            Mode::Browse => self.browse_key(key),
+            Mode::Input(prompt) => self.input_key(prompt, key),
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('/') | KeyCode::Char('i') => self.edit(),
+            KeyCode::Char('t') => {
+                self.input.clear();
+                self.mode = Mode::Input(Prompt::Type);
+            }
+            KeyCode::Char('s') => {
+                self.input.clear();
+                self.mode = Mode::Input(Prompt::Server);
+            }
+            KeyCode::Char('r') if !self.current.name.is_empty() => {
+                self.pending = Some(self.current.clone())
+            }
+            KeyCode::Tab => self.focus = self.focus.cycle(true),
+            KeyCode::BackTab => self.focus = self.focus.cycle(false),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll(1),
+            KeyCode::PageUp => self.scroll(-(PAGE as i32)),
+            KeyCode::PageDown => self.scroll(PAGE as i32),
+            KeyCode::Enter => self.follow(),
+            _ => (),
+        }
+    }
+
+    // opens the input line with the current query to edit
+    fn edit(&mut self) {
+        self.input = if self.current.name.is_empty() {
+            String::new()
+        } else {
+            self.current.to_string()
+        };
+        self.mode = Mode::Input(Prompt::Query);
+    }
+
+    fn input_key(&mut self, prompt: Prompt, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Browse,
+            KeyCode::Enter => {
+                match self.submit(prompt) {
+                    Ok(query) => self.pending = Some(query),
+                    Err(e) => self.status = Err(e.to_string()),
+                }
+                self.recall = None;
+                self.mode = Mode::Browse;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Up if prompt == Prompt::Query => self.recall(-1),
+            KeyCode::Down if prompt == Prompt::Query => self.recall(1),
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.clear()
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            _ => (),
+        }
+    }
+
+    fn submit(&self, prompt: Prompt) -> Result<Query> {
+        let input = self.input.trim();
+        match prompt {
+            Prompt::Query => Query::parse(input, &self.current),
+            Prompt::Type => Ok(Query {
+                qtype: input.parse()?,
+                ..self.current.clone()
+            }),
+            Prompt::Server => Ok(Query {
+                server: parse_server(input).map_err(|e| anyhow!(e))?,
+                ..self.current.clone()
+            }),
+        }
+    }
+
+    // walks through the history in the input line, older with up and newer with down
+    fn recall(&mut self, step: i32) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() - 1;
+        let index = match (self.recall, step < 0) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(0), true) => Some(0),
+            (Some(i), true) => Some(i - 1),
+            (Some(i), false) if i < last => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        self.recall = index;
+        self.input = match index {
+            Some(i) => self.history[i].to_string(),
+            None => String::new(),
+        };
+    }
+
+    fn scroll(&mut self, step: i32) {
+        let index = match self.focus {
+            Pane::Header => 0,
+            Pane::Message => 1,
+            Pane::Statistics => 2,
+            Pane::Records => {
+                let count = self.response.as_ref().map_or(0, |(m, _)| m.records.len());
+                if count > 0 {
+                    let current = self.records.selected().unwrap_or(0) as i32;
+                    let next = (current + step).clamp(0, count as i32 - 1);
+                    self.records.select(Some(next as usize));
+                }
+                return;
+            }
+        };
+        let offset = self.scroll[index] as i32 + step;
+        self.scroll[index] = offset.max(0) as u16;
+    }
+
+    // looks up where the selected record points: the same type for a CNAME, the address of
+    // anything else
+    fn follow(&mut self) {
+        let Some((message, _)) = &self.response else {
+            return;
+        };
+        let Some(record) = self.records.selected().and_then(|i| message.records.get(i)) else {
+            return;
+        };
+        let Some(target) = record.rdata.target() else {
+            self.status = Err(format!(
+                "a {} record points nowhere to follow",
+                record.qtype
+            ));
+            return;
+        };
+        let qtype = match record.rdata {
+            RData::CNAME(_) => self.current.qtype.clone(),
+            _ => QType::A,
+        };
+        self.pending = Some(Query {
+            name: target.to_string(),
+            qtype,
+            ..self.current.clone()
+        });
+    }
+
+    fn block(&self, title: String, pane: Pane) -> Block<'static> {
+        let color = if self.focus == pane && self.mode == Mode::Browse {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        Block::new().title(title).borders(Borders::ALL).fg(color)
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let idn = self.cli.idn();
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(1),
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let title = format!("== Who are you? == v{} == interactive ==", VERSION);
+        frame.render_widget(Paragraph::new(title).fg(Color::White), outer[0]);
+
+        // Input
+        let (prompt, text) = match self.mode {
+            Mode::Input(prompt) => (prompt.title(), self.input.clone()),
+            Mode::Browse => ("Query", self.current.to_string()),
+        };
+        let input_block = Block::new()
+            .title(prompt)
+            .borders(Borders::ALL)
+            .fg(match self.mode {
+                Mode::Input(_) => Color::Yellow,
+                Mode::Browse => Color::Green,
+            });
+        frame.render_widget(
+            Paragraph::new(text.clone())
+                .fg(Color::White)
+                .block(input_block),
+            outer[1],
+        );
+        if let Mode::Input(_) = self.mode {
+            let x = outer[1].x + 1 + text.chars().count() as u16;
+            frame.set_cursor(x.min(outer[1].right().saturating_sub(2)), outer[1].y + 1);
+        }
+
+        let status = match &self.status {
+            Ok(status) => Paragraph::new(status.clone()).fg(Color::White),
+            Err(error) => Paragraph::new(error.clone()).fg(Color::Red),
+        };
+        frame.render_widget(status, outer[3]);
+
+        let Some((message, stats)) = &self.response else {
+            let hint = Paragraph::new("press / and type a name to look up")
+                .fg(Color::White)
+                .block(self.block("Records".to_owned(), Pane::Records));
+            frame.render_widget(hint, outer[2]);
+            return;
+        };
+
+        // the records get all the width the header and statistics do not need
+        let header = header_text(message);
+        let statistics = statistics_text(stats);
+        let widest = header
+            .lines()
+            .chain(statistics.lines())
+            .map(|l| l.width() as u16)
+            .max()
+            .unwrap_or(0);
+        let left_width = (widest + BORDER_SIZE).min(outer[2].width * 2 / 5);
+        let body = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Length(left_width), Constraint::Min(0)])
+            .split(outer[2]);
+
+        let text_width = left_width.saturating_sub(BORDER_SIZE);
+        let questions = question_grid(message, idn, left_width);
+        let statistics = wrap_lines(&statistics, text_width);
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Min(3),
+                Constraint::Length(questions.height() + BORDER_SIZE),
+                Constraint::Length(statistics.len() as u16 + BORDER_SIZE),
+            ])
+            .split(body[0]);
+
+        // Header
+        frame.render_widget(
+            Paragraph::new(wrap_lines(&header, text_width).join("\n"))
+                .fg(Color::White)
+                .scroll((self.scroll[0], 0))
+                .block(self.block("Header".to_owned(), Pane::Header)),
+            left[0],
+        );
+
+        // Question
+        let rows = questions.rows().into_iter().skip(self.scroll[1] as usize);
+        frame.render_widget(
+            Table::new(rows)
+                .block(self.block("Message".to_owned(), Pane::Message))
+                .widths(questions.widths()),
+            left[1],
+        );
+
+        // Statistics
+        frame.render_widget(
+            Paragraph::new(statistics.join("\n"))
+                .fg(Color::White)
+                .scroll((self.scroll[2], 0))
+                .block(self.block("Statistics".to_owned(), Pane::Statistics)),
+            left[2],
+        );
+
+        // Records
+        let records = record_grid(message, self.cli.concat, idn, body[1].width);
+        let title = match message.question() {
+            Some(q) => format!(
+                "{} of {}",
+                records_title(message),
+                display_name(&q.qname, idn)
+            ),
+            None => records_title(message),
+        };
+        let table = Table::new(records.rows())
+            .block(self.block(title, Pane::Records))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .widths(records.widths());
+        frame.render_stateful_widget(table, body[1], &mut self.records);
+    }
+}
+
+// Opens the full screen app, starting with the given query when there is one. The terminal is
+// restored however the app ends.
+pub async fn run(cli: &Cli, query: Query, ask: bool, tsig_key: Option<&TsigKey>) -> Result<()> {
+    enable_raw_mode().context("failed to enable raw mode")?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen).context("failed to open the screen")?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let mut app = App::new(cli, query, tsig_key);
+    if ask {
+        app.pending = Some(app.current.clone());
+    } else {
+        app.edit();
+    }
+    let result = app.run(&mut terminal).await;
+
+    disable_raw_mode().context("failed to disable raw mode")?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_query_line() {
+        let current = Query {
+            name: "example.com".to_owned(),
+            qtype: QType::MX,
+            server: "1.1.1.1:53".to_owned(),
+        };
+
+        let query = Query::parse("www.example.org", &current).unwrap();
+        assert_eq!("www.example.org MX @1.1.1.1:53", query.to_string());
+
+        let query = Query::parse("example.net txt @9.9.9.9", &current).unwrap();
+        assert_eq!(
+            Query {
+                name: "example.net".to_owned(),
+                qtype: QType::TXT,
+                server: "9.9.9.9:53".to_owned(),
+            },
+            query
+        );
+        assert!(Query::parse("@8.8.8.8", &current).is_err());
+        assert!(Query::parse("example.net nosuchtype", &current).is_err());
+    }
+}
@@ -0,0 +1,18 @@
+use serde_json::{json, Value};
+
+use crate::dns::record::Record;
+
+// a record in json, the rdata is kept in its presentation format
+pub fn record(record: &Record) -> Value {
+    json!({
+        "name": record.name.to_string(),
+        "ttl": record.ttl.as_secs(),
+        "class": record.qclass.to_string(),
+        "type": record.qtype.to_string(),
+        "data": record.rdata.to_string(),
+    })
+}
+
+pub fn records(records: &[Record]) -> Value {
+    Value::Array(records.iter().map(record).collect())
+}
@@ -0,0 +1,225 @@
+use std::mem;
+
+use ratatui::{prelude::*, widgets::*};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// the space ratatui leaves between two table columns
+const COLUMN_SPACING: usize = 1;
+// the first and the last column wrap when the terminal is narrow, below these widths they wrap so
+// often that dropping one of the columns in between reads better
+const MIN_FIRST_WIDTH: usize = 12;
+const MIN_LAST_WIDTH: usize = 24;
+
+// A table sized by what is in it. Every column is as wide as its widest cell. When that does not
+// fit, the first and the last column (the owner name and the record data) wrap onto more lines,
+// and on terminals too narrow even for that the optional columns are left out.
+pub struct Grid {
+    widths: Vec<Constraint>,
+    rows: Vec<Vec<Vec<String>>>,
+}
+
+impl Grid {
+    // lays out `cells` in `width` cells, `optional` lists the columns that may be dropped in the
+    // order they go
+    pub fn new(cells: Vec<Vec<String>>, width: u16, optional: &[usize]) -> Grid {
+        let width = width as usize;
+        let columns = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        let natural = (0..columns)
+            .map(|i| {
+                cells
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .flat_map(|cell| cell.lines().map(|line| line.width()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+
+        let mut keep = (0..columns).collect::<Vec<_>>();
+        let mut dropping = optional.iter();
+        let room = loop {
+            let middle = keep.len().saturating_sub(2);
+            let fixed = keep
+                .iter()
+                .skip(1)
+                .take(middle)
+                .map(|&i| natural[i])
+                .sum::<usize>()
+                + COLUMN_SPACING * keep.len().saturating_sub(1);
+            let room = width.saturating_sub(fixed);
+            let needed = match keep.len() {
+                0 => 0,
+                1 => MIN_FIRST_WIDTH,
+                n => {
+                    natural[keep[0]].min(MIN_FIRST_WIDTH) + natural[keep[n - 1]].min(MIN_LAST_WIDTH)
+                }
+            };
+            if room >= needed {
+                break room;
+            }
+            match dropping.next() {
+                Some(column) => keep.retain(|i| i != column),
+                None => break room,
+            }
+        };
+
+        let mut fitted = keep.iter().map(|&i| natural[i]).collect::<Vec<_>>();
+        match fitted.len() {
+            0 => {}
+            1 => fitted[0] = room,
+            n => {
+                let (first, last) = (fitted[0], fitted[n - 1]);
+                if first + last > room {
+                    let first = first.min(room.saturating_sub(last).max(room / 2));
+                    fitted[0] = first;
+                    fitted[n - 1] = room - first;
+                }
+            }
+        }
+
+        let rows = cells
+            .iter()
+            .map(|row| {
+                keep.iter()
+                    .zip(&fitted)
+                    .map(|(&i, &width)| wrap_cell(row.get(i).map_or("", |c| c), width))
+                    .collect()
+            })
+            .collect();
+        Grid {
+            widths: fitted
+                .into_iter()
+                .map(|w| Constraint::Length(w as u16))
+                .collect(),
+            rows,
+        }
+    }
+
+    pub fn widths(&self) -> &[Constraint] {
+        &self.widths
+    }
+
+    // the number of lines the rows take
+    pub fn height(&self) -> u16 {
+        self.rows.iter().map(|r| row_height(r)).sum::<usize>() as u16
+    }
+
+    pub fn rows(&self) -> Vec<Row<'static>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                Row::new(row.iter().map(|lines| Cell::from(lines.join("\n"))))
+                    .height(row_height(row) as u16)
+                    .fg(Color::White)
+            })
+            .collect()
+    }
+}
+
+fn row_height(row: &[Vec<String>]) -> usize {
+    row.iter().map(|lines| lines.len()).max().unwrap_or(1)
+}
+
+// a cell of a grid wrapped to its column, cells may hold several lines
+fn wrap_cell(text: &str, width: usize) -> Vec<String> {
+    if text.is_empty() {
+        return vec![String::new()];
+    }
+    text.lines().flat_map(|line| wrap(line, width)).collect()
+}
+
+// breaks every line of `text` into lines of at most `width` cells
+pub fn wrap_lines(text: &str, width: u16) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| wrap(line, width as usize))
+        .collect()
+}
+
+// breaks `text` into lines of at most `width` cells, at spaces where it can and inside the words
+// that are longer than a line (base64 keys and signatures)
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut used = 0;
+    for (n, word) in text.split(' ').enumerate() {
+        if n > 0 {
+            if used > 0 && used + 1 + word.width() > width {
+                lines.push(mem::take(&mut line));
+                used = 0;
+            } else {
+                line.push(' ');
+                used += 1;
+            }
+        }
+        for c in word.chars() {
+            let w = c.width().unwrap_or(0);
+            if used > 0 && used + w > width {
+                lines.push(mem::take(&mut line));
+                used = 0;
+            }
+            line.push(c);
+            used += w;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn wrap_at_spaces_and_inside_long_words() {
+        assert_eq!(
+            vec!["10", "mail.exa", "mple.com", "."],
+            wrap("10 mail.example.com.", 8)
+        );
+        assert_eq!(vec!["1 2", "3"], wrap("1 2 3", 4));
+        assert_eq!(vec!["a", "bcd", "ef"], wrap("a bcdef", 3));
+        assert_eq!(vec![""], wrap("", 5));
+        assert_eq!(vec!["müx", "n"], wrap("müxn", 3));
+        assert_eq!(vec!["a", "bcd", "e"], wrap_cell("a\nbcde", 3));
+    }
+
+    fn cells(rows: &[[&str; 5]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|r| r.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn fit_columns_to_their_content() {
+        let rows = cells(&[
+            [
+                "example.com",
+                "300",
+                "IN",
+                "TXT",
+                "v=spf1 include:_spf.example.com ~all",
+            ],
+            ["example.com", "300", "IN", "A", "192.0.2.1"],
+        ]);
+
+        let wide = Grid::new(rows.clone(), 100, &[2, 1]);
+        assert_eq!(
+            [11, 3, 2, 3, 36].map(Constraint::Length).to_vec(),
+            wide.widths()
+        );
+        assert_eq!(2, wide.height());
+
+        // the data wraps before the name gives up space
+        let narrow = Grid::new(rows.clone(), 50, &[2, 1]);
+        assert_eq!(
+            [11, 3, 2, 3, 27].map(Constraint::Length).to_vec(),
+            narrow.widths()
+        );
+        assert_eq!(4, narrow.height());
+
+        // class and ttl are left out before the name and data get too narrow to read
+        let tiny = Grid::new(rows, 30, &[2, 1]);
+        assert_eq!([11, 3, 14].map(Constraint::Length).to_vec(), tiny.widths());
+    }
+}
@@ -1,5 +1,7 @@
 use std::{
     io::{self, Stdout},
+    net::SocketAddr,
+    path::PathBuf,
     process,
     time::{Duration, Instant},
 };
@@ -7,22 +9,48 @@ use std::{
 use chrono::{DateTime, Local};
 
 use anyhow::{anyhow, Context, Result};
-use clap::{Parser, Subcommand};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand};
+use client::{exchange, parse_server, resolve_server, DEFAULT_SERVER};
+use cookies::{CookieJar, CookieStatus};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
-use dns::{message::Message, DeSerialize, Serialize};
-use tokio::net::UdpSocket;
+use dns::edns::{self, ClientSubnet, Edns, EdnsOption};
+use dns::tsig::{self, TsigKey, Verifier};
+use dns::{
+    header::{RequestFlags, ResponseCode},
+    message::Message,
+    name::Name,
+    record::{RData, Record},
+    QClass, QType, Serialize,
+};
+use layout::{wrap_lines, Grid};
+use update::Update;
 use validation::{check_length, check_token_length};
 
-use crate::dns::Buffer;
 use ratatui::{prelude::*, widgets::*};
+use tokio::net::UdpSocket;
+use unicode_width::UnicodeWidthStr;
+mod bench;
+mod client;
+mod cookies;
+mod decode;
+mod diff;
 mod dns;
+mod idn;
+mod interactive;
+mod json;
+mod layout;
+mod notify;
+mod pcap;
+mod propagate;
+mod serve;
+mod update;
 mod validation;
+mod watch;
+mod xfr;
 
 const TOP_BLOCK_SIZE: u16 = 1;
-const HEADER_BLOCK_SIZE: u16 = 5;
-const QUESTION_BLOCK_SIZE: u16 = 2;
-const MESSAGE_BLOCK_SIZE: u16 = 2;
-const STAT_BLOCK_SIZE: u16 = 6;
+const BORDER_SIZE: u16 = 2;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -31,24 +59,464 @@ struct Statistics {
     pub msg_sent: usize,
     pub msg_rcvd: usize,
     pub current_time: DateTime<Local>,
+    pub cookie: CookieStatus,
+}
+
+#[derive(Args)]
+pub struct QueryArgs {
+    #[arg(help = "the domain you are asking for")]
+    domain: String,
+
+    #[arg(help = "the server to ask, ex. @8.8.8.8 or @[::1]:5353", value_parser = parse_server)]
+    server: Option<String>,
+}
+
+#[derive(Args)]
+pub struct TransferArgs {
+    #[arg(help = "the zone to transfer")]
+    zone: String,
+
+    #[arg(help = "the primary to transfer from, ex. @ns1.example.com", value_parser = parse_server)]
+    server: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SERIAL",
+        help = "only transfer the changes since this serial (IXFR), also accepted as ixfr=<serial>"
+    )]
+    ixfr: Option<u32>,
+
+    #[arg(long, help = "print the transfer as json instead of a zone file")]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct UpdateArgs {
+    #[arg(help = "the zone to update, may also be set by the script")]
+    zone: Option<String>,
+
+    #[arg(help = "the primary to send the update to, ex. @ns1.example.com", value_parser = parse_server)]
+    server: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "NAME [TTL] TYPE DATA",
+        help = "add a record, ex. --add \"www.example.com 300 A 192.0.2.1\""
+    )]
+    add: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "NAME [TYPE [DATA]]",
+        help = "delete a name, a RRset or a single record, applied before the additions"
+    )]
+    delete: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "CONDITION",
+        help = "only update when this holds, ex. \"nxdomain www.example.com\" or \"yxrrset www.example.com A\""
+    )]
+    prereq: Vec<String>,
+
+    #[arg(
+        long,
+        default_value_t = 3600,
+        help = "the ttl of added records that have none"
+    )]
+    ttl: u32,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "read an nsupdate style script, - reads it from stdin"
+    )]
+    file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct NotifyArgs {
+    #[arg(help = "the zone that changed")]
+    zone: String,
+
+    #[arg(help = "the secondary to notify, ex. @ns2.example.com", value_parser = parse_server)]
+    server: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "SERVER",
+        help = "where to read the current SOA of the zone from, ex. ns1.example.com [default: 1.1.1.1]",
+        value_parser = parse_server
+    )]
+    primary: Option<String>,
+
+    #[arg(long, help = "send the notify without the SOA of the zone")]
+    no_soa: bool,
+}
+
+#[derive(Args)]
+pub struct ListenArgs {
+    #[arg(
+        short,
+        long,
+        default_value = "0.0.0.0:5300",
+        help = "the address and port to receive NOTIFY messages on"
+    )]
+    listen: SocketAddr,
+
+    #[arg(short = 'n', long, help = "stop after this many messages")]
+    count: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct PropagateArgs {
+    #[arg(help = "the name to check")]
+    domain: String,
+
+    #[arg(help = "the type to check", default_value = "A", value_parser = parse_type)]
+    qtype: QType,
+
+    #[arg(
+        short = 'f',
+        long,
+        value_name = "FILE",
+        help = "also ask the resolvers in this file, one `address [name]` per line"
+    )]
+    resolvers: Option<PathBuf>,
+
+    #[arg(
+        long,
+        requires = "resolvers",
+        help = "only ask the resolvers in the file, not the built-in public ones"
+    )]
+    no_public: bool,
+
+    #[arg(
+        long,
+        value_name = "VALUE",
+        help = "count the resolvers that answer with VALUE as converged instead of the ones that agree with the most"
+    )]
+    expect: Option<String>,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    #[arg(help = "the server to compare with, ex. @ns1.old-provider.net", value_parser = parse_server)]
+    server: String,
+
+    #[arg(help = "the server to compare, ex. @ns1.new-provider.net", value_parser = parse_server)]
+    other: String,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "the names to compare, one per line, - reads them from stdin"
+    )]
+    names: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "TYPES",
+        value_delimiter = ',',
+        default_value = "a,aaaa,mx,txt",
+        value_parser = parse_type,
+        help = "the types to compare for every name"
+    )]
+    types: Vec<QType>,
+
+    #[arg(long, help = "also report records whose TTL differs")]
+    ttl: bool,
+
+    #[arg(long, help = "print the differences as json")]
+    json: bool,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("source").required(true).args(["names", "random"])))]
+pub struct BenchArgs {
+    #[arg(
+        help = "the servers to ask, ex. @1.1.1.1 @9.9.9.9 [default: 1.1.1.1]",
+        value_parser = parse_server
+    )]
+    servers: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "ask for the names in this file, one per line"
+    )]
+    names: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "ZONE",
+        help = "ask for random names under ZONE, so no answer comes from a cache"
+    )]
+    random: Option<String>,
+
+    #[arg(
+        short = 't',
+        long = "type",
+        default_value = "A",
+        value_parser = parse_type,
+        help = "the type to ask for"
+    )]
+    qtype: QType,
+
+    #[arg(
+        short = 'n',
+        long,
+        default_value_t = 100,
+        help = "how many queries to send to every server"
+    )]
+    count: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "how many queries may wait for an answer at the same time"
+    )]
+    concurrency: u16,
+
+    #[arg(
+        long,
+        value_name = "QPS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "send at most this many queries per second to every server"
+    )]
+    rate: Option<u32>,
+
+    #[arg(long, help = "print the results as json instead of a live chart")]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct PcapArgs {
+    #[arg(help = "the capture to read, pcap or pcapng")]
+    file: PathBuf,
+
+    #[arg(long, help = "only show questions for NAME and the names under it")]
+    name: Option<String>,
+
+    #[arg(
+        short = 't',
+        long = "type",
+        value_parser = parse_type,
+        help = "only show questions for this type"
+    )]
+    qtype: Option<QType>,
+
+    #[arg(
+        long,
+        value_parser = parse_rcode,
+        help = "only show responses with this code, ex. NXDOMAIN, SERVFAIL or 2"
+    )]
+    rcode: Option<ResponseCode>,
+}
+
+#[derive(Args)]
+pub struct DecodeArgs {
+    #[arg(
+        help = "the message as hex, base64 or a file with either or the binary message, - reads stdin"
+    )]
+    input: String,
+}
+
+#[derive(Args)]
+pub struct EncodeArgs {
+    #[arg(help = "the domain to ask for")]
+    domain: String,
+
+    #[arg(help = "the type to ask for", default_value = "A", value_parser = parse_type)]
+    qtype: QType,
+
+    #[arg(long, help = "the message id instead of a random one")]
+    id: Option<u16>,
+
+    #[arg(long, help = "print the message as one line of hex")]
+    hex: bool,
+
+    #[arg(long, conflicts_with = "hex", help = "print the message as base64")]
+    base64: bool,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        required = true,
+        help = "a master file to answer from, give --zone again for more zones"
+    )]
+    zone: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        help = "the origin of names in the files that do not set one with $ORIGIN"
+    )]
+    origin: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "127.0.0.1:5353",
+        help = "the address and port to answer on, over udp and tcp"
+    )]
+    listen: SocketAddr,
+}
+
+#[derive(Args)]
+pub struct ZoneArgs {
+    #[arg(help = "the master file to read")]
+    file: PathBuf,
+
+    #[arg(
+        long,
+        help = "the origin of names in the file when it does not set one with $ORIGIN"
+    )]
+    origin: Option<String>,
+
+    #[arg(short, long, help = "only check the file, do not print it")]
+    quiet: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     #[command(long_about = "fetch text records")]
-    Txt { domain: String },
+    Txt(QueryArgs),
     #[command(long_about = "fetch cname records")]
-    Cname { domain: String },
+    Cname(QueryArgs),
     #[command(long_about = "fetch A (ipv4) records")]
-    A { domain: String },
+    A(QueryArgs),
     #[command(long_about = "fetch AAAA (ipv6) records")]
-    AAAA { domain: String },
+    AAAA(QueryArgs),
     #[command(long_about = "fetch NS (name server) records")]
-    NS { domain: String },
+    NS(QueryArgs),
     #[command(long_about = "fetch MX records")]
-    MX { domain: String },
+    MX(QueryArgs),
     #[command(long_about = "fetch SOA records")]
-    SOA { domain: String },
+    SOA(QueryArgs),
+    #[command(long_about = "fetch SRV (service locator) records")]
+    SRV(QueryArgs),
+    #[command(long_about = "fetch CAA (certification authority authorization) records")]
+    CAA(QueryArgs),
+    #[command(long_about = "fetch HTTPS (service binding) records")]
+    HTTPS(QueryArgs),
+    #[command(long_about = "fetch SVCB (service binding) records")]
+    SVCB(QueryArgs),
+    #[command(long_about = "fetch TLSA (DANE certificate association) records")]
+    TLSA(QueryArgs),
+    #[command(long_about = "fetch SSHFP (ssh fingerprint) records")]
+    SSHFP(QueryArgs),
+    #[command(long_about = "fetch NAPTR (naming authority pointer) records")]
+    NAPTR(QueryArgs),
+    #[command(long_about = "fetch SPF records (obsolete, published as TXT today)")]
+    SPF(QueryArgs),
+    #[command(long_about = "fetch HINFO (host information) records")]
+    HINFO(QueryArgs),
+    #[command(long_about = "fetch MINFO (mailbox information) records")]
+    MINFO(QueryArgs),
+    #[command(long_about = "fetch MB (mailbox domain name) records")]
+    MB(QueryArgs),
+    #[command(long_about = "fetch MG (mail group member) records")]
+    MG(QueryArgs),
+    #[command(long_about = "fetch MR (mail rename domain name) records")]
+    MR(QueryArgs),
+    #[command(long_about = "fetch NULL records")]
+    NULL(QueryArgs),
+    #[command(long_about = "fetch WKS (well known service) records")]
+    WKS(QueryArgs),
+    #[command(long_about = "fetch RP (responsible person) records")]
+    RP(QueryArgs),
+    #[command(long_about = "fetch AFSDB (AFS database location) records")]
+    AFSDB(QueryArgs),
+    #[command(long_about = "fetch LOC (location) records")]
+    LOC(QueryArgs),
+    #[command(long_about = "transfer a whole zone over tcp (AXFR), or its changes (IXFR)")]
+    Axfr(TransferArgs),
+    #[command(long_about = "send a dynamic update (rfc 2136) to the primary of a zone")]
+    Update(UpdateArgs),
+    #[command(long_about = "tell a secondary that a zone changed (NOTIFY, rfc 1996)")]
+    Notify(NotifyArgs),
+    #[command(long_about = "receive, acknowledge and print NOTIFY messages")]
+    ListenNotify(ListenArgs),
+    #[command(
+        long_about = "ask many public and your own resolvers and show which see which answer"
+    )]
+    Propagate(PropagateArgs),
+    #[command(
+        long_about = "compare the answers of two servers for a list of names, exits with status 1 when they differ"
+    )]
+    Diff(DiffArgs),
+    #[command(long_about = "measure the latency, loss and response codes of one or more servers")]
+    Bench(BenchArgs),
+    #[command(
+        long_about = "read the DNS queries and responses from a pcap or pcapng capture, udp and tcp on port 53"
+    )]
+    Pcap(PcapArgs),
+    #[command(
+        long_about = "show every field of a message given as hex or base64, with its offset and octets"
+    )]
+    Decode(DecodeArgs),
+    #[command(
+        long_about = "print the octets of the query the other options describe, without sending it"
+    )]
+    Encode(EncodeArgs),
+    #[command(
+        long_about = "answer queries from master files over udp and tcp, as the authoritative server of the zones"
+    )]
+    Serve(ServeArgs),
+    #[command(
+        long_about = "read a master file, report the first mistake in it or print it in canonical form"
+    )]
+    Zone(ZoneArgs),
+}
+
+impl Commands {
+    fn query(&self) -> Option<(&QueryArgs, QType)> {
+        match self {
+            Commands::Txt(args) => Some((args, QType::TXT)),
+            Commands::Cname(args) => Some((args, QType::CNAME)),
+            Commands::A(args) => Some((args, QType::A)),
+            Commands::AAAA(args) => Some((args, QType::AAAA)),
+            Commands::NS(args) => Some((args, QType::NS)),
+            Commands::MX(args) => Some((args, QType::MX)),
+            Commands::SOA(args) => Some((args, QType::SOA)),
+            Commands::SRV(args) => Some((args, QType::SRV)),
+            Commands::CAA(args) => Some((args, QType::CAA)),
+            Commands::HTTPS(args) => Some((args, QType::HTTPS)),
+            Commands::SVCB(args) => Some((args, QType::SVCB)),
+            Commands::TLSA(args) => Some((args, QType::TLSA)),
+            Commands::SSHFP(args) => Some((args, QType::SSHFP)),
+            Commands::NAPTR(args) => Some((args, QType::NAPTR)),
+            Commands::SPF(args) => Some((args, QType::SPF)),
+            Commands::HINFO(args) => Some((args, QType::HINFO)),
+            Commands::MINFO(args) => Some((args, QType::MINFO)),
+            Commands::MB(args) => Some((args, QType::MB)),
+            Commands::MG(args) => Some((args, QType::MG)),
+            Commands::MR(args) => Some((args, QType::MR)),
+            Commands::NULL(args) => Some((args, QType::NULL)),
+            Commands::WKS(args) => Some((args, QType::WKS)),
+            Commands::RP(args) => Some((args, QType::RP)),
+            Commands::AFSDB(args) => Some((args, QType::AFSDB)),
+            Commands::LOC(args) => Some((args, QType::LOC)),
+            Commands::Axfr(_)
+            | Commands::Update(_)
+            | Commands::Notify(_)
+            | Commands::ListenNotify(_)
+            | Commands::Propagate(_)
+            | Commands::Diff(_)
+            | Commands::Bench(_)
+            | Commands::Pcap(_)
+            | Commands::Decode(_)
+            | Commands::Encode(_)
+            | Commands::Serve(_)
+            | Commands::Zone(_) => None,
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -62,78 +530,773 @@ struct Cli {
     #[arg(help = "the domain you are asking for")]
     domain: Option<String>,
 
+    #[arg(help = "the server to ask, ex. @8.8.8.8 or @[::1]:5353", value_parser = parse_server)]
+    server: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 
     #[arg(short, long = "raw-records")]
     raw: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "open a full screen app to look up one name after the other"
+    )]
+    interactive: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "5",
+        value_parser = parse_interval,
+        help = "ask again every SECONDS [default: 5] and show what changes, also +watch[=SECONDS]"
+    )]
+    watch: Option<Duration>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "VALUE",
+        requires = "watch",
+        help = "stop watching with exit status 2 once a record holds VALUE, ex. 192.0.2.1"
+    )]
+    until: Option<String>,
+
+    #[arg(
+        short,
+        long = "concat-txt",
+        help = "show TXT and SPF strings concatenated, the way SPF and DKIM evaluation reads them"
+    )]
+    concat: bool,
+
+    #[arg(
+        long,
+        help = "the class to query, ex. IN, CH, HS, ANY or CLASS<n>",
+        default_value = "IN",
+        value_parser = parse_class
+    )]
+    class: QClass,
+
+    #[arg(
+        short = 'y',
+        long = "tsig",
+        global = true,
+        value_name = "[ALGORITHM:]NAME:SECRET",
+        help = "sign the request with this TSIG key, algorithm is one of hmac-sha256 (default), hmac-sha384 or hmac-sha512",
+        value_parser = parse_tsig_key
+    )]
+    tsig: Option<TsigKey>,
+
+    #[arg(
+        short = 'k',
+        long = "tsig-keyfile",
+        global = true,
+        value_name = "FILE",
+        conflicts_with = "tsig",
+        help = "sign the request with the TSIG key in this bind style key file"
+    )]
+    tsig_keyfile: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "do not ask for recursion (RD), also +norecurse"
+    )]
+    norecurse: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "ask for DNSSEC validated data (AD), also +adflag"
+    )]
+    adflag: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "do not let the resolver validate DNSSEC (CD), also +cdflag"
+    )]
+    cdflag: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "set the authoritative answer bit (AA) on the query, also +aaonly"
+    )]
+    aaonly: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "do not send or remember DNS cookies, also +nocookie"
+    )]
+    nocookie: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "ADDRESS[/PREFIX]",
+        help = "send an EDNS client subnet, ex. 203.0.113.0/24 or 2001:db8::/56",
+        value_parser = parse_subnet
+    )]
+    subnet: Option<ClientSubnet>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "ask the server for its name server identifier, also +nsid"
+    )]
+    nsid: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "BLOCK",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "128",
+        help = "pad the query to a multiple of BLOCK octets [default: 128], also +padding[=BLOCK]"
+    )]
+    padding: Option<u16>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "send names as typed instead of converting them to punycode (IDNA), also +noidn"
+    )]
+    noidn: bool,
+}
+
+impl Cli {
+    // names are converted to and shown with their internationalized form unless turned off
+    fn idn(&self) -> bool {
+        !self.noidn
+    }
+
+    fn request_flags(&self) -> RequestFlags {
+        RequestFlags {
+            rd: !self.norecurse,
+            aa: self.aaonly,
+            ad: self.adflag,
+            cd: self.cdflag,
+        }
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        if self.nocookie {
+            CookieJar::default()
+        } else {
+            CookieJar::load()
+        }
+    }
+
+    fn tsig_key(&self) -> Result<Option<TsigKey>> {
+        match (&self.tsig, &self.tsig_keyfile) {
+            (Some(key), _) => Ok(Some(key.clone())),
+            (None, Some(path)) => Ok(Some(TsigKey::from_file(path)?)),
+            (None, None) => Ok(None),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
-
-    let m = match &cli.command {
-        Some(Commands::Txt { domain }) => Message::txt(valid(domain)),
-        Some(Commands::Cname { domain }) => Message::cname(valid(domain)),
-        Some(Commands::A { domain }) => Message::a(valid(domain)),
-        Some(Commands::AAAA { domain }) => Message::aaaa(valid(domain)),
-        Some(Commands::NS { domain }) => Message::ns(valid(domain)),
-        Some(Commands::MX { domain }) => Message::mx(valid(domain)),
-        Some(Commands::SOA { domain }) => Message::soa(valid(domain)),
+    let cli = Cli::parse_from(normalize_args(std::env::args()));
+
+    let tsig_key = cli.tsig_key()?;
+
+    if let Some(Commands::Axfr(args)) = &cli.command {
+        return zone_transfer(args, tsig_key.as_ref(), cli.idn()).await;
+    }
+    if let Some(Commands::Update(args)) = &cli.command {
+        return dynamic_update(args, cli.class.clone(), tsig_key.as_ref(), cli.idn()).await;
+    }
+    if let Some(Commands::Notify(args)) = &cli.command {
+        return send_notify(args, tsig_key.as_ref(), cli.idn()).await;
+    }
+    if let Some(Commands::ListenNotify(args)) = &cli.command {
+        return listen_notify(args).await;
+    }
+    if let Some(Commands::Propagate(args)) = &cli.command {
+        return check_propagation(args, &cli).await;
+    }
+    if let Some(Commands::Bench(args)) = &cli.command {
+        return benchmark(args, &cli).await;
+    }
+    if let Some(Commands::Pcap(args)) = &cli.command {
+        let filter = pcap::Filter {
+            name: args
+                .name
+                .as_deref()
+                .map(|name| query_name(name, cli.idn()))
+                .transpose()?,
+            qtype: args.qtype.clone(),
+            rcode: args.rcode.clone(),
+        };
+        return pcap::run(&args.file, &filter, cli.idn());
+    }
+    if let Some(Commands::Decode(args)) = &cli.command {
+        return decode::run(&decode::input(&args.input)?);
+    }
+    if let Some(Commands::Encode(args)) = &cli.command {
+        return encode_query(args, &cli, tsig_key.as_ref());
+    }
+    if let Some(Commands::Serve(args)) = &cli.command {
+        return serve_zones(args, cli.idn()).await;
+    }
+    if let Some(Commands::Zone(args)) = &cli.command {
+        return check_zone(args, cli.idn());
+    }
+    if let Some(Commands::Diff(args)) = &cli.command {
+        if !compare_servers(args, &cli).await? {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if cli.interactive && cli.watch.is_some() {
+        return Err(anyhow!("--watch can not be used in the interactive app"));
+    }
+    if cli.interactive {
+        let (domain, server, qtype) = match cli.command.as_ref().and_then(|c| c.query()) {
+            Some((args, qtype)) => (Some(&args.domain), args.server.as_ref(), qtype),
+            None => (cli.domain.as_ref(), cli.server.as_ref(), QType::A),
+        };
+        let query = interactive::Query {
+            name: domain.cloned().unwrap_or_default(),
+            qtype,
+            server: server.map_or(DEFAULT_SERVER.to_owned(), |s| s.to_owned()),
+        };
+        return interactive::run(&cli, query, domain.is_some(), tsig_key.as_ref()).await;
+    }
+
+    let (m, server) = match cli.command.as_ref().and_then(|c| c.query()) {
+        Some((args, qtype)) => (
+            Message::query(valid(&args.domain, cli.idn()), qtype, cli.class.clone())
+                .with_flags(&cli.request_flags()),
+            args.server.as_ref(),
+        ),
         None => {
             if let Some(address) = &cli.domain {
-                Message::a(valid(address))
+                (
+                    Message::query(valid(address, cli.idn()), QType::A, cli.class.clone())
+                        .with_flags(&cli.request_flags()),
+                    cli.server.as_ref(),
+                )
             } else {
                 eprintln!("You must supply a valid address as a first argument");
                 process::exit(1);
             }
         }
     };
+    let server = resolve_server(server.map_or(DEFAULT_SERVER, |s| s.as_str())).await?;
 
-    let sock = UdpSocket::bind("0.0.0.0:8080")
-        .await
-        .context("could not bind")?;
+    if let Some(interval) = cli.watch {
+        let until = cli.until.as_deref();
+        if watch::run(&cli, &m, server, interval, until, tsig_key.as_ref()).await? {
+            process::exit(watch::TARGET_SEEN);
+        }
+        return Ok(());
+    }
+
+    let mut cookies = cli.cookie_jar();
+    let (message, stats) = lookup(&cli, &m, server, tsig_key.as_ref(), &mut cookies).await?;
+    cookies.save();
+
+    if !cli.raw {
+        let (width, _) = crossterm::terminal::size().context("unable to get the terminal size")?;
+        let screen = Screen::new(&message, &stats, cli.concat, cli.idn(), width);
+        let mut terminal = setup_terminal(screen.height()).context("setup failed")?;
+        terminal.draw(|f| render_app(f, &screen, &message, cli.idn()))?;
+        disable_raw_mode().context("failed to disable raw mode")?;
+        let _ = terminal.show_cursor().context("unable to show cursor");
+    } else {
+        for line in edns_lines(&message) {
+            println!("; {}", line);
+        }
+        if let Some(subnet) = message.edns().as_ref().and_then(|e| e.client_subnet()) {
+            println!("; subnet {}", subnet);
+        }
+        let unicode = message
+            .question()
+            .and_then(|q| idn::to_unicode(&q.qname.to_string()));
+        if let (true, Some(unicode)) = (cli.idn(), unicode) {
+            println!("; {}", unicode);
+        }
+        for r in message.records {
+            if cli.concat {
+                println!("{:#}", r);
+            } else {
+                println!("{}", r);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Asks the server with the EDNS options of the command line and returns the response. A server
+// that rejects our server cookie hands out a fresh one, it is asked once more with that one.
+async fn lookup(
+    cli: &Cli,
+    query: &Message,
+    server: SocketAddr,
+    tsig_key: Option<&TsigKey>,
+    cookies: &mut CookieJar,
+) -> Result<(Message, Statistics)> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let cookie = (!cli.nocookie).then(|| cookies.option(server));
+        let edns = request_edns(cli, query, cookie, tsig_key)?;
+        let mut request = query.clone();
+        request.additionals.push(edns.record());
+        let (request, buffer, elapsed) = send_query(request, server, tsig_key).await?;
+        let message =
+            Message::try_from(buffer.as_slice()).context("Failed to deserialize response")?;
+
+        let cookie = cookies.receive(server, &message);
+        if cookie == CookieStatus::Bad && attempt == 1 {
+            continue;
+        }
+        let stats = Statistics {
+            query_time: elapsed,
+            msg_sent: request.len(),
+            msg_rcvd: buffer.len(),
+            current_time: Local::now(),
+            cookie,
+        };
+        return Ok((message, stats));
+    }
+}
+
+// the EDNS options of the command line, with the cookie first when there is one
+fn request_edns(
+    cli: &Cli,
+    query: &Message,
+    cookie: Option<EdnsOption>,
+    tsig_key: Option<&TsigKey>,
+) -> Result<Edns> {
+    let mut edns = Edns::new(edns::DEFAULT_PAYLOAD);
+    edns.options.extend(cookie);
+    if let Some(subnet) = &cli.subnet {
+        edns.options.push(EdnsOption::ClientSubnet(subnet.clone()));
+    }
+    if cli.nsid {
+        edns.options.push(EdnsOption::Nsid(Vec::new()));
+    }
+    if let Some(block) = cli.padding {
+        edns.options.push(EdnsOption::Padding(0));
+        edns.pad(signed_length(query, &edns, tsig_key)?, block);
+    }
+    Ok(edns)
+}
 
-    let m = m.serialize().context("Failed to serialize request")?;
+// the length the request will have on the wire with this OPT record, signed when a key is given
+fn signed_length(request: &Message, edns: &Edns, tsig_key: Option<&TsigKey>) -> Result<usize> {
+    let mut request = request.clone();
+    request.additionals.push(edns.record());
+    if let Some(key) = tsig_key {
+        tsig::sign(&mut request, key)?;
+    }
+    Ok(request.serialize()?.len())
+}
+
+// signs the request when a key is given, sends it and verifies the signature of the response.
+// Returns the raw request and response and how long the server took to answer.
+async fn send_query(
+    mut request: Message,
+    server: SocketAddr,
+    tsig_key: Option<&TsigKey>,
+) -> Result<(Vec<u8>, Vec<u8>, Duration)> {
+    let request_mac = match tsig_key {
+        Some(key) => Some(tsig::sign(&mut request, key)?),
+        None => None,
+    };
+    let request = request.serialize().context("Failed to serialize request")?;
 
-    let mut buffer = [0; 1024];
     let start = Instant::now();
-    let _len = sock.send_to(&m, "1.1.1.1:53").await?;
-    let (msg_length, _) = sock.recv_from(&mut buffer).await?;
+    let response = exchange(&request, server).await?;
     let elapsed = start.elapsed();
 
-    let mut buffer = Buffer {
-        current: &buffer,
-        source: &buffer,
+    if let (Some(key), Some(request_mac)) = (tsig_key, request_mac) {
+        Verifier::new(key, request_mac).verify(&response)?;
+    }
+    Ok((request, response, elapsed))
+}
+
+// the options of any command that take the next argument as their value, like `--subnet` or `-y`
+fn value_options(command: &clap::Command, options: &mut Vec<String>) {
+    for arg in command.get_arguments() {
+        if arg.is_positional() || !arg.get_action().takes_values() || arg.is_require_equals_set() {
+            continue;
+        }
+        options.extend(arg.get_long().map(|long| format!("--{}", long)));
+        options.extend(arg.get_short().map(|short| format!("-{}", short)));
+    }
+    for subcommand in command.get_subcommands() {
+        value_options(subcommand, options);
+    }
+}
+
+// Rewrites dig style arguments, like `ixfr=<serial>` or `+adflag`, into their long option form.
+// The values of options and everything after `--` are left as they are.
+fn normalize_args(mut args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut options = Vec::new();
+    value_options(&Cli::command(), &mut options);
+    let mut normalized = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            normalized.push(arg);
+            normalized.extend(args.by_ref());
+            break;
+        }
+        let arg = if let Some(serial) = arg.strip_prefix("ixfr=") {
+            format!("--ixfr={}", serial)
+        } else if let Some(flag) = arg.strip_prefix('+') {
+            format!("--{}", flag)
+        } else {
+            arg
+        };
+        let value = options.contains(&arg);
+        normalized.push(arg);
+        if value {
+            normalized.extend(args.next());
+        }
+    }
+    normalized
+}
+
+async fn zone_transfer(args: &TransferArgs, tsig_key: Option<&TsigKey>, idn: bool) -> Result<()> {
+    let zone = &valid(&args.zone, idn);
+    let server = resolve_server(args.server.as_deref().unwrap_or(DEFAULT_SERVER)).await?;
+    let (transfer, stats) = xfr::transfer(zone, args.ixfr, server, tsig_key).await?;
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&xfr::to_json(zone, &transfer))?
+        );
+    } else {
+        xfr::print(&transfer, &stats);
+    }
+    Ok(())
+}
+
+async fn dynamic_update(
+    args: &UpdateArgs,
+    class: QClass,
+    tsig_key: Option<&TsigKey>,
+    idn: bool,
+) -> Result<()> {
+    let zone = args.zone.as_ref().map(|zone| valid(zone, idn));
+    let mut batches = match &args.file {
+        Some(path) => {
+            let script = if path.as_os_str() == "-" {
+                io::read_to_string(io::stdin())?
+            } else {
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("could not read {}", path.display()))?
+            };
+            update::parse_script(&script, zone.clone())?
+        }
+        None => Vec::new(),
     };
 
-    let (_buffer, message) =
-        Message::deserialize(&mut buffer).context("Failed to deserialize response")?;
+    if !args.add.is_empty() || !args.delete.is_empty() || !args.prereq.is_empty() {
+        let zone = zone.ok_or_else(|| anyhow!("no zone given to update"))?;
+        let mut update = Update::new(zone, class);
+        for prereq in &args.prereq {
+            update.prerequisite(prereq)?;
+        }
+        for delete in &args.delete {
+            update.delete(delete)?;
+        }
+        for add in &args.add {
+            update.add(add, Some(args.ttl))?;
+        }
+        batches.push(update::Batch {
+            server: None,
+            key: None,
+            update,
+        });
+    }
+    if batches.is_empty() {
+        return Err(anyhow!("nothing to update, use --add, --delete or --file"));
+    }
+
+    for batch in batches {
+        let server = batch
+            .server
+            .as_deref()
+            .or(args.server.as_deref())
+            .unwrap_or(DEFAULT_SERVER);
+        let server = resolve_server(server).await?;
+        let key = batch.key.as_ref().or(tsig_key);
+        let r_code = update::send(&batch.update, server, key).await?;
+        if r_code != ResponseCode::NoError {
+            return Err(anyhow!(
+                "update of {} at {} failed: {}",
+                batch.update.zone,
+                server,
+                r_code
+            ));
+        }
+        println!("update of {} at {}: {}", batch.update.zone, server, r_code);
+    }
+    Ok(())
+}
 
-    let stats = Statistics {
-        query_time: elapsed,
-        msg_sent: m.len(),
-        msg_rcvd: msg_length,
-        current_time: Local::now(),
+async fn send_notify(args: &NotifyArgs, tsig_key: Option<&TsigKey>, idn: bool) -> Result<()> {
+    let zone = &valid(&args.zone, idn);
+    let soa = if args.no_soa {
+        None
+    } else {
+        let primary = resolve_server(args.primary.as_deref().unwrap_or(DEFAULT_SERVER)).await?;
+        Some(notify::current_soa(zone, primary).await?)
     };
-    if !cli.raw {
-        let mut terminal = setup_terminal(message.header.qd_count, message.header.an_count)
-            .context("setup failed")?;
-        terminal.draw(|f| render_app(f, &message, &stats))?;
-        disable_raw_mode().context("failed to disable raw mode")?;
-        let _ = terminal.show_cursor().context("unable to show cursor");
+    let serial = match soa.as_ref().map(|r| &r.rdata) {
+        Some(RData::SOA { serial, .. }) => serial.to_string(),
+        _ => "none".to_owned(),
+    };
+    let server = resolve_server(args.server.as_deref().unwrap_or(DEFAULT_SERVER)).await?;
+    let acknowledgement = notify::send(zone, soa, server, tsig_key).await?;
+    if acknowledgement.r_code != ResponseCode::NoError {
+        return Err(anyhow!(
+            "notify of {} at {} failed: {}",
+            zone,
+            server,
+            acknowledgement.r_code
+        ));
+    }
+    println!(
+        "notify of {} serial {} acknowledged by {} in {:?}",
+        zone, serial, server, acknowledgement.elapsed
+    );
+    Ok(())
+}
+
+async fn listen_notify(args: &ListenArgs) -> Result<()> {
+    let socket = UdpSocket::bind(args.listen)
+        .await
+        .with_context(|| format!("could not listen on {}", args.listen))?;
+    eprintln!("; listening for NOTIFY on {}", socket.local_addr()?);
+    let mut received = 0;
+    while args.count.is_none_or(|count| received < count) {
+        let notification = notify::receive(&socket).await?;
+        notify::print(&notification);
+        received += 1;
+    }
+    Ok(())
+}
+
+async fn check_propagation(args: &PropagateArgs, cli: &Cli) -> Result<()> {
+    let mut resolvers = if args.no_public {
+        Vec::new()
     } else {
-        for r in message.records {
-            println!("{}", r);
+        propagate::Resolver::public()
+    };
+    if let Some(path) = &args.resolvers {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        resolvers.extend(
+            propagate::parse_resolvers(&text)
+                .with_context(|| format!("invalid resolver in {}", path.display()))?,
+        );
+    }
+
+    let mut query = Message::query(
+        valid(&args.domain, cli.idn()),
+        args.qtype.clone(),
+        cli.class.clone(),
+    )
+    .with_flags(&cli.request_flags());
+    query
+        .additionals
+        .push(Edns::new(edns::DEFAULT_PAYLOAD).record());
+    propagate::run(&query, resolvers, args.expect.clone(), cli.raw, cli.idn()).await
+}
+
+async fn compare_servers(args: &DiffArgs, cli: &Cli) -> Result<bool> {
+    let text = if args.names.as_os_str() == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        std::fs::read_to_string(&args.names)
+            .with_context(|| format!("could not read {}", args.names.display()))?
+    };
+    let mut queries = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let name = line.split('#').next().unwrap_or_default().trim();
+        if name.is_empty() {
+            continue;
         }
+        let name = query_name(name, cli.idn())
+            .with_context(|| format!("{} line {}", args.names.display(), n + 1))?;
+        for qtype in &args.types {
+            queries.push(
+                Message::query(name.clone(), qtype.clone(), cli.class.clone())
+                    .with_flags(&cli.request_flags()),
+            );
+        }
+    }
+
+    let servers = [
+        resolve_server(&args.server).await?,
+        resolve_server(&args.other).await?,
+    ];
+    diff::run(queries, servers, args.ttl, args.json).await
+}
+
+// reads every zone before answering for any of them, so a mistake in one stops the server
+async fn serve_zones(args: &ServeArgs, idn: bool) -> Result<()> {
+    let origin = args
+        .origin
+        .as_deref()
+        .map(|origin| query_name(origin, idn))
+        .transpose()?;
+    let mut zones = Vec::new();
+    for path in &args.zone {
+        zones.push(dns::zone::read(path, origin.clone())?);
+    }
+    serve::run(zones, args.listen).await
+}
+
+// reads a master file and prints it the way it was understood, all names fully qualified
+fn check_zone(args: &ZoneArgs, idn: bool) -> Result<()> {
+    let origin = args
+        .origin
+        .as_deref()
+        .map(|origin| query_name(origin, idn))
+        .transpose()?;
+    let zone = dns::zone::read(&args.file, origin)?;
+    if !args.quiet {
+        print!("{}", zone);
     }
+    eprintln!("; {} records in {}", zone.records.len(), zone.origin);
+    Ok(())
+}
 
+// Prints the query as it would be sent, with the EDNS options and signed when a key is given.
+// There is no server to send it to, so it carries no cookie.
+fn encode_query(args: &EncodeArgs, cli: &Cli, tsig_key: Option<&TsigKey>) -> Result<()> {
+    let mut query = Message::query(
+        query_name(&args.domain, cli.idn())?,
+        args.qtype.clone(),
+        cli.class.clone(),
+    )
+    .with_flags(&cli.request_flags());
+    if let Some(id) = args.id {
+        query.header.id = id;
+    }
+    let edns = request_edns(cli, &query, None, tsig_key)?;
+    query.additionals.push(edns.record());
+    if let Some(key) = tsig_key {
+        tsig::sign(&mut query, key)?;
+    }
+    let octets = query.serialize()?;
+    if args.hex {
+        println!(
+            "{}",
+            octets
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+    } else if args.base64 {
+        println!("{}", STANDARD.encode(&octets));
+    } else {
+        print!("{}", decode::hexdump(&octets));
+    }
     Ok(())
 }
 
-fn valid(address: &String) -> &str {
-    match validate(address) {
+async fn benchmark(args: &BenchArgs, cli: &Cli) -> Result<()> {
+    let names = match (&args.names, &args.random) {
+        (Some(path), _) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            let mut names = Vec::new();
+            for (n, line) in text.lines().enumerate() {
+                let name = line.split('#').next().unwrap_or_default().trim();
+                if !name.is_empty() {
+                    names.push(
+                        query_name(name, cli.idn())
+                            .with_context(|| format!("{} line {}", path.display(), n + 1))?,
+                    );
+                }
+            }
+            if names.is_empty() {
+                return Err(anyhow!("no names in {}", path.display()));
+            }
+            bench::Names::List(names)
+        }
+        (None, Some(zone)) => bench::Names::Random(query_name(zone, cli.idn())?),
+        (None, None) => unreachable!("clap requires --names or --random"),
+    };
+
+    let mut servers = Vec::new();
+    for server in &args.servers {
+        servers.push(resolve_server(server).await?);
+    }
+    if servers.is_empty() {
+        servers.push(resolve_server(DEFAULT_SERVER).await?);
+    }
+
+    let plan = bench::Plan {
+        names,
+        qtype: args.qtype.clone(),
+        qclass: cli.class.clone(),
+        flags: cli.request_flags(),
+        count: args.count,
+        concurrency: args.concurrency as usize,
+        rate: args.rate,
+    };
+    bench::run(plan, servers, args.json).await
+}
+
+fn parse_tsig_key(value: &str) -> Result<TsigKey, String> {
+    value.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+fn parse_subnet(value: &str) -> Result<ClientSubnet, String> {
+    value.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+        _ => Err(format!("{} is not a number of seconds", value)),
+    }
+}
+
+fn parse_type(value: &str) -> Result<QType, String> {
+    value.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+fn parse_rcode(value: &str) -> Result<ResponseCode, String> {
+    value.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+fn parse_class(value: &str) -> Result<QClass, String> {
+    value.parse().map_err(|e: anyhow::Error| e.to_string())
+}
+
+// the name to ask for as typed by the user, converted to A-labels unless idn is turned off
+fn query_name(address: &str, idn: bool) -> Result<Name> {
+    let address = if idn {
+        idn::to_ascii(address)?
+    } else {
+        address.to_owned()
+    };
+    validate(&address)?.parse()
+}
+
+fn valid(address: &str, idn: bool) -> Name {
+    match query_name(address, idn) {
         Ok(address) => address,
         Err(msg) => {
             eprintln!("{}", msg);
@@ -142,7 +1305,7 @@ fn valid(address: &String) -> &str {
     }
 }
 
-fn validate(address: &String) -> Result<&str> {
+fn validate(address: &str) -> Result<&str> {
     let length_result = check_length(address);
     if !length_result {
         return Err(anyhow!(format!(
@@ -161,15 +1324,28 @@ fn validate(address: &String) -> Result<&str> {
     Ok(value)
 }
 
-fn setup_terminal(qd_count: u16, an_count: u16) -> Result<Terminal<CrosstermBackend<Stdout>>> {
-    let viewport_size = TOP_BLOCK_SIZE
-        + HEADER_BLOCK_SIZE
-        + QUESTION_BLOCK_SIZE
-        + qd_count
-        + MESSAGE_BLOCK_SIZE
-        + an_count
-        + STAT_BLOCK_SIZE;
+// the header followed by a line for every extended dns error the server gave
+fn header_text(message: &Message) -> String {
+    let mut text = message.header.to_string();
+    for line in edns_lines(message) {
+        text.push_str(&format!("{}\n", line));
+    }
+    text
+}
+
+// the EDNS options worth showing with the header, who answered and why it failed
+fn edns_lines(message: &Message) -> Vec<String> {
+    let Some(edns) = message.edns() else {
+        return Vec::new();
+    };
+    edns.nsid()
+        .into_iter()
+        .chain(edns.extended_errors())
+        .map(|o| o.to_string())
+        .collect()
+}
 
+fn setup_terminal(viewport_size: u16) -> Result<Terminal<CrosstermBackend<Stdout>>> {
     let stdout = io::stdout();
     enable_raw_mode().context("failed to enable raw mode")?;
     let terminal = Terminal::with_options(
@@ -181,37 +1357,197 @@ fn setup_terminal(qd_count: u16, an_count: u16) -> Result<Terminal<CrosstermBack
     Ok(terminal)
 }
 
-fn render_app(frame: &mut Frame, message: &Message, stats: &Statistics) {
-    let outer = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(vec![Constraint::Percentage(40), Constraint::Percentage(60)])
-        .split(frame.size());
+// the records depend on the client subnet when the server tells us how far the answer reaches
+fn records_title(message: &Message) -> String {
+    match message.edns().as_ref().and_then(|e| e.client_subnet()) {
+        Some(subnet) => format!("Records (subnet {})", subnet),
+        None => "Records".to_owned(),
+    }
+}
+
+// a name the way the tui shows it, followed by its U-labels unless idn is turned off
+fn display_name(name: &Name, idn: bool) -> String {
+    if idn {
+        idn::display(&name.to_string())
+    } else {
+        name.to_string()
+    }
+}
+
+// the cells of the question section, the class and type next to every name
+fn question_cells(message: &Message, idn: bool) -> Vec<Vec<String>> {
+    message
+        .questions
+        .iter()
+        .map(|q| {
+            vec![
+                display_name(&q.qname, idn),
+                q.qclass.to_string(),
+                q.qtype.to_string(),
+            ]
+        })
+        .collect()
+}
+
+fn record_cells(r: &Record, concat_txt: bool, idn: bool) -> Vec<String> {
+    let string_data = if concat_txt {
+        format!("{:#}", r.rdata)
+    } else {
+        r.rdata.to_string()
+    };
+
+    vec![
+        display_name(&r.name, idn),
+        r.ttl.as_secs().to_string(),
+        r.qclass.to_string(),
+        r.qtype.to_string(),
+        string_data,
+    ]
+}
+
+// the questions as they fit in a block `width` cells wide, the class goes on narrow terminals
+fn question_grid(message: &Message, idn: bool, width: u16) -> Grid {
+    Grid::new(
+        question_cells(message, idn),
+        width.saturating_sub(BORDER_SIZE),
+        &[1],
+    )
+}
 
-    let inner = Layout::default()
+// the records as they fit in a block `width` cells wide, the class and then the ttl go on
+// narrow terminals
+fn record_grid(message: &Message, concat_txt: bool, idn: bool, width: u16) -> Grid {
+    let cells = message
+        .records
+        .iter()
+        .map(|r| record_cells(r, concat_txt, idn))
+        .collect();
+    Grid::new(cells, width.saturating_sub(BORDER_SIZE), &[2, 1])
+}
+
+fn statistics_text(stats: &Statistics) -> String {
+    format!(
+        "Query time: {} msec\nWhen: {}\nMsg SENT: {} bytes\nMsg RCVD: {} bytes\nCookie: {}\n",
+        stats.query_time.as_millis(),
+        stats.current_time.format("%Y-%m-%d %H:%M:%S"),
+        stats.msg_sent,
+        stats.msg_rcvd,
+        stats.cookie
+    )
+}
+
+// The blocks of the tui laid out for a terminal `width` cells wide. The tables take the full
+// width, header and statistics share a row when they fit next to each other and are stacked
+// otherwise. Everything wraps instead of being cut off.
+struct Screen {
+    header: Vec<String>,
+    statistics: Vec<String>,
+    side_by_side: bool,
+    questions: Grid,
+    records: Grid,
+}
+
+impl Screen {
+    fn new(
+        message: &Message,
+        stats: &Statistics,
+        concat_txt: bool,
+        idn: bool,
+        width: u16,
+    ) -> Screen {
+        let header = header_text(message);
+        let statistics = statistics_text(stats);
+        let widest = |text: &str| text.lines().map(|l| l.width() as u16).max().unwrap_or(0);
+        let header_width = widest(&header) + BORDER_SIZE;
+        let statistics_width = widest(&statistics) + BORDER_SIZE;
+        let side_by_side = header_width + statistics_width <= width;
+        let (header_width, statistics_width) = if side_by_side {
+            (width - statistics_width, statistics_width)
+        } else {
+            (width, width)
+        };
+
+        Screen {
+            header: wrap_lines(&header, header_width.saturating_sub(BORDER_SIZE)),
+            statistics: wrap_lines(&statistics, statistics_width.saturating_sub(BORDER_SIZE)),
+            side_by_side,
+            questions: question_grid(message, idn, width),
+            records: record_grid(message, concat_txt, idn, width),
+        }
+    }
+
+    // the heights of the rows of blocks, from the top
+    fn rows(&self) -> Vec<u16> {
+        let header = self.header.len() as u16 + BORDER_SIZE;
+        let statistics = self.statistics.len() as u16 + BORDER_SIZE;
+        let mut rows = vec![TOP_BLOCK_SIZE];
+        if self.side_by_side {
+            rows.push(header.max(statistics));
+        } else {
+            rows.push(header);
+        }
+        rows.push(self.questions.height() + BORDER_SIZE);
+        rows.push(self.records.height() + BORDER_SIZE);
+        if !self.side_by_side {
+            rows.push(statistics);
+        }
+        rows
+    }
+
+    fn height(&self) -> u16 {
+        self.rows().iter().sum()
+    }
+}
+
+fn render_app(frame: &mut Frame, screen: &Screen, message: &Message, idn: bool) {
+    let rows = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(vec![
-            Constraint::Length(TOP_BLOCK_SIZE),
-            Constraint::Length(HEADER_BLOCK_SIZE),
-            Constraint::Length(QUESTION_BLOCK_SIZE + message.header.qd_count),
-            Constraint::Length(MESSAGE_BLOCK_SIZE + message.header.an_count),
-            Constraint::Length(STAT_BLOCK_SIZE),
-        ])
-        .split(outer[0]);
+        .constraints(
+            screen
+                .rows()
+                .into_iter()
+                .map(Constraint::Length)
+                .collect::<Vec<_>>(),
+        )
+        .split(frame.size());
 
     let program_info = Line::from(vec![
         "== Who are you? ==".into(),
         " ".into(),
         format!("v{}", VERSION).into(),
         " == ".into(),
-        message.question.qname.clone().into(),
+        message
+            .question()
+            .map(|q| display_name(&q.qname, idn))
+            .unwrap_or_default()
+            .into(),
         " == ".into(),
     ]);
 
-    frame.render_widget(Paragraph::new(program_info).fg(Color::White), inner[0]);
+    frame.render_widget(Paragraph::new(program_info).fg(Color::White), rows[0]);
+
+    let (header_area, statistics_area) = if screen.side_by_side {
+        let statistics_width = screen
+            .statistics
+            .iter()
+            .map(|l| l.width())
+            .max()
+            .unwrap_or(0);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Min(0),
+                Constraint::Length(statistics_width as u16 + BORDER_SIZE),
+            ])
+            .split(rows[1]);
+        (columns[0], columns[1])
+    } else {
+        (rows[1], rows[4])
+    };
 
     // Header
     frame.render_widget(
-        Paragraph::new(format!("{}", message.header))
+        Paragraph::new(screen.header.join("\n"))
             .fg(Color::White)
             .block(
                 Block::new()
@@ -219,118 +1555,33 @@ fn render_app(frame: &mut Frame, message: &Message, stats: &Statistics) {
                     .borders(Borders::ALL)
                     .fg(Color::Green),
             ),
-        inner[1],
+        header_area,
     );
 
     // Question
-    let row = Row::new(vec![
-        Cell::from(message.question.qname.clone()),
-        Cell::from(""),
-        Cell::from(message.question.qclass.to_string()),
-        Cell::from(message.question.qtype.to_string()),
-    ])
-    .fg(Color::White);
-
-    let t = Table::new(vec![row])
+    let t = Table::new(screen.questions.rows())
         .block(
             Block::new()
                 .title("Message")
                 .borders(Borders::ALL)
                 .fg(Color::Green),
         )
-        .widths(&[
-            Constraint::Percentage(30),
-            Constraint::Percentage(10),
-            Constraint::Percentage(15),
-            Constraint::Percentage(15),
-        ]);
+        .widths(screen.questions.widths());
 
-    frame.render_widget(t, inner[2]);
+    frame.render_widget(t, rows[2]);
 
     // Records
-    let record_rows = message.records.iter().map(|r| {
-        let string_data = match &r.rdata {
-            dns::record::RData::A(ip) => ip.to_string(),
-            dns::record::RData::CNAME(cname) => cname.to_string(),
-            dns::record::RData::TXT(txt) => txt.to_string(),
-            dns::record::RData::AAAA(ip) => ip.to_string(),
-            dns::record::RData::NS(ns) => ns.to_string(),
-            dns::record::RData::MX {
-                preference,
-                exchange,
-            } => format!("{preference} {exchange}"),
-            dns::record::RData::SOA {
-                mname,
-                rname,
-                serial,
-                refresh,
-                retry,
-                expire,
-                minimum,
-            } => format!("{mname} {rname} {serial} {refresh} {retry} {expire} {minimum}"),
-        };
-
-        Row::new(vec![
-            Cell::from(r.name.clone()),
-            Cell::from(r.ttl.as_secs().to_string()),
-            Cell::from(r.qclass.to_string()),
-            Cell::from(r.qtype.to_string()),
-            Cell::from(string_data),
-        ])
-        .fg(Color::White)
-    });
-
-    let record_table = Table::new(record_rows)
+    let record_table = Table::new(screen.records.rows())
         .block(
             Block::new()
-                .title("Records")
+                .title(records_title(message))
                 .borders(Borders::ALL)
                 .fg(Color::Green),
         )
-        .widths(&[
-            Constraint::Percentage(30),
-            Constraint::Percentage(10),
-            Constraint::Percentage(15),
-            Constraint::Percentage(15),
-            Constraint::Percentage(40),
-        ]);
-    frame.render_widget(record_table, inner[3]);
-
-    let query_time = Line::from(vec![
-        "Query time:".into(),
-        " ".into(),
-        stats.query_time.as_millis().to_string().into(),
-        " ".into(),
-        "msec".into(),
-    ]);
-
-    let current_time = Line::from(vec![
-        "When:".into(),
-        " ".into(),
-        stats
-            .current_time
-            .format("%Y-%m-%d %H:%M:%S")
-            .to_string()
-            .into(),
-    ]);
-
-    let message_sent = Line::from(vec![
-        "Msg SENT:".into(),
-        " ".into(),
-        stats.msg_sent.to_string().into(),
-        " ".into(),
-        "bytes".into(),
-    ]);
-
-    let message_rcvd = Line::from(vec![
-        "Msg RCVD:".into(),
-        " ".into(),
-        stats.msg_rcvd.to_string().into(),
-        " ".into(),
-        "bytes".into(),
-    ]);
+        .widths(screen.records.widths());
+    frame.render_widget(record_table, rows[3]);
 
-    let t = Paragraph::new(vec![query_time, current_time, message_sent, message_rcvd])
+    let t = Paragraph::new(screen.statistics.join("\n"))
         .block(
             Block::new()
                 .title("Statistics")
@@ -338,5 +1589,44 @@ fn render_app(frame: &mut Frame, message: &Message, stats: &Statistics) {
                 .fg(Color::Green),
         )
         .fg(Color::White);
-    frame.render_widget(t, inner[4]);
+    frame.render_widget(t, statistics_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn normalize_only_flags() {
+        let args = [
+            "who",
+            "example.com",
+            "+adflag",
+            "ixfr=5",
+            "--subnet",
+            "+1",
+            "-y",
+            "ixfr=x",
+            "+cd",
+            "--",
+            "+tcp",
+        ];
+        assert_eq!(
+            vec![
+                "who",
+                "example.com",
+                "--adflag",
+                "--ixfr=5",
+                "--subnet",
+                "+1",
+                "-y",
+                "ixfr=x",
+                "--cd",
+                "--",
+                "+tcp",
+            ],
+            normalize_args(args.iter().map(|a| a.to_string()))
+        );
+    }
 }
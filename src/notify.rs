@@ -0,0 +1,194 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use tokio::net::UdpSocket;
+
+use crate::client::{exchange, send_udp, MAX_UDP_SIZE};
+use crate::dns::header::{Opcode, ResponseCode};
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::record::Record;
+use crate::dns::tsig::{self, TsigKey, Verifier};
+use crate::dns::Serialize;
+use crate::xfr::soa_serial;
+
+// asks a server for the SOA of the zone, the serial in it is what secondaries compare against
+pub async fn current_soa(zone: &Name, server: SocketAddr) -> Result<Record> {
+    let request = Message::soa(zone.clone()).serialize()?;
+    let response = exchange(&request, server).await?;
+    let message =
+        Message::try_from(response.as_slice()).context("Failed to deserialize response")?;
+    message
+        .records
+        .into_iter()
+        .find(|r| soa_serial(r).is_some())
+        .ok_or_else(|| {
+            anyhow!(
+                "{} has no SOA for {}: {}",
+                server,
+                zone,
+                message.header.r_code
+            )
+        })
+}
+
+// The answer of a secondary to a NOTIFY.
+pub struct Acknowledgement {
+    pub r_code: ResponseCode,
+    pub elapsed: Duration,
+}
+
+// Sends a NOTIFY for the zone and waits for the secondary to acknowledge it (rfc 1996 3.6).
+// NOTIFY is only sent over udp, a secondary that does not answer is asked again later by real
+// primaries, here it is an error.
+pub async fn send(
+    zone: &Name,
+    soa: Option<Record>,
+    server: SocketAddr,
+    key: Option<&TsigKey>,
+) -> Result<Acknowledgement> {
+    let mut request = Message::notify(zone.clone(), soa);
+    let request_mac = match key {
+        Some(key) => Some(tsig::sign(&mut request, key)?),
+        None => None,
+    };
+    let raw = request.serialize().context("Failed to serialize notify")?;
+
+    let start = Instant::now();
+    let response = send_udp(&raw, server).await?;
+    let elapsed = start.elapsed();
+
+    let message =
+        Message::try_from(response.as_slice()).context("Failed to deserialize response")?;
+    if message.header.id != request.header.id
+        || !message.header.qr
+        || message.header.opcode != Opcode::Notify
+    {
+        return Err(anyhow!(
+            "response from {} is not an acknowledgement",
+            server
+        ));
+    }
+    if let (Some(key), Some(request_mac)) = (key, request_mac) {
+        Verifier::new(key, request_mac).verify(&response)?;
+    }
+    Ok(Acknowledgement {
+        r_code: message.header.r_code,
+        elapsed,
+    })
+}
+
+// A NOTIFY that arrived at the listener.
+pub struct Notification {
+    pub from: SocketAddr,
+    pub message: Message,
+}
+
+impl Notification {
+    pub fn zone(&self) -> Name {
+        self.message
+            .question()
+            .map(|q| q.qname.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn serial(&self) -> Option<u32> {
+        self.message.records.iter().find_map(soa_serial)
+    }
+}
+
+// Waits for the next NOTIFY on the socket and acknowledges it. Anything else that is not a
+// request is dropped, other requests are refused with NOTIMP.
+pub async fn receive(socket: &UdpSocket) -> Result<Notification> {
+    let mut buffer = vec![0; MAX_UDP_SIZE];
+    loop {
+        let (length, from) = socket.recv_from(&mut buffer).await?;
+        let Ok(message) = Message::try_from(&buffer[..length]) else {
+            eprintln!("; dropped malformed message from {}", from);
+            continue;
+        };
+        if message.header.qr {
+            continue;
+        }
+        if message.header.opcode != Opcode::Notify {
+            let reply = message.reply(ResponseCode::NotImplemented).serialize()?;
+            socket.send_to(&reply, from).await?;
+            continue;
+        }
+
+        let reply = message.reply(ResponseCode::NoError).serialize()?;
+        socket.send_to(&reply, from).await?;
+        return Ok(Notification { from, message });
+    }
+}
+
+// prints a received NOTIFY the way the rest of the output looks, as a zone file comment
+// followed by the records it carried
+pub fn print(notification: &Notification) {
+    let serial = notification
+        .serial()
+        .map_or("unknown".to_owned(), |s| s.to_string());
+    println!(
+        "; {} NOTIFY from {} for zone {}, serial {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        notification.from,
+        notification.zone(),
+        serial
+    );
+    for record in &notification.message.records {
+        println!("{}", record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::record::RData;
+    use crate::dns::QClass;
+    use pretty_assertions::assert_eq;
+
+    fn soa(serial: u32) -> Record {
+        let rdata = RData::SOA {
+            mname: "ns1.example.com".parse().unwrap(),
+            rname: "hostmaster.example.com".parse().unwrap(),
+            serial,
+            refresh: 3600,
+            retry: 600,
+            expire: 86400,
+            minimum: 300,
+        };
+        let rd_length = rdata.serialize().unwrap().len() as u16;
+        Record::new(
+            "example.com".parse().unwrap(),
+            crate::dns::QType::SOA,
+            QClass::IN,
+            Duration::from_secs(3600),
+            rd_length,
+            rdata,
+        )
+    }
+
+    #[tokio::test]
+    async fn notify_is_acknowledged() {
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let secondary = tokio::spawn(async move { receive(&listener).await.unwrap() });
+
+        let acknowledgement = send(
+            &"example.com".parse().unwrap(),
+            Some(soa(2024010101)),
+            address,
+            None,
+        )
+        .await
+        .unwrap();
+        let notification = secondary.await.unwrap();
+
+        assert_eq!(ResponseCode::NoError, acknowledgement.r_code);
+        assert_eq!("example.com", notification.zone());
+        assert_eq!(Some(2024010101), notification.serial());
+        assert!(notification.message.header.aa);
+    }
+}
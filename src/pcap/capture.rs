@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use nom::bytes::complete::take;
+use nom::number::complete::{u16, u32};
+use nom::number::Endianness;
+use nom::Finish;
+
+use crate::dns::parse_utils::VResult;
+
+const PCAP_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER: u32 = 0x1a2b_3c4d;
+
+// pcapng block types (draft-ietf-opsawg-pcapng 4)
+const INTERFACE_DESCRIPTION: u32 = 1;
+const SIMPLE_PACKET: u32 = 3;
+const ENHANCED_PACKET: u32 = 6;
+// the interface option that tells how fine the timestamps are
+const IF_TSRESOL: u16 = 9;
+
+// A packet as it was captured: when, the link layer it starts with (a LINKTYPE_ value) and the
+// bytes that were kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub time: Duration,
+    pub link: u32,
+    pub data: Vec<u8>,
+}
+
+fn error(what: &str, e: nom::error::Error<&[u8]>) -> anyhow::Error {
+    anyhow!(
+        "{} is cut short ({:?} with {} octets left)",
+        what,
+        e.code,
+        e.input.len()
+    )
+}
+
+// reads every packet of a classic pcap or a pcapng capture
+pub fn read(bytes: &[u8]) -> Result<Vec<Frame>> {
+    let magic = bytes
+        .get(..4)
+        .ok_or_else(|| anyhow!("not a capture, the file is too short"))?;
+    let big = u32::from_be_bytes(magic.try_into()?);
+    let little = u32::from_le_bytes(magic.try_into()?);
+    match (big, little) {
+        (PCAP_MICROS, _) => read_pcap(bytes, Endianness::Big, 1_000),
+        (PCAP_NANOS, _) => read_pcap(bytes, Endianness::Big, 1),
+        (_, PCAP_MICROS) => read_pcap(bytes, Endianness::Little, 1_000),
+        (_, PCAP_NANOS) => read_pcap(bytes, Endianness::Little, 1),
+        (PCAPNG_SECTION, _) => read_pcapng(bytes),
+        _ => Err(anyhow!("not a pcap or pcapng capture, magic {:08x}", big)),
+    }
+}
+
+// the global header: magic, version, zone, sigfigs, snaplen and link type
+fn pcap_header(endian: Endianness) -> impl Fn(&[u8]) -> VResult<&[u8], u32> {
+    move |buffer| {
+        let (buffer, _) = take(20usize)(buffer)?;
+        u32(endian)(buffer)
+    }
+}
+
+// seconds, the fraction of a second and the data of a packet
+type PcapRecord<'a> = (u32, u32, &'a [u8]);
+
+// a packet record: seconds, fraction, captured and original length and the data
+fn pcap_record(endian: Endianness) -> impl Fn(&[u8]) -> VResult<&[u8], PcapRecord<'_>> {
+    move |buffer| {
+        let (buffer, seconds) = u32(endian)(buffer)?;
+        let (buffer, fraction) = u32(endian)(buffer)?;
+        let (buffer, captured) = u32(endian)(buffer)?;
+        let (buffer, _) = u32(endian)(buffer)?;
+        let (buffer, data) = take(captured)(buffer)?;
+        Ok((buffer, (seconds, fraction, data)))
+    }
+}
+
+fn read_pcap(bytes: &[u8], endian: Endianness, nanos_per_unit: u32) -> Result<Vec<Frame>> {
+    let (mut buffer, link) = pcap_header(endian)(bytes)
+        .finish()
+        .map_err(|e| error("the pcap header", e))?;
+    let mut frames = Vec::new();
+    while !buffer.is_empty() {
+        let (rest, (seconds, fraction, data)) = pcap_record(endian)(buffer)
+            .finish()
+            .map_err(|e| error("a packet record", e))?;
+        frames.push(Frame {
+            time: Duration::new(seconds as u64, fraction.saturating_mul(nanos_per_unit)),
+            link,
+            data: data.to_vec(),
+        });
+        buffer = rest;
+    }
+    Ok(frames)
+}
+
+// a block: its type and body, the length is given before and after the body
+fn block(endian: Endianness) -> impl Fn(&[u8]) -> VResult<&[u8], (u32, &[u8])> {
+    move |buffer| {
+        let (buffer, kind) = u32(endian)(buffer)?;
+        let (buffer, length) = u32(endian)(buffer)?;
+        let (buffer, body) = take(length.saturating_sub(12))(buffer)?;
+        let (buffer, _) = u32(endian)(buffer)?;
+        Ok((buffer, (kind, body)))
+    }
+}
+
+// an option of an interface: its code and value, padded to 32 bits
+fn option(endian: Endianness) -> impl Fn(&[u8]) -> VResult<&[u8], (u16, &[u8])> {
+    move |buffer| {
+        let (buffer, code) = u16(endian)(buffer)?;
+        let (buffer, length) = u16(endian)(buffer)?;
+        let (buffer, value) = take(length)(buffer)?;
+        let (buffer, _) = take((4 - length as usize % 4) % 4)(buffer)?;
+        Ok((buffer, (code, value)))
+    }
+}
+
+// an interface of a pcapng section, timestamps count in units of 1/`resolution` seconds
+struct Interface {
+    link: u32,
+    resolution: u64,
+}
+
+impl Interface {
+    fn parse(body: &[u8], endian: Endianness) -> VResult<&[u8], Interface> {
+        let (buffer, link) = u16(endian)(body)?;
+        let (mut buffer, _) = take(6usize)(buffer)?;
+        let mut resolution = 1_000_000;
+        while buffer.len() >= 4 {
+            let (rest, (code, value)) = option(endian)(buffer)?;
+            if code == IF_TSRESOL && value.len() == 1 {
+                resolution = match value[0] {
+                    v if v & 0x80 == 0 => 10u64.saturating_pow(v as u32),
+                    v => 1u64 << (v & 0x7f).min(63),
+                };
+            }
+            buffer = rest;
+        }
+        Ok((
+            buffer,
+            Interface {
+                link: link as u32,
+                resolution,
+            },
+        ))
+    }
+
+    fn time(&self, units: u64) -> Duration {
+        let seconds = units / self.resolution;
+        let rest = units % self.resolution;
+        Duration::new(
+            seconds,
+            (rest as u128 * 1_000_000_000 / self.resolution as u128) as u32,
+        )
+    }
+}
+
+// an enhanced packet: interface, timestamp in two halves, captured and original length, data
+fn enhanced_packet(body: &[u8], endian: Endianness) -> VResult<&[u8], (u32, u64, &[u8])> {
+    let (buffer, interface) = u32(endian)(body)?;
+    let (buffer, high) = u32(endian)(buffer)?;
+    let (buffer, low) = u32(endian)(buffer)?;
+    let (buffer, captured) = u32(endian)(buffer)?;
+    let (buffer, _) = u32(endian)(buffer)?;
+    let (buffer, data) = take(captured)(buffer)?;
+    Ok((buffer, (interface, (high as u64) << 32 | low as u64, data)))
+}
+
+// Reads the blocks of every section. A section starts over with its own byte order and
+// interfaces, blocks other than interfaces and packets are skipped.
+fn read_pcapng(bytes: &[u8]) -> Result<Vec<Frame>> {
+    let mut buffer = bytes;
+    let mut endian = Endianness::Big;
+    let mut interfaces = Vec::new();
+    let mut frames = Vec::new();
+    while !buffer.is_empty() {
+        if buffer.len() >= 12 && buffer[..4] == PCAPNG_SECTION.to_be_bytes() {
+            let order = u32::from_be_bytes(buffer[8..12].try_into()?);
+            endian = if order == PCAPNG_BYTE_ORDER {
+                Endianness::Big
+            } else {
+                Endianness::Little
+            };
+            interfaces.clear();
+        }
+        let (rest, (kind, body)) = block(endian)(buffer)
+            .finish()
+            .map_err(|e| error("a pcapng block", e))?;
+        match kind {
+            INTERFACE_DESCRIPTION => {
+                let (_, interface) = Interface::parse(body, endian)
+                    .finish()
+                    .map_err(|e| error("an interface description", e))?;
+                interfaces.push(interface);
+            }
+            ENHANCED_PACKET => {
+                let (_, (index, units, data)) = enhanced_packet(body, endian)
+                    .finish()
+                    .map_err(|e| error("an enhanced packet", e))?;
+                let interface = interfaces
+                    .get(index as usize)
+                    .ok_or_else(|| anyhow!("a packet of unknown interface {}", index))?;
+                frames.push(Frame {
+                    time: interface.time(units),
+                    link: interface.link,
+                    data: data.to_vec(),
+                });
+            }
+            SIMPLE_PACKET => {
+                let interface = interfaces
+                    .first()
+                    .ok_or_else(|| anyhow!("a simple packet before any interface"))?;
+                let (data, length) = u32::<_, nom::error::Error<&[u8]>>(endian)(body)
+                    .finish()
+                    .map_err(|e| error("a simple packet", e))?;
+                let length = (length as usize).min(data.len());
+                frames.push(Frame {
+                    time: Duration::ZERO,
+                    link: interface.link,
+                    data: data[..length].to_vec(),
+                });
+            }
+            _ => {}
+        }
+        buffer = rest;
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_classic_pcap() {
+        let mut bytes = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        bytes.extend([0; 8]);
+        bytes.extend(65535u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        for (seconds, micros) in [(1_700_000_000u32, 250_000u32), (1_700_000_001, 5)] {
+            bytes.extend(seconds.to_le_bytes());
+            bytes.extend(micros.to_le_bytes());
+            bytes.extend(3u32.to_le_bytes());
+            bytes.extend(60u32.to_le_bytes());
+            bytes.extend([1, 2, 3]);
+        }
+        let frames = read(&bytes).unwrap();
+        assert_eq!(2, frames.len());
+        assert_eq!(Duration::new(1_700_000_000, 250_000_000), frames[0].time);
+        assert_eq!(Duration::new(1_700_000_001, 5_000), frames[1].time);
+        assert_eq!(vec![1, 2, 3], frames[1].data);
+        assert_eq!(1, frames[1].link);
+
+        assert!(read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read(b"GIF89a").is_err());
+    }
+
+    fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+        let length = 12 + body.len() as u32;
+        let mut block = kind.to_be_bytes().to_vec();
+        block.extend(length.to_be_bytes());
+        block.extend(body);
+        block.extend(length.to_be_bytes());
+        block
+    }
+
+    #[test]
+    fn read_pcapng_with_nanosecond_timestamps() {
+        let mut section = PCAPNG_BYTE_ORDER.to_be_bytes().to_vec();
+        section.extend([0, 1, 0, 0]);
+        section.extend(u64::MAX.to_be_bytes());
+        let mut bytes = block(PCAPNG_SECTION, &section);
+
+        // raw ip, if_tsresol 9 and the end of options
+        let mut interface = vec![0, 101, 0, 0, 0, 0, 0xff, 0xff];
+        interface.extend([0, 9, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend(block(INTERFACE_DESCRIPTION, &interface));
+
+        let units: u64 = 1_700_000_000_123_456_789;
+        let mut packet = 0u32.to_be_bytes().to_vec();
+        packet.extend(((units >> 32) as u32).to_be_bytes());
+        packet.extend((units as u32).to_be_bytes());
+        packet.extend(5u32.to_be_bytes());
+        packet.extend(5u32.to_be_bytes());
+        packet.extend([1, 2, 3, 4, 5, 0, 0, 0]);
+        bytes.extend(block(ENHANCED_PACKET, &packet));
+
+        let frames = read(&bytes).unwrap();
+        assert_eq!(
+            vec![Frame {
+                time: Duration::new(1_700_000_000, 123_456_789),
+                link: 101,
+                data: vec![1, 2, 3, 4, 5],
+            }],
+            frames
+        );
+    }
+}
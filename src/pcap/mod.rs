@@ -0,0 +1,315 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{Local, TimeZone};
+
+use crate::dns::header::ResponseCode;
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::question::Question;
+use crate::dns::QType;
+use packet::Transport;
+
+mod capture;
+mod packet;
+
+// a DNS message read from a capture
+#[derive(Debug, Clone)]
+struct Seen {
+    time: Duration,
+    transport: Transport,
+    source: SocketAddr,
+    destination: SocketAddr,
+    message: Message,
+}
+
+// a query and the response to it, either can be missing from the capture
+#[derive(Debug, Clone)]
+struct Exchange {
+    query: Option<Seen>,
+    response: Option<Seen>,
+}
+
+// which exchanges to show, a name also matches the names under it
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub name: Option<Name>,
+    pub qtype: Option<QType>,
+    pub rcode: Option<ResponseCode>,
+}
+
+// Pairs every response with the first query before it that has the same id over the same
+// transport between the same two addresses, the other way around.
+fn pair(seen: Vec<Seen>) -> Vec<Exchange> {
+    let mut exchanges: Vec<Exchange> = Vec::new();
+    let mut waiting: HashMap<_, VecDeque<usize>> = HashMap::new();
+    for seen in seen {
+        let id = seen.message.header.id;
+        if !seen.message.header.qr {
+            let key = (seen.transport, id, seen.source, seen.destination);
+            waiting.entry(key).or_default().push_back(exchanges.len());
+            exchanges.push(Exchange {
+                query: Some(seen),
+                response: None,
+            });
+            continue;
+        }
+        let key = (seen.transport, id, seen.destination, seen.source);
+        match waiting.get_mut(&key).and_then(VecDeque::pop_front) {
+            Some(i) => exchanges[i].response = Some(seen),
+            None => exchanges.push(Exchange {
+                query: None,
+                response: Some(seen),
+            }),
+        }
+    }
+    exchanges
+}
+
+impl Exchange {
+    fn first(&self) -> &Seen {
+        self.query
+            .as_ref()
+            .or(self.response.as_ref())
+            .expect("an exchange has a query or a response")
+    }
+
+    fn question(&self) -> Option<&Question> {
+        self.first().message.question()
+    }
+
+    fn elapsed(&self) -> Option<Duration> {
+        let (query, response) = (self.query.as_ref()?, self.response.as_ref()?);
+        Some(response.time.saturating_sub(query.time))
+    }
+
+    fn text(&self, idn: bool) -> String {
+        let first = self.first();
+        let (client, server) = match &self.query {
+            Some(query) => (query.source, query.destination),
+            None => (first.destination, first.source),
+        };
+        let time = Local
+            .timestamp_opt(first.time.as_secs() as i64, first.time.subsec_nanos())
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+            .unwrap_or_default();
+        let question = self
+            .question()
+            .map(|q| {
+                format!(
+                    "{} {} {}",
+                    crate::display_name(&q.qname, idn),
+                    q.qclass,
+                    q.qtype
+                )
+            })
+            .unwrap_or_else(|| "no question".to_owned());
+        let mut text = format!(
+            "{} {} {} > {} id {} {}\n",
+            time, first.transport, client, server, first.message.header.id, question
+        );
+
+        let Some(response) = &self.response else {
+            text.push_str("  no response\n");
+            return text;
+        };
+        let message = &response.message;
+        let status = match self.elapsed() {
+            Some(elapsed) => format!(
+                "{} after {:.3}ms",
+                message.header.r_code,
+                elapsed.as_secs_f64() * 1000.0
+            ),
+            None => format!("{} without a query", message.header.r_code),
+        };
+        text.push_str(&format!(
+            "  {}, answer {}, authority {}, additional {}{}\n",
+            status,
+            message.records.len(),
+            message.authorities.len(),
+            message.additionals.len(),
+            if message.header.tc { ", truncated" } else { "" }
+        ));
+        for record in &message.records {
+            text.push_str(&format!(
+                "  {}\n",
+                crate::record_cells(record, false, idn).join(" ")
+            ));
+        }
+        text
+    }
+}
+
+impl Filter {
+    fn matches(&self, exchange: &Exchange) -> bool {
+        let question = exchange.question();
+        let name = self.name.as_ref().is_none_or(|name| {
+            question.is_some_and(|q| {
+                q.qname
+                    .to_lowercase()
+                    .labels()
+                    .ends_with(name.to_lowercase().labels())
+            })
+        });
+        let qtype = self
+            .qtype
+            .as_ref()
+            .is_none_or(|qtype| question.is_some_and(|q| &q.qtype == qtype));
+        let rcode = self.rcode.as_ref().is_none_or(|rcode| {
+            exchange
+                .response
+                .as_ref()
+                .is_some_and(|r| &r.message.header.r_code == rcode)
+        });
+        name && qtype && rcode
+    }
+}
+
+// Reads a pcap or pcapng capture and prints the DNS queries in it with their responses, the
+// ones the filter lets through.
+pub fn run(path: &Path, filter: &Filter, idn: bool) -> Result<()> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+    let frames = capture::read(&bytes).with_context(|| format!("reading {}", path.display()))?;
+
+    let mut seen = Vec::new();
+    let mut malformed = 0;
+    for payload in packet::payloads(&frames) {
+        match Message::try_from(payload.data.as_slice()) {
+            Ok(message) => seen.push(Seen {
+                time: payload.time,
+                transport: payload.transport,
+                source: payload.source,
+                destination: payload.destination,
+                message,
+            }),
+            Err(_) => malformed += 1,
+        }
+    }
+
+    let exchanges = pair(seen);
+    let shown = exchanges
+        .iter()
+        .filter(|e| filter.matches(e))
+        .collect::<Vec<_>>();
+    for exchange in &shown {
+        print!("{}", exchange.text(idn));
+    }
+    let unanswered = shown.iter().filter(|e| e.response.is_none()).count();
+    println!(
+        "{} of {} exchanges in {} frames, {} without a response",
+        shown.len(),
+        exchanges.len(),
+        frames.len(),
+        unanswered
+    );
+    if malformed > 0 {
+        println!(
+            "{} packets to or from port 53 could not be decoded",
+            malformed
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::QClass;
+    use pretty_assertions::assert_eq;
+
+    fn seen(time: u64, transport: Transport, from: &str, to: &str, message: &Message) -> Seen {
+        Seen {
+            time: Duration::from_millis(time),
+            transport,
+            source: from.parse().unwrap(),
+            destination: to.parse().unwrap(),
+            message: message.clone(),
+        }
+    }
+
+    #[test]
+    fn pair_by_id_and_addresses() {
+        let mut query = Message::query("www.example.com".parse().unwrap(), QType::A, QClass::IN);
+        query.header.id = 7;
+        let mut other = Message::query("example.net".parse().unwrap(), QType::MX, QClass::IN);
+        other.header.id = 7;
+        let (client, server) = ("192.0.2.1:40000", "192.0.2.53:53");
+
+        let exchanges = pair(vec![
+            seen(0, Transport::Udp, client, server, &query),
+            seen(1, Transport::Tcp, client, server, &other),
+            // the same id from another client
+            seen(2, Transport::Udp, "192.0.2.2:40000", server, &query),
+            seen(
+                5,
+                Transport::Tcp,
+                server,
+                client,
+                &other.reply(ResponseCode::NameError),
+            ),
+            seen(
+                9,
+                Transport::Udp,
+                server,
+                client,
+                &query.reply(ResponseCode::NoError),
+            ),
+            seen(
+                12,
+                Transport::Udp,
+                server,
+                client,
+                &query.reply(ResponseCode::NoError),
+            ),
+        ]);
+        let summary = exchanges
+            .iter()
+            .map(|e| {
+                (
+                    e.question().unwrap().qname.to_string(),
+                    e.first().source.to_string(),
+                    e.elapsed(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    "www.example.com".to_owned(),
+                    client.to_owned(),
+                    Some(Duration::from_millis(9))
+                ),
+                (
+                    "example.net".to_owned(),
+                    client.to_owned(),
+                    Some(Duration::from_millis(4))
+                ),
+                (
+                    "www.example.com".to_owned(),
+                    "192.0.2.2:40000".to_owned(),
+                    None
+                ),
+                ("www.example.com".to_owned(), server.to_owned(), None),
+            ],
+            summary
+        );
+
+        let filter = |name: Option<&str>, qtype, rcode: Option<&str>| Filter {
+            name: name.map(|n| n.parse().unwrap()),
+            qtype,
+            rcode: rcode.map(|r| r.parse().unwrap()),
+        };
+        let count = |filter: Filter| exchanges.iter().filter(|e| filter.matches(e)).count();
+        assert_eq!(4, count(Filter::default()));
+        assert_eq!(3, count(filter(Some("EXAMPLE.com"), None, None)));
+        assert_eq!(0, count(filter(Some("ample.com"), None, None)));
+        assert_eq!(1, count(filter(None, Some(QType::MX), None)));
+        assert_eq!(1, count(filter(None, None, Some("nxdomain"))));
+        assert_eq!(2, count(filter(Some("example.com"), None, Some("0"))));
+    }
+}
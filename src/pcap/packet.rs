@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use nom::bytes::complete::take;
+use nom::number::complete::{be_u16, be_u32, be_u8, le_u32};
+
+use super::capture::Frame;
+use crate::dns::parse_utils::VResult;
+
+const DNS_PORT: u16 = 53;
+
+// link types of the frames (https://www.tcpdump.org/linktypes.html)
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_UDP: u8 = 17;
+// ipv6 extension headers that are skipped to get to the transport: hop-by-hop, routing and
+// destination options
+const IPV6_EXTENSIONS: [u8; 3] = [0, 43, 60];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "udp"),
+            Transport::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+// A DNS message as it was seen on the wire, not parsed yet. Over tcp it is the message without
+// its length prefix and the time is that of the latest segment it needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    pub time: Duration,
+    pub transport: Transport,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+// a tcp segment to or from port 53
+#[derive(Debug)]
+struct Segment {
+    time: Duration,
+    seq: u32,
+    syn: bool,
+    data: Vec<u8>,
+}
+
+// the ip layer of a frame, None for anything that is not ip
+fn link(frame: &Frame) -> Option<&[u8]> {
+    let data = frame.data.as_slice();
+    let (data, ethertype) = match frame.link {
+        LINKTYPE_ETHERNET => {
+            let (data, _) = take::<_, _, nom::error::Error<&[u8]>>(12usize)(data).ok()?;
+            ethertype(data)?
+        }
+        // the address family in host byte order, ipv4 is 2 everywhere, ipv6 differs per system
+        LINKTYPE_NULL => {
+            let (data, family) = le_u32::<_, nom::error::Error<&[u8]>>(data).ok()?;
+            let family = if family > 0xffff {
+                family.swap_bytes()
+            } else {
+                family
+            };
+            match family {
+                2 => (data, ETHERTYPE_IPV4),
+                24 | 28 | 30 => (data, ETHERTYPE_IPV6),
+                _ => return None,
+            }
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => match data.first()? >> 4 {
+            4 => (data, ETHERTYPE_IPV4),
+            6 => (data, ETHERTYPE_IPV6),
+            _ => return None,
+        },
+        // packet type, address type and length, 8 octets of address and the protocol
+        LINKTYPE_LINUX_SLL => {
+            let (data, _) = take::<_, _, nom::error::Error<&[u8]>>(14usize)(data).ok()?;
+            be_u16::<_, nom::error::Error<&[u8]>>(data).ok()?
+        }
+        // the protocol first, then interface, address type, packet type, length and address
+        LINKTYPE_LINUX_SLL2 => {
+            let (rest, protocol) = be_u16::<_, nom::error::Error<&[u8]>>(data).ok()?;
+            let (rest, _) = take::<_, _, nom::error::Error<&[u8]>>(18usize)(rest).ok()?;
+            (rest, protocol)
+        }
+        _ => return None,
+    };
+    matches!(ethertype, ETHERTYPE_IPV4 | ETHERTYPE_IPV6).then_some(data)
+}
+
+// the ethertype after any 802.1Q and 802.1ad tags
+fn ethertype(data: &[u8]) -> Option<(&[u8], u16)> {
+    let (mut data, mut ethertype) = be_u16::<_, nom::error::Error<&[u8]>>(data).ok()?;
+    while matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+        let (rest, _) = be_u16::<_, nom::error::Error<&[u8]>>(data).ok()?;
+        (data, ethertype) = be_u16::<_, nom::error::Error<&[u8]>>(rest).ok()?;
+    }
+    Some((data, ethertype))
+}
+
+// the addresses, transport protocol and transport layer of an ip packet
+fn ip(data: &[u8]) -> VResult<&[u8], (IpAddr, IpAddr, u8, &[u8])> {
+    let (_, version) = be_u8(data)?;
+    if version >> 4 == 4 {
+        ipv4(data)
+    } else {
+        ipv6(data)
+    }
+}
+
+fn ipv4(data: &[u8]) -> VResult<&[u8], (IpAddr, IpAddr, u8, &[u8])> {
+    let (buffer, version) = be_u8(data)?;
+    let header_length = (version & 0x0f) as usize * 4;
+    let (buffer, _) = be_u8(buffer)?;
+    let (buffer, total_length) = be_u16(buffer)?;
+    let (buffer, _) = take(5usize)(buffer)?;
+    let (buffer, protocol) = be_u8(buffer)?;
+    let (buffer, _) = be_u16(buffer)?;
+    let (buffer, source) = be_u32(buffer)?;
+    let (_, destination) = be_u32(buffer)?;
+    let (rest, _) = take(header_length)(data)?;
+    // ethernet pads short frames, the ip length tells where the packet ends
+    let length = (total_length as usize)
+        .saturating_sub(header_length)
+        .min(rest.len());
+    Ok((
+        &rest[length..],
+        (
+            IpAddr::V4(Ipv4Addr::from(source)),
+            IpAddr::V4(Ipv4Addr::from(destination)),
+            protocol,
+            &rest[..length],
+        ),
+    ))
+}
+
+fn ipv6(data: &[u8]) -> VResult<&[u8], (IpAddr, IpAddr, u8, &[u8])> {
+    let (buffer, _) = take(4usize)(data)?;
+    let (buffer, payload_length) = be_u16(buffer)?;
+    let (buffer, mut next) = be_u8(buffer)?;
+    let (buffer, _) = be_u8(buffer)?;
+    let (buffer, source) = take(16usize)(buffer)?;
+    let (buffer, destination) = take(16usize)(buffer)?;
+    let length = (payload_length as usize).min(buffer.len());
+    let (rest, mut payload) = (&buffer[length..], &buffer[..length]);
+    while IPV6_EXTENSIONS.contains(&next) {
+        let (buffer, header) = be_u8(payload)?;
+        let (_, length) = be_u8(buffer)?;
+        (payload, _) = take((length as usize + 1) * 8)(payload)?;
+        next = header;
+    }
+    let address = |octets: &[u8]| {
+        let octets: [u8; 16] = octets.try_into().unwrap_or_default();
+        IpAddr::V6(Ipv6Addr::from(octets))
+    };
+    Ok((rest, (address(source), address(destination), next, payload)))
+}
+
+// source and destination port, the rest is the payload
+fn udp(data: &[u8]) -> VResult<&[u8], (u16, u16, &[u8])> {
+    let (buffer, source) = be_u16(data)?;
+    let (buffer, destination) = be_u16(buffer)?;
+    let (buffer, length) = be_u16(buffer)?;
+    let (buffer, _) = be_u16(buffer)?;
+    let length = (length as usize).saturating_sub(8).min(buffer.len());
+    Ok((&buffer[length..], (source, destination, &buffer[..length])))
+}
+
+// source and destination port, sequence number, whether it is a SYN and the payload
+type TcpSegment<'a> = (u16, u16, u32, bool, &'a [u8]);
+
+fn tcp(data: &[u8]) -> VResult<&[u8], TcpSegment<'_>> {
+    let (buffer, source) = be_u16(data)?;
+    let (buffer, destination) = be_u16(buffer)?;
+    let (buffer, seq) = be_u32(buffer)?;
+    let (buffer, _) = be_u32(buffer)?;
+    let (buffer, offset) = be_u8(buffer)?;
+    let (_, flags) = be_u8(buffer)?;
+    let (payload, _) = take((offset >> 4) as usize * 4)(data)?;
+    Ok((&[], (source, destination, seq, flags & 0x02 != 0, payload)))
+}
+
+// Puts a tcp stream back together in the order of the sequence numbers and splits it into the
+// length prefixed messages. Segments that were sent again are used once, a gap in the capture
+// drops the message it falls in. A message is as late as the latest segment it needed.
+fn reassemble(mut segments: Vec<Segment>) -> Vec<(Duration, Vec<u8>)> {
+    let start = segments
+        .iter()
+        .find(|s| s.syn)
+        .map(|s| s.seq.wrapping_add(1))
+        .or_else(|| segments.first().map(|s| s.seq))
+        .unwrap_or_default();
+    segments.retain(|s| !s.data.is_empty());
+    segments.sort_by_key(|s| s.seq.wrapping_sub(start));
+
+    let mut messages = Vec::new();
+    let mut stream: Vec<u8> = Vec::new();
+    let mut next = 0u32;
+    let mut latest = Duration::ZERO;
+    for segment in segments {
+        let offset = segment.seq.wrapping_sub(start);
+        let end = offset.wrapping_add(segment.data.len() as u32);
+        if end <= next {
+            continue;
+        }
+        if offset > next {
+            stream.clear();
+            next = offset;
+        }
+        stream.extend_from_slice(&segment.data[(next - offset) as usize..]);
+        next = end;
+        latest = latest.max(segment.time);
+        while stream.len() >= 2 {
+            let length = u16::from_be_bytes([stream[0], stream[1]]) as usize;
+            if stream.len() < 2 + length {
+                break;
+            }
+            messages.push((latest, stream[2..2 + length].to_vec()));
+            stream.drain(..2 + length);
+        }
+    }
+    messages
+}
+
+// the DNS messages in the frames of a capture, in the order they were completed
+pub fn payloads(frames: &[Frame]) -> Vec<Payload> {
+    let mut payloads = Vec::new();
+    let mut streams: HashMap<(SocketAddr, SocketAddr), Vec<Segment>> = HashMap::new();
+    for frame in frames {
+        let Some((_, (source, destination, protocol, data))) = link(frame).and_then(|d| ip(d).ok())
+        else {
+            continue;
+        };
+        match protocol {
+            PROTOCOL_UDP => {
+                let Ok((_, (sport, dport, data))) = udp(data) else {
+                    continue;
+                };
+                if sport == DNS_PORT || dport == DNS_PORT {
+                    payloads.push(Payload {
+                        time: frame.time,
+                        transport: Transport::Udp,
+                        source: SocketAddr::new(source, sport),
+                        destination: SocketAddr::new(destination, dport),
+                        data: data.to_vec(),
+                    });
+                }
+            }
+            PROTOCOL_TCP => {
+                let Ok((_, (sport, dport, seq, syn, data))) = tcp(data) else {
+                    continue;
+                };
+                if sport == DNS_PORT || dport == DNS_PORT {
+                    let key = (
+                        SocketAddr::new(source, sport),
+                        SocketAddr::new(destination, dport),
+                    );
+                    streams.entry(key).or_default().push(Segment {
+                        time: frame.time,
+                        seq,
+                        syn,
+                        data: data.to_vec(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for ((source, destination), segments) in streams {
+        for (time, data) in reassemble(segments) {
+            payloads.push(Payload {
+                time,
+                transport: Transport::Tcp,
+                source,
+                destination,
+                data,
+            });
+        }
+    }
+    payloads.sort_by_key(|p| p.time);
+    payloads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn ipv4(protocol: u8, transport: Vec<u8>) -> Vec<u8> {
+        let mut packet = vec![0x45, 0];
+        packet.extend((20 + transport.len() as u16).to_be_bytes());
+        packet.extend([0, 0, 0, 0, 64, protocol, 0, 0, 192, 0, 2, 1, 192, 0, 2, 53]);
+        packet.extend(transport);
+        packet
+    }
+
+    fn tcp(seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = 40000u16.to_be_bytes().to_vec();
+        segment.extend(53u16.to_be_bytes());
+        segment.extend(seq.to_be_bytes());
+        segment.extend([0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        segment.extend(payload);
+        segment
+    }
+
+    fn frame(time: u64, link: u32, data: Vec<u8>) -> Frame {
+        Frame {
+            time: Duration::from_secs(time),
+            link,
+            data,
+        }
+    }
+
+    #[test]
+    fn find_dns_in_udp_and_tcp() {
+        // ethernet with a vlan tag, padded to the minimum frame size
+        let mut ethernet = vec![0; 12];
+        ethernet.extend([0x81, 0x00, 0x00, 0x07, 0x08, 0x00]);
+        let mut udp = 40000u16.to_be_bytes().to_vec();
+        udp.extend([0, 53, 0, 11, 0, 0, 1, 2, 3]);
+        ethernet.extend(ipv4(PROTOCOL_UDP, udp));
+        ethernet.extend([0; 10]);
+
+        // a message split over two segments, the second one captured twice and before the first
+        let frames = vec![
+            frame(1, LINKTYPE_ETHERNET, ethernet),
+            frame(2, LINKTYPE_RAW, ipv4(PROTOCOL_TCP, tcp(999, 0x02, &[]))),
+            frame(
+                3,
+                LINKTYPE_RAW,
+                ipv4(PROTOCOL_TCP, tcp(1003, 0x18, &[6, 7, 0])),
+            ),
+            frame(
+                4,
+                LINKTYPE_RAW,
+                ipv4(PROTOCOL_TCP, tcp(1000, 0x18, &[0, 3, 5])),
+            ),
+            frame(
+                5,
+                LINKTYPE_RAW,
+                ipv4(PROTOCOL_TCP, tcp(1003, 0x18, &[6, 7, 0])),
+            ),
+            frame(
+                6,
+                LINKTYPE_RAW,
+                ipv4(PROTOCOL_TCP, tcp(1006, 0x18, &[2, 8, 9])),
+            ),
+        ];
+        let client: SocketAddr = "192.0.2.1:40000".parse().unwrap();
+        let server: SocketAddr = "192.0.2.53:53".parse().unwrap();
+        let payload = |time, transport, data| Payload {
+            time: Duration::from_secs(time),
+            transport,
+            source: client,
+            destination: server,
+            data,
+        };
+        assert_eq!(
+            vec![
+                payload(1, Transport::Udp, vec![1, 2, 3]),
+                payload(4, Transport::Tcp, vec![5, 6, 7]),
+                payload(6, Transport::Tcp, vec![8, 9]),
+            ],
+            payloads(&frames)
+        );
+    }
+}
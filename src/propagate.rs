@@ -0,0 +1,468 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use ratatui::{prelude::*, widgets::*};
+use tokio::task::JoinSet;
+
+use crate::client::{ask, parse_server, resolve_server};
+use crate::dns::header::ResponseCode;
+use crate::dns::message::Message;
+use crate::dns::record::Record;
+use crate::dns::QType;
+use crate::layout::Grid;
+use crate::watch::shows;
+use crate::{BORDER_SIZE, VERSION};
+
+// well known public resolvers that are asked unless --no-public is given
+const PUBLIC_RESOLVERS: [(&str, &str); 14] = [
+    ("Cloudflare", "1.1.1.1"),
+    ("Cloudflare", "1.0.0.1"),
+    ("Google", "8.8.8.8"),
+    ("Google", "8.8.4.4"),
+    ("Quad9", "9.9.9.9"),
+    ("Quad9", "149.112.112.112"),
+    ("OpenDNS", "208.67.222.222"),
+    ("OpenDNS", "208.67.220.220"),
+    ("AdGuard", "94.140.14.14"),
+    ("CleanBrowsing", "185.228.168.9"),
+    ("Comodo", "8.26.56.26"),
+    ("Level3", "4.2.2.1"),
+    ("DNS.WATCH", "84.200.69.80"),
+    ("Yandex", "77.88.8.8"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolver {
+    pub name: String,
+    pub server: String,
+}
+
+impl Resolver {
+    pub fn public() -> Vec<Resolver> {
+        PUBLIC_RESOLVERS
+            .iter()
+            .map(|(name, address)| Resolver {
+                name: name.to_string(),
+                server: parse_server(address).expect("valid public resolver"),
+            })
+            .collect()
+    }
+}
+
+// Reads a list of resolvers, one `address [name]` per line, ex. `192.0.2.53:5353 office`. Empty
+// lines and everything after a # are skipped, a resolver without a name goes by its address.
+pub fn parse_resolvers(text: &str) -> Result<Vec<Resolver>> {
+    let mut resolvers = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(address) = tokens.next() else {
+            continue;
+        };
+        let server = parse_server(address).map_err(|e| anyhow!("line {}: {}", n + 1, e))?;
+        let name = tokens.collect::<Vec<_>>().join(" ");
+        resolvers.push(Resolver {
+            name: if name.is_empty() {
+                server.clone()
+            } else {
+                name
+            },
+            server,
+        });
+    }
+    Ok(resolvers)
+}
+
+// what a resolver answered, or why it did not
+struct Outcome {
+    resolver: Resolver,
+    answer: Result<(Message, Duration), String>,
+}
+
+impl Outcome {
+    // the answer the way resolvers are compared: the data of the records sorted, without the
+    // ttl that every resolver counts down on its own. Answers without records go by their code.
+    fn lines(&self, qtype: &QType) -> Option<Vec<String>> {
+        let (message, _) = self.answer.as_ref().ok()?;
+        if message.records.is_empty() {
+            let code = match message.header.r_code {
+                ResponseCode::NoError => "NoError (no records)".to_owned(),
+                ref code => code.to_string(),
+            };
+            return Some(vec![code]);
+        }
+        let mut lines = message
+            .records
+            .iter()
+            .map(|r| {
+                if r.qtype == *qtype {
+                    format!("{:#}", r.rdata)
+                } else {
+                    format!("{} {:#}", r.qtype, r.rdata)
+                }
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        Some(lines)
+    }
+
+    fn ttl(&self) -> Option<u64> {
+        let (message, _) = self.answer.as_ref().ok()?;
+        message.records.iter().map(|r| r.ttl.as_secs()).min()
+    }
+
+    fn records(&self) -> &[Record] {
+        match &self.answer {
+            Ok((message, _)) => &message.records,
+            Err(_) => &[],
+        }
+    }
+}
+
+// The outcomes side by side: every distinct answer once, the most common first, and which of
+// them every resolver gave.
+struct Matrix {
+    qtype: QType,
+    outcomes: Vec<Outcome>,
+    answers: Vec<(Vec<String>, usize)>,
+    expect: Option<String>,
+}
+
+impl Matrix {
+    fn new(qtype: QType, outcomes: Vec<Outcome>, expect: Option<String>) -> Matrix {
+        let mut answers: Vec<(Vec<String>, usize)> = Vec::new();
+        for lines in outcomes.iter().filter_map(|o| o.lines(&qtype)) {
+            match answers.iter_mut().find(|(a, _)| *a == lines) {
+                Some((_, count)) => *count += 1,
+                None => answers.push((lines, 1)),
+            }
+        }
+        // a stable sort keeps the order resolvers were listed in for answers as common
+        answers.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        Matrix {
+            qtype,
+            outcomes,
+            answers,
+            expect,
+        }
+    }
+
+    // the column of the answer a resolver gave
+    fn answer_of(&self, outcome: &Outcome) -> Option<usize> {
+        let lines = outcome.lines(&self.qtype)?;
+        self.answers.iter().position(|(a, _)| *a == lines)
+    }
+
+    // whether a resolver sees what it should, the expected value when there is one and the
+    // answer most resolvers agree on otherwise
+    fn converged(&self, outcome: &Outcome) -> bool {
+        match &self.expect {
+            Some(value) => outcome.records().iter().any(|r| shows(r, value)),
+            None => self.answer_of(outcome) == Some(0),
+        }
+    }
+
+    fn summary(&self) -> String {
+        let total = self.outcomes.len();
+        let converged = self.outcomes.iter().filter(|o| self.converged(o)).count();
+        let percent = (converged * 100).checked_div(total).unwrap_or(0);
+        let on = match &self.expect {
+            Some(value) => format!("hold {}", value),
+            None => "agree with #1".to_owned(),
+        };
+        format!(
+            "converged: {} of {} resolvers ({}%) {}",
+            converged, total, percent, on
+        )
+    }
+
+    fn answer_cells(&self) -> Vec<Vec<String>> {
+        self.answers
+            .iter()
+            .enumerate()
+            .map(|(i, (lines, count))| {
+                vec![
+                    format!("#{}", i + 1),
+                    format!("{} of {}", count, self.outcomes.len()),
+                    lines.join("\n"),
+                ]
+            })
+            .collect()
+    }
+
+    // a row for every resolver with the ttl it has left in the column of its answer
+    fn resolver_cells(&self) -> Vec<Vec<String>> {
+        let mut header = vec!["Resolver".to_owned(), "Server".to_owned()];
+        header.extend((1..=self.answers.len()).map(|i| format!("#{}", i)));
+        header.push("Time".to_owned());
+
+        let rows = self.outcomes.iter().map(|outcome| {
+            let mut cells = vec![
+                outcome.resolver.name.clone(),
+                outcome.resolver.server.clone(),
+            ];
+            let column = self.answer_of(outcome);
+            cells.extend(
+                (0..self.answers.len()).map(|i| match (column, outcome.ttl()) {
+                    (Some(c), Some(ttl)) if c == i => ttl.to_string(),
+                    (Some(c), None) if c == i => "-".to_owned(),
+                    _ => String::new(),
+                }),
+            );
+            cells.push(match &outcome.answer {
+                Ok((_, elapsed)) => format!("{} msec", elapsed.as_millis()),
+                Err(error) => error.clone(),
+            });
+            cells
+        });
+        std::iter::once(header).chain(rows).collect()
+    }
+
+    fn color(&self, outcome: &Outcome) -> Color {
+        if outcome.answer.is_err() {
+            Color::Red
+        } else if self.converged(outcome) {
+            Color::Green
+        } else {
+            Color::Yellow
+        }
+    }
+}
+
+// asks every resolver at the same time, the outcomes come back in the order of the resolvers
+async fn ask_all(resolvers: Vec<Resolver>, query: &Message) -> Result<Vec<Outcome>> {
+    let mut tasks = JoinSet::new();
+    for (i, resolver) in resolvers.iter().enumerate() {
+        let mut request = query.clone();
+        request.header.id = rand::random();
+        let server = resolver.server.clone();
+        tasks.spawn(async move {
+            let answer = match resolve_server(&server).await {
+                Ok(server) => ask(&request, server).await,
+                Err(error) => Err(error),
+            };
+            (i, answer)
+        });
+    }
+
+    let mut answers = Vec::with_capacity(resolvers.len());
+    while let Some(joined) = tasks.join_next().await {
+        answers.push(joined?);
+    }
+    answers.sort_by_key(|(i, _)| *i);
+    Ok(resolvers
+        .into_iter()
+        .zip(answers)
+        .map(|(resolver, (_, answer))| Outcome {
+            resolver,
+            answer: answer.map_err(|e| format!("{:#}", e)),
+        })
+        .collect())
+}
+
+fn render(frame: &mut Frame, matrix: &Matrix, answers: &Grid, resolvers: &Grid, title: &str) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Length(1),
+            Constraint::Length(answers.height() + BORDER_SIZE),
+            Constraint::Length(resolvers.height() + BORDER_SIZE),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    frame.render_widget(Paragraph::new(title.to_owned()).fg(Color::White), rows[0]);
+
+    frame.render_widget(
+        Table::new(answers.rows())
+            .block(
+                Block::new()
+                    .title("Answers")
+                    .borders(Borders::ALL)
+                    .fg(Color::Green),
+            )
+            .widths(answers.widths()),
+        rows[1],
+    );
+
+    let mut table_rows = resolvers.rows().into_iter();
+    let header = table_rows
+        .next()
+        .map(|row| row.add_modifier(Modifier::BOLD));
+    let table_rows = table_rows
+        .zip(&matrix.outcomes)
+        .map(|(row, outcome)| row.fg(matrix.color(outcome)));
+    let mut table = Table::new(table_rows)
+        .block(
+            Block::new()
+                .title("Resolvers")
+                .borders(Borders::ALL)
+                .fg(Color::Green),
+        )
+        .widths(resolvers.widths());
+    if let Some(header) = header {
+        table = table.header(header);
+    }
+    frame.render_widget(table, rows[2]);
+
+    frame.render_widget(Paragraph::new(matrix.summary()).fg(Color::White), rows[3]);
+}
+
+// Asks every resolver for the query and shows which of them see which answer. The matrix goes
+// to the terminal, or as tab separated lines with `raw`.
+pub async fn run(
+    query: &Message,
+    resolvers: Vec<Resolver>,
+    expect: Option<String>,
+    raw: bool,
+    idn: bool,
+) -> Result<()> {
+    let question = query.question().ok_or_else(|| anyhow!("nothing to ask"))?;
+    let qtype = question.qtype.clone();
+    let title = format!(
+        "== Who are you? == v{} == propagation of {} {} ==",
+        VERSION,
+        crate::display_name(&question.qname, idn),
+        qtype
+    );
+    let outcomes = ask_all(resolvers, query).await?;
+    let matrix = Matrix::new(qtype, outcomes, expect);
+
+    if raw {
+        for (i, (lines, count)) in matrix.answers.iter().enumerate() {
+            println!(
+                "; #{} {} of {}: {}",
+                i + 1,
+                count,
+                matrix.outcomes.len(),
+                lines.join(", ")
+            );
+        }
+        for outcome in &matrix.outcomes {
+            let answer = matrix
+                .answer_of(outcome)
+                .map_or("-".to_owned(), |i| format!("#{}", i + 1));
+            let ttl = outcome.ttl().map_or("-".to_owned(), |t| t.to_string());
+            let time = match &outcome.answer {
+                Ok((_, elapsed)) => format!("{} msec", elapsed.as_millis()),
+                Err(error) => error.clone(),
+            };
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                outcome.resolver.name, outcome.resolver.server, answer, ttl, time
+            );
+        }
+        println!("; {}", matrix.summary());
+        return Ok(());
+    }
+
+    let (width, _) = crossterm::terminal::size().context("unable to get the terminal size")?;
+    let answers = Grid::new(
+        matrix.answer_cells(),
+        width.saturating_sub(BORDER_SIZE),
+        &[1],
+    );
+    let resolvers = Grid::new(
+        matrix.resolver_cells(),
+        width.saturating_sub(BORDER_SIZE),
+        &[1],
+    );
+    let height = 1 + answers.height() + BORDER_SIZE + resolvers.height() + BORDER_SIZE + 1;
+    let mut terminal = crate::setup_terminal(height).context("setup failed")?;
+    terminal.draw(|f| render(f, &matrix, &answers, &resolvers, &title))?;
+    crossterm::terminal::disable_raw_mode().context("failed to disable raw mode")?;
+    let _ = terminal.show_cursor().context("unable to show cursor");
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::record::RData;
+    use crate::dns::QClass;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn read_resolver_file() {
+        let text = "# office resolvers\n192.0.2.53 office\n\n[2001:db8::53]:5353 # lab\n";
+        let expected = vec![
+            Resolver {
+                name: "office".to_owned(),
+                server: "192.0.2.53:53".to_owned(),
+            },
+            Resolver {
+                name: "[2001:db8::53]:5353".to_owned(),
+                server: "[2001:db8::53]:5353".to_owned(),
+            },
+        ];
+        assert_eq!(expected, parse_resolvers(text).unwrap());
+        assert!(parse_resolvers("@\n").is_err());
+        assert_eq!(14, Resolver::public().len());
+    }
+
+    fn outcome(name: &str, addresses: &[[u8; 4]], ttl: u64) -> Outcome {
+        let mut message = Message::query("example.com".parse().unwrap(), QType::A, QClass::IN);
+        message.records = addresses
+            .iter()
+            .map(|a| {
+                Record::new(
+                    "example.com".parse().unwrap(),
+                    QType::A,
+                    QClass::IN,
+                    Duration::from_secs(ttl),
+                    4,
+                    RData::A(Ipv4Addr::from(*a)),
+                )
+            })
+            .collect();
+        Outcome {
+            resolver: Resolver {
+                name: name.to_owned(),
+                server: "192.0.2.53:53".to_owned(),
+            },
+            answer: Ok((message, Duration::from_millis(3))),
+        }
+    }
+
+    #[test]
+    fn group_answers_and_count_the_converged() {
+        let outcomes = vec![
+            outcome("old", &[[192, 0, 2, 1]], 3000),
+            outcome("new", &[[192, 0, 2, 2], [192, 0, 2, 9]], 300),
+            outcome("newer", &[[192, 0, 2, 9], [192, 0, 2, 2]], 120),
+            Outcome {
+                resolver: Resolver {
+                    name: "down".to_owned(),
+                    server: "192.0.2.54:53".to_owned(),
+                },
+                answer: Err("no response".to_owned()),
+            },
+        ];
+        let matrix = Matrix::new(QType::A, outcomes, None);
+        assert_eq!(
+            vec![
+                (vec!["192.0.2.2".to_owned(), "192.0.2.9".to_owned()], 2),
+                (vec!["192.0.2.1".to_owned()], 1),
+            ],
+            matrix.answers
+        );
+        assert_eq!(
+            ["old", "192.0.2.53:53", "", "3000", "3 msec"]
+                .map(String::from)
+                .to_vec(),
+            matrix.resolver_cells()[1]
+        );
+        assert_eq!(
+            "converged: 2 of 4 resolvers (50%) agree with #1",
+            matrix.summary()
+        );
+
+        let matrix = Matrix::new(QType::A, matrix.outcomes, Some("192.0.2.1".to_owned()));
+        assert_eq!(
+            "converged: 1 of 4 resolvers (25%) hold 192.0.2.1",
+            matrix.summary()
+        );
+    }
+}
@@ -0,0 +1,505 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+use crate::client::MAX_UDP_SIZE;
+use crate::dns::edns::{self, Edns};
+use crate::dns::header::{Opcode, ResponseCode};
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::record::{RData, Record};
+use crate::dns::zone::Zone;
+use crate::dns::{QType, Serialize};
+
+// what fits in a udp response when the request has no OPT record (rfc 1035 4.2.1)
+const PLAIN_UDP_SIZE: usize = 512;
+// how many CNAMEs are followed within a zone before giving up on a loop
+const MAX_CHAIN: usize = 8;
+// how long a tcp connection may sit idle between requests
+const TCP_IDLE: Duration = Duration::from_secs(10);
+
+// whether a name is the ancestor or the ancestor itself
+fn within(name: &Name, ancestor: &Name) -> bool {
+    name.to_lowercase()
+        .labels()
+        .ends_with(ancestor.to_lowercase().labels())
+}
+
+// the name with its first `n` labels taken off
+fn parent(name: &Name, n: usize) -> Name {
+    Name::from_labels(name.labels()[n..].to_vec()).unwrap_or_default()
+}
+
+// A zone that is served, with its SOA at hand for negative answers.
+struct Served {
+    zone: Zone,
+    soa: Record,
+}
+
+impl Served {
+    fn node(&self, name: &Name) -> Vec<Record> {
+        self.zone
+            .records
+            .iter()
+            .filter(|r| &r.name == name)
+            .cloned()
+            .collect()
+    }
+
+    // a name exists when it has records or names below it do, empty non-terminals exist too
+    fn exists(&self, name: &Name) -> bool {
+        self.zone.records.iter().any(|r| within(&r.name, name))
+    }
+
+    // the NS records of the closest zone cut between the origin and the name, if there is one
+    fn delegation(&self, name: &Name) -> Option<Vec<Record>> {
+        let depth = name.labels().len() - self.zone.origin.labels().len();
+        (0..depth).rev().find_map(|n| {
+            let records = self.node(&parent(name, n));
+            let ns = records
+                .into_iter()
+                .filter(|r| r.qtype == QType::NS)
+                .collect::<Vec<_>>();
+            (!ns.is_empty()).then_some(ns)
+        })
+    }
+
+    // The records at a name, or those of the wildcard at its closest encloser with the name as
+    // their owner (rfc 4592). Empty when the name does not exist.
+    fn lookup(&self, name: &Name) -> Vec<Record> {
+        let records = self.node(name);
+        if !records.is_empty() || self.exists(name) {
+            return records;
+        }
+        let depth = name.labels().len() - self.zone.origin.labels().len();
+        let Some(encloser) = (1..=depth)
+            .map(|n| parent(name, n))
+            .find(|a| self.exists(a))
+        else {
+            return Vec::new();
+        };
+        let mut wildcard = vec![b"*".to_vec()];
+        wildcard.extend(encloser.labels().iter().cloned());
+        let Ok(wildcard) = Name::from_labels(wildcard) else {
+            return Vec::new();
+        };
+        self.node(&wildcard)
+            .into_iter()
+            .map(|mut r| {
+                r.name = name.clone();
+                r
+            })
+            .collect()
+    }
+
+    // the SOA for the authority section of a negative answer, its ttl is how long the answer may
+    // be cached (rfc 2308 3)
+    fn negative(&self) -> Record {
+        let mut soa = self.soa.clone();
+        if let RData::SOA { minimum, .. } = soa.rdata {
+            soa.ttl = soa.ttl.min(Duration::from_secs(minimum.into()));
+        }
+        soa
+    }
+
+    fn apex_ns(&self) -> Vec<Record> {
+        self.node(&self.zone.origin)
+            .into_iter()
+            .filter(|r| r.qtype == QType::NS)
+            .collect()
+    }
+
+    // the addresses of the name servers, mail exchangers and services in the response that are
+    // in the zone, the glue of a referral among them
+    fn additionals(&self, response: &Message) -> Vec<Record> {
+        let mut additionals: Vec<Record> = Vec::new();
+        for record in response.records.iter().chain(&response.authorities) {
+            if !matches!(record.qtype, QType::NS | QType::MX | QType::SRV) {
+                continue;
+            }
+            let Some(target) = record.rdata.target() else {
+                continue;
+            };
+            for address in self.node(target) {
+                let new = !additionals.iter().any(|a| a == &address);
+                if matches!(address.qtype, QType::A | QType::AAAA) && new {
+                    additionals.push(address);
+                }
+            }
+        }
+        additionals
+    }
+
+    // Answers a question in the zone (rfc 1034 4.3.2): a referral below a zone cut, the records
+    // of the name following CNAMEs within the zone, or NODATA and NXDOMAIN with the SOA.
+    fn answer(&self, name: &Name, qtype: &QType, response: &mut Message) {
+        let mut name = name.clone();
+        for _ in 0..MAX_CHAIN {
+            if let Some(ns) = self.delegation(&name) {
+                response.header.aa = !response.records.is_empty();
+                response.authorities = ns;
+                response.additionals = self.additionals(response);
+                return;
+            }
+            let records = self.lookup(&name);
+            if records.is_empty() && !self.exists(&name) {
+                response.header.r_code = ResponseCode::NameError;
+                response.authorities = vec![self.negative()];
+                return;
+            }
+            let matching = records
+                .iter()
+                .filter(|r| qtype == &QType::STAR || &r.qtype == qtype)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !matching.is_empty() {
+                response.records.extend(matching);
+                if !(qtype == &QType::NS && name == self.zone.origin) {
+                    response.authorities = self.apex_ns();
+                }
+                response.additionals = self.additionals(response);
+                return;
+            }
+            let Some(cname) = records.into_iter().find(|r| r.qtype == QType::CNAME) else {
+                response.authorities = vec![self.negative()];
+                return;
+            };
+            let target = cname.rdata.target().cloned().unwrap_or_default();
+            response.records.push(cname);
+            if !within(&target, &self.zone.origin) {
+                return;
+            }
+            name = target;
+        }
+    }
+}
+
+// The zones a server answers for.
+pub struct Authority {
+    zones: Vec<Served>,
+}
+
+impl Authority {
+    // every zone needs exactly one SOA, at its origin
+    pub fn new(zones: Vec<Zone>) -> Result<Authority> {
+        let mut served = Vec::new();
+        for zone in zones {
+            let soas = zone
+                .records
+                .iter()
+                .filter(|r| r.qtype == QType::SOA)
+                .collect::<Vec<_>>();
+            let soa = match soas[..] {
+                [soa] if soa.name == zone.origin => soa.clone(),
+                [] => return Err(anyhow!("zone {} has no SOA record", zone.origin)),
+                _ => {
+                    return Err(anyhow!(
+                        "zone {} needs exactly one SOA record, at its origin",
+                        zone.origin
+                    ))
+                }
+            };
+            if let Some(outside) = zone.records.iter().find(|r| !within(&r.name, &zone.origin)) {
+                return Err(anyhow!("{} is not in zone {}", outside.name, zone.origin));
+            }
+            served.push(Served { zone, soa });
+        }
+        Ok(Authority { zones: served })
+    }
+
+    // the zone closest to the name, the one with the longest origin the name is in
+    fn zone(&self, name: &Name) -> Option<&Served> {
+        self.zones
+            .iter()
+            .filter(|s| within(name, &s.zone.origin))
+            .max_by_key(|s| s.zone.origin.labels().len())
+    }
+
+    // The response to a request. Only standard queries with a single question for a name in one
+    // of the zones are answered, transfers are refused.
+    pub fn respond(&self, request: &Message) -> Message {
+        let mut response = request.reply(ResponseCode::NoError);
+        if request.header.opcode != Opcode::Query {
+            response.header.r_code = ResponseCode::NotImplemented;
+            return response;
+        }
+        let question = match &request.questions[..] {
+            [question] => question,
+            _ => {
+                response.header.r_code = ResponseCode::FormatError;
+                return response;
+            }
+        };
+        let zone = self.zone(&question.qname);
+        match (zone, &question.qtype) {
+            (_, QType::AXFR | QType::IXFR) | (None, _) => {
+                response.header.r_code = ResponseCode::Refused;
+            }
+            (Some(zone), qtype) => {
+                response.header.aa = true;
+                zone.answer(&question.qname, qtype, &mut response);
+            }
+        }
+        if request.edns().is_some() {
+            response
+                .additionals
+                .push(Edns::new(edns::DEFAULT_PAYLOAD).record());
+        }
+        response
+    }
+
+    // The response on the wire, truncated to what the client can take over udp: the records
+    // are left out and the TC bit tells the client to ask again over tcp.
+    fn octets(&self, request: &Message, limit: Option<usize>) -> Result<Vec<u8>> {
+        let mut response = self.respond(request);
+        let octets = response.serialize()?;
+        let Some(limit) = limit else {
+            return Ok(octets);
+        };
+        if octets.len() <= limit {
+            return Ok(octets);
+        }
+        response.header.tc = true;
+        response.records.clear();
+        response.authorities.clear();
+        response.additionals.retain(|r| r.qtype == QType::OPT);
+        response.serialize()
+    }
+}
+
+// a line for every request answered: when, who asked, the question and the response code
+fn log(from: SocketAddr, transport: &str, request: &Message, octets: &[u8]) {
+    let question = request.question().map_or("no question".to_owned(), |q| {
+        format!("{} {}", q.qname, q.qtype)
+    });
+    let status = Message::try_from(octets).map_or("unreadable".to_owned(), |m| {
+        format!(
+            "{}{}{}",
+            m.header.r_code,
+            if m.header.aa { " aa" } else { "" },
+            if m.header.tc { " tc" } else { "" }
+        )
+    });
+    println!(
+        "; {} {} {} {} -> {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        from,
+        transport,
+        question,
+        status
+    );
+}
+
+async fn serve_udp(socket: UdpSocket, authority: Arc<Authority>) -> Result<()> {
+    let mut buffer = vec![0; MAX_UDP_SIZE];
+    loop {
+        // an icmp error for an earlier response can show up here, it is no reason to stop
+        let (length, from) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                eprintln!("; {}", e);
+                continue;
+            }
+        };
+        let Ok(request) = Message::try_from(&buffer[..length]) else {
+            eprintln!("; dropped malformed message from {}", from);
+            continue;
+        };
+        if request.header.qr {
+            continue;
+        }
+        let limit = request
+            .edns()
+            .map_or(PLAIN_UDP_SIZE, |e| (e.payload as usize).max(PLAIN_UDP_SIZE));
+        let octets = match authority.octets(&request, Some(limit)) {
+            Ok(octets) => octets,
+            Err(e) => {
+                eprintln!("; could not answer {}: {}", from, e);
+                continue;
+            }
+        };
+        log(from, "udp", &request, &octets);
+        if let Err(e) = socket.send_to(&octets, from).await {
+            eprintln!("; could not answer {}: {}", from, e);
+        }
+    }
+}
+
+// answers the requests on a connection until the client closes it or leaves it idle
+async fn serve_connection(
+    mut stream: TcpStream,
+    from: SocketAddr,
+    authority: Arc<Authority>,
+) -> Result<()> {
+    loop {
+        let Ok(length) = timeout(TCP_IDLE, stream.read_u16()).await else {
+            return Ok(());
+        };
+        let Ok(length) = length else {
+            return Ok(());
+        };
+        let mut buffer = vec![0; length as usize];
+        timeout(TCP_IDLE, stream.read_exact(&mut buffer)).await??;
+        let Ok(request) = Message::try_from(buffer.as_slice()) else {
+            return Err(anyhow!("malformed message from {}", from));
+        };
+        let octets = authority.octets(&request, None)?;
+        log(from, "tcp", &request, &octets);
+        let length: u16 = octets
+            .len()
+            .try_into()
+            .context("response too large for tcp")?;
+        stream.write_all(&length.to_be_bytes()).await?;
+        stream.write_all(&octets).await?;
+    }
+}
+
+async fn serve_tcp(listener: TcpListener, authority: Arc<Authority>) -> Result<()> {
+    loop {
+        let (stream, from) = listener.accept().await?;
+        let authority = authority.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, from, authority).await {
+                eprintln!("; {}", e);
+            }
+        });
+    }
+}
+
+// Answers for the zones over udp and tcp on the same address until it is stopped.
+pub async fn run(zones: Vec<Zone>, listen: SocketAddr) -> Result<()> {
+    let authority = Arc::new(Authority::new(zones)?);
+    let socket = UdpSocket::bind(listen)
+        .await
+        .with_context(|| format!("could not listen on {}", listen))?;
+    // with port 0 tcp takes the port udp was given
+    let address = socket.local_addr()?;
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("could not listen on {} over tcp", address))?;
+    let origins = authority
+        .zones
+        .iter()
+        .map(|s| s.zone.origin.to_string())
+        .collect::<Vec<_>>();
+    eprintln!("; serving {} on {}", origins.join(", "), address);
+
+    tokio::select! {
+        result = serve_udp(socket, authority.clone()) => result,
+        result = serve_tcp(listener, authority) => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::zone;
+    use crate::dns::QClass;
+    use pretty_assertions::assert_eq;
+
+    fn sections(authority: &Authority, name: &str, qtype: QType) -> (String, Vec<Vec<String>>) {
+        let request = Message::query(name.parse().unwrap(), qtype, QClass::IN);
+        let response = authority.respond(&request);
+        let lines = |records: &[Record]| {
+            records
+                .iter()
+                .map(|r| format!("{} {} {}", r.name, r.qtype, r.rdata))
+                .collect::<Vec<_>>()
+        };
+        (
+            format!(
+                "{}{}",
+                response.header.r_code,
+                if response.header.aa { " aa" } else { "" }
+            ),
+            vec![
+                lines(&response.records),
+                lines(&response.authorities),
+                lines(&response.additionals),
+            ],
+        )
+    }
+
+    #[test]
+    fn answer_refer_and_deny() {
+        let zone = zone::parse(
+            "$ORIGIN example.com.
+$TTL 3600
+@       SOA ns1 hostmaster 1 3600 600 86400 300
+        NS  ns1
+        MX  10 mail
+ns1     A   192.0.2.1
+mail    A   192.0.2.25
+www     CNAME web.lab
+web.lab A   192.0.2.80
+*.apps  TXT \"wildcard\"
+sub     NS  ns.sub
+ns.sub  A   192.0.2.53
+",
+            None,
+        )
+        .unwrap();
+        let authority = Authority::new(vec![zone]).unwrap();
+        let soa =
+            "example.com SOA ns1.example.com, hostmaster.example.com, 1, 3600, 600, 86400, 300";
+
+        assert_eq!(
+            (
+                "NoError aa".to_owned(),
+                vec![
+                    vec!["example.com MX 10 mail.example.com".to_owned()],
+                    vec!["example.com NS ns1.example.com".to_owned()],
+                    vec![
+                        "mail.example.com A 192.0.2.25".to_owned(),
+                        "ns1.example.com A 192.0.2.1".to_owned(),
+                    ],
+                ]
+            ),
+            sections(&authority, "example.com", QType::MX)
+        );
+        assert_eq!(
+            vec![
+                "www.example.com CNAME web.lab.example.com".to_owned(),
+                "web.lab.example.com A 192.0.2.80".to_owned(),
+            ],
+            sections(&authority, "www.example.com", QType::A).1[0]
+        );
+        assert_eq!(
+            (
+                "NameError aa".to_owned(),
+                vec![vec![], vec![soa.to_owned()], vec![]]
+            ),
+            sections(&authority, "nope.example.com", QType::A)
+        );
+        // lab exists as an empty non-terminal
+        assert_eq!(
+            (
+                "NoError aa".to_owned(),
+                vec![vec![], vec![soa.to_owned()], vec![]]
+            ),
+            sections(&authority, "lab.example.com", QType::A)
+        );
+        assert_eq!(
+            vec![r#"x.apps.example.com TXT "wildcard""#.to_owned()],
+            sections(&authority, "x.apps.example.com", QType::TXT).1[0]
+        );
+        assert_eq!(
+            (
+                "NoError".to_owned(),
+                vec![
+                    vec![],
+                    vec!["sub.example.com NS ns.sub.example.com".to_owned()],
+                    vec!["ns.sub.example.com A 192.0.2.53".to_owned()],
+                ]
+            ),
+            sections(&authority, "deep.sub.example.com", QType::A)
+        );
+        assert_eq!("Refused", sections(&authority, "example.net", QType::A).0);
+    }
+}
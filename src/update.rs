@@ -0,0 +1,541 @@
+use std::net::SocketAddr;
+use std::str;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::client::{exchange, parse_server};
+use crate::dns::header::{Opcode, ResponseCode};
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::presentation::{parse_rdata, tokenize};
+use crate::dns::record::{RData, Record};
+use crate::dns::tsig::{self, TsigKey, Verifier};
+use crate::dns::{QClass, QType, Serialize};
+
+// A condition the zone has to meet before the server applies any of the updates (rfc 2136 2.4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prerequisite {
+    // the name has no records at all
+    NxDomain(Name),
+    // the name has at least one record
+    YxDomain(Name),
+    // the name has no records of this type
+    NxRRSet(Name, QType),
+    // the name has records of this type, and exactly these when rdata is given
+    YxRRSet(Name, QType, Option<RData>),
+}
+
+// A change to the zone (rfc 2136 2.5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Add {
+        name: Name,
+        ttl: u32,
+        qtype: QType,
+        rdata: RData,
+    },
+    // removes every record of the name
+    DeleteName(Name),
+    // removes every record of this type from the name
+    DeleteRRSet(Name, QType),
+    // removes a single record
+    DeleteRR(Name, QType, RData),
+}
+
+// A single UPDATE message, the prerequisites and changes of one zone that the server applies
+// all at once or not at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub zone: Name,
+    pub class: QClass,
+    pub prerequisites: Vec<Prerequisite>,
+    pub changes: Vec<Change>,
+}
+
+fn record(name: &Name, qtype: QType, qclass: QClass, ttl: u32, rdata: RData) -> Record {
+    Record::new(
+        name.clone(),
+        qtype,
+        qclass,
+        Duration::from_secs(ttl.into()),
+        0,
+        rdata,
+    )
+}
+
+impl Update {
+    pub fn new(zone: Name, class: QClass) -> Update {
+        Update {
+            zone,
+            class,
+            prerequisites: Vec::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    // Prerequisites go into the answer section and changes into the authority section. What a
+    // record means is told by its class: the zone class carries data, ANY matches a whole RRset
+    // or name and NONE asks for absence, or in the update section removes a single record.
+    pub fn message(&self) -> Message {
+        let mut message = Message::update(self.zone.clone(), self.class.clone());
+        for prerequisite in &self.prerequisites {
+            message.records.push(match prerequisite {
+                Prerequisite::NxDomain(name) => {
+                    record(name, QType::STAR, QClass::NONE, 0, RData::Empty)
+                }
+                Prerequisite::YxDomain(name) => {
+                    record(name, QType::STAR, QClass::ANY, 0, RData::Empty)
+                }
+                Prerequisite::NxRRSet(name, qtype) => {
+                    record(name, qtype.clone(), QClass::NONE, 0, RData::Empty)
+                }
+                Prerequisite::YxRRSet(name, qtype, None) => {
+                    record(name, qtype.clone(), QClass::ANY, 0, RData::Empty)
+                }
+                Prerequisite::YxRRSet(name, qtype, Some(rdata)) => {
+                    record(name, qtype.clone(), self.class.clone(), 0, rdata.clone())
+                }
+            });
+        }
+        for change in &self.changes {
+            message.authorities.push(match change {
+                Change::Add {
+                    name,
+                    ttl,
+                    qtype,
+                    rdata,
+                } => record(name, qtype.clone(), self.class.clone(), *ttl, rdata.clone()),
+                Change::DeleteName(name) => record(name, QType::STAR, QClass::ANY, 0, RData::Empty),
+                Change::DeleteRRSet(name, qtype) => {
+                    record(name, qtype.clone(), QClass::ANY, 0, RData::Empty)
+                }
+                Change::DeleteRR(name, qtype, rdata) => {
+                    record(name, qtype.clone(), QClass::NONE, 0, rdata.clone())
+                }
+            });
+        }
+        message
+    }
+
+    fn is_empty(&self) -> bool {
+        self.prerequisites.is_empty() && self.changes.is_empty()
+    }
+
+    // reads the arguments of a prerequisite: `nxdomain|yxdomain <name>`,
+    // `nxrrset <name> [class] <type>` or `yxrrset <name> [class] <type> [data...]`
+    pub fn prerequisite(&mut self, line: &str) -> Result<()> {
+        let tokens = tokenize(line)?;
+        let mut fields = Fields::new(&tokens);
+        let kind = fields.str("prerequisite")?.to_ascii_lowercase();
+        let name = fields.name()?;
+        let prerequisite = match kind.as_str() {
+            "nxdomain" => Prerequisite::NxDomain(name),
+            "yxdomain" => Prerequisite::YxDomain(name),
+            "nxrrset" | "yxrrset" => {
+                fields.class(&self.class)?;
+                let qtype = fields.qtype()?;
+                match (kind.as_str(), fields.rest()) {
+                    ("nxrrset", []) => Prerequisite::NxRRSet(name, qtype),
+                    ("nxrrset", _) => return Err(anyhow!("nxrrset takes no record data")),
+                    (_, []) => Prerequisite::YxRRSet(name, qtype, None),
+                    (_, data) => {
+                        let rdata = parse_rdata(&qtype, data)?;
+                        Prerequisite::YxRRSet(name, qtype, Some(rdata))
+                    }
+                }
+            }
+            _ => return Err(anyhow!("unknown prerequisite: {}", kind)),
+        };
+        fields.done()?;
+        self.prerequisites.push(prerequisite);
+        Ok(())
+    }
+
+    // reads a record to add: `<name> [ttl] [class] <type> <data...>`, without a ttl the default
+    // one is used
+    pub fn add(&mut self, line: &str, default_ttl: Option<u32>) -> Result<()> {
+        let tokens = tokenize(line)?;
+        let mut fields = Fields::new(&tokens);
+        let name = fields.name()?;
+        let ttl = match fields.ttl() {
+            Some(ttl) => ttl,
+            None => default_ttl.ok_or_else(|| anyhow!("no ttl given for: {}", line))?,
+        };
+        fields.class(&self.class)?;
+        let qtype = fields.qtype()?;
+        let rdata = parse_rdata(&qtype, fields.rest())?;
+        self.changes.push(Change::Add {
+            name,
+            ttl,
+            qtype,
+            rdata,
+        });
+        Ok(())
+    }
+
+    // reads what to delete: `<name> [class] [type [data...]]`, the whole name, a RRset or a
+    // single record
+    pub fn delete(&mut self, line: &str) -> Result<()> {
+        let tokens = tokenize(line)?;
+        let mut fields = Fields::new(&tokens);
+        let name = fields.name()?;
+        // nsupdate accepts a ttl here and ignores it, so do we
+        fields.ttl();
+        fields.class(&self.class)?;
+        let change = if fields.is_empty() {
+            Change::DeleteName(name)
+        } else {
+            let qtype = fields.qtype()?;
+            match fields.rest() {
+                [] => Change::DeleteRRSet(name, qtype),
+                data => {
+                    let rdata = parse_rdata(&qtype, data)?;
+                    Change::DeleteRR(name, qtype, rdata)
+                }
+            }
+        };
+        self.changes.push(change);
+        Ok(())
+    }
+}
+
+// a cursor over the tokens of a prerequisite or update line
+struct Fields<'a> {
+    tokens: &'a [Vec<u8>],
+}
+
+impl<'a> Fields<'a> {
+    fn new(tokens: &'a [Vec<u8>]) -> Fields<'a> {
+        Fields { tokens }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.first().and_then(|t| str::from_utf8(t).ok())
+    }
+
+    fn str(&mut self, what: &str) -> Result<&'a str> {
+        let token = self.peek().ok_or_else(|| anyhow!("missing {}", what))?;
+        self.tokens = &self.tokens[1..];
+        Ok(token)
+    }
+
+    fn name(&mut self) -> Result<Name> {
+        self.str("name")?.parse()
+    }
+
+    fn ttl(&mut self) -> Option<u32> {
+        let ttl = self.peek()?.parse().ok()?;
+        self.tokens = &self.tokens[1..];
+        Some(ttl)
+    }
+
+    // an optional class, it has to match the class of the zone. ANY is read as the type.
+    fn class(&mut self, zone_class: &QClass) -> Result<()> {
+        let Some(token) = self.peek() else {
+            return Ok(());
+        };
+        if token.parse::<QType>().is_ok() {
+            return Ok(());
+        }
+        if let Ok(class) = token.parse::<QClass>() {
+            if &class != zone_class {
+                return Err(anyhow!(
+                    "class {} does not match the zone class {}",
+                    class,
+                    zone_class
+                ));
+            }
+            self.tokens = &self.tokens[1..];
+        }
+        Ok(())
+    }
+
+    fn qtype(&mut self) -> Result<QType> {
+        self.str("type")?.parse()
+    }
+
+    fn rest(&mut self) -> &'a [Vec<u8>] {
+        let rest = self.tokens;
+        self.tokens = &[];
+        rest
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn done(&self) -> Result<()> {
+        match self.tokens.first() {
+            None => Ok(()),
+            Some(extra) => Err(anyhow!(
+                "unexpected trailing data: {}",
+                String::from_utf8_lossy(extra)
+            )),
+        }
+    }
+}
+
+// An update ready to be sent, with the server and key that were in effect for it.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub server: Option<String>,
+    pub key: Option<TsigKey>,
+    pub update: Update,
+}
+
+// Reads an nsupdate style script. The supported commands are:
+//
+//     server <host> [port]
+//     zone <zone>
+//     class <class>
+//     ttl <seconds>
+//     key [algorithm:]<name> <secret>
+//     prereq nxdomain|yxdomain <name>
+//     prereq nxrrset|yxrrset <name> [class] <type> [data...]
+//     [update] add <name> [ttl] [class] <type> <data...>
+//     [update] del[ete] <name> [class] [type [data...]]
+//     send
+//
+// Every `send` finishes one UPDATE message, changes that are left at the end of the script are
+// sent as well. Empty lines and lines starting with `;` or `#` are skipped.
+pub fn parse_script(script: &str, zone: Option<Name>) -> Result<Vec<Batch>> {
+    let mut batches = Vec::new();
+    let mut server = None;
+    let mut key = None;
+    let mut class = QClass::IN;
+    let mut default_ttl = None;
+    let mut update = zone.map(|zone| Update::new(zone, class.clone()));
+
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let (command, rest) = match command.to_ascii_lowercase().as_str() {
+            "update" => {
+                let (command, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                (command.to_ascii_lowercase(), rest.trim())
+            }
+            command => (command.to_owned(), rest),
+        };
+
+        let result = (|| -> Result<()> {
+            let current = || {
+                update
+                    .clone()
+                    .ok_or_else(|| anyhow!("no zone given, start the script with `zone <zone>`"))
+            };
+            match command.as_str() {
+                "server" => {
+                    let server_arg = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                        [host] => host.to_owned(),
+                        [host, port] if host.contains(':') => format!("[{}]:{}", host, port),
+                        [host, port] => format!("{}:{}", host, port),
+                        _ => return Err(anyhow!("expected: server <host> [port]")),
+                    };
+                    server = Some(parse_server(&server_arg).map_err(|e| anyhow!(e))?);
+                }
+                "zone" => {
+                    let zone: Name = rest.parse()?;
+                    if zone.is_root() {
+                        return Err(anyhow!("expected: zone <zone>"));
+                    }
+                    match &mut update {
+                        Some(update) if update.is_empty() => update.zone = zone,
+                        Some(_) => return Err(anyhow!("zone changed before `send`")),
+                        None => update = Some(Update::new(zone, class.clone())),
+                    }
+                }
+                "class" => {
+                    class = rest.parse()?;
+                    if let Some(update) = &mut update {
+                        update.class = class.clone();
+                    }
+                }
+                "ttl" => {
+                    default_ttl = Some(
+                        rest.parse()
+                            .with_context(|| format!("invalid ttl: {}", rest))?,
+                    )
+                }
+                "key" => {
+                    let key_arg = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                        [name, secret] => format!("{}:{}", name, secret),
+                        _ => return Err(anyhow!("expected: key [algorithm:]<name> <secret>")),
+                    };
+                    key = Some(key_arg.parse()?);
+                }
+                "prereq" => {
+                    let mut current = current()?;
+                    current.prerequisite(rest)?;
+                    update = Some(current);
+                }
+                "add" => {
+                    let mut current = current()?;
+                    current.add(rest, default_ttl)?;
+                    update = Some(current);
+                }
+                "del" | "delete" => {
+                    let mut current = current()?;
+                    current.delete(rest)?;
+                    update = Some(current);
+                }
+                "send" => {
+                    let current = current()?;
+                    if !current.is_empty() {
+                        batches.push(Batch {
+                            server: server.clone(),
+                            key: key.clone(),
+                            update: current.clone(),
+                        });
+                    }
+                    update = Some(Update::new(current.zone, class.clone()));
+                }
+                _ => return Err(anyhow!("unknown command: {}", command)),
+            }
+            Ok(())
+        })();
+        result.with_context(|| format!("line {}: {}", number + 1, line))?;
+    }
+
+    if let Some(update) = update.filter(|u| !u.is_empty()) {
+        batches.push(Batch {
+            server,
+            key,
+            update,
+        });
+    }
+    Ok(batches)
+}
+
+// Sends an update, signed when a key is given, and returns the response code of the server.
+pub async fn send(
+    update: &Update,
+    server: SocketAddr,
+    key: Option<&TsigKey>,
+) -> Result<ResponseCode> {
+    let mut request = update.message();
+    let request_mac = match key {
+        Some(key) => Some(tsig::sign(&mut request, key)?),
+        None => None,
+    };
+    let raw = request.serialize().context("Failed to serialize update")?;
+    let response = exchange(&raw, server).await?;
+    let message =
+        Message::try_from(response.as_slice()).context("Failed to deserialize response")?;
+    if message.header.id != request.header.id || message.header.opcode != Opcode::Update {
+        return Err(anyhow!(
+            "response from {} does not match the update",
+            server
+        ));
+    }
+    if let (Some(key), Some(request_mac)) = (key, request_mac) {
+        Verifier::new(key, request_mac).verify(&response)?;
+    }
+    Ok(message.header.r_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv4Addr;
+
+    fn name(name: &str) -> Name {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_nsupdate_script() {
+        let script = "
+            server 127.0.0.1 5353
+            zone example.com.
+            key hmac-sha256:update. c2VjcmV0
+            prereq nxdomain new.example.com
+            update add new.example.com 300 IN A 192.0.2.1
+            update delete old.example.com A
+            send
+            ttl 60
+            add txt.example.com TXT \"hello world\"
+            del gone.example.com
+        ";
+
+        let batches = parse_script(script, None).unwrap();
+
+        assert_eq!(2, batches.len());
+        assert_eq!(Some("127.0.0.1:5353".to_owned()), batches[0].server);
+        assert_eq!("update", batches[0].key.as_ref().unwrap().name);
+        assert_eq!(
+            Update {
+                zone: name("example.com"),
+                class: QClass::IN,
+                prerequisites: vec![Prerequisite::NxDomain(name("new.example.com"))],
+                changes: vec![
+                    Change::Add {
+                        name: name("new.example.com"),
+                        ttl: 300,
+                        qtype: QType::A,
+                        rdata: RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                    },
+                    Change::DeleteRRSet(name("old.example.com"), QType::A),
+                ],
+            },
+            batches[0].update
+        );
+        assert_eq!(
+            vec![
+                Change::Add {
+                    name: name("txt.example.com"),
+                    ttl: 60,
+                    qtype: QType::TXT,
+                    rdata: RData::TXT(vec![b"hello world".to_vec()]),
+                },
+                Change::DeleteName(name("gone.example.com")),
+            ],
+            batches[1].update.changes
+        );
+    }
+
+    #[test]
+    fn update_sections_follow_rfc_2136() {
+        let mut update = Update::new(name("example.com"), QClass::IN);
+        update.prerequisite("yxrrset www.example.com A").unwrap();
+        update.delete("www.example.com A 192.0.2.1").unwrap();
+        update.delete("www.example.com").unwrap();
+
+        let raw = update.message().serialize().unwrap();
+        let message = Message::try_from(raw.as_slice()).unwrap();
+
+        assert_eq!(Opcode::Update, message.header.opcode);
+        assert_eq!(QType::SOA, message.questions[0].qtype);
+        assert_eq!(
+            vec![(QClass::ANY, QType::A, RData::Empty)],
+            message
+                .records
+                .iter()
+                .map(|r| (r.qclass.clone(), r.qtype.clone(), r.rdata.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                (
+                    QClass::NONE,
+                    QType::A,
+                    RData::A(Ipv4Addr::new(192, 0, 2, 1))
+                ),
+                (QClass::ANY, QType::STAR, RData::Empty),
+            ],
+            message
+                .authorities
+                .iter()
+                .map(|r| (r.qclass.clone(), r.qtype.clone(), r.rdata.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert!(update
+            .add("www.example.com CH A 192.0.2.1", Some(60))
+            .is_err());
+    }
+}
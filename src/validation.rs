@@ -1,11 +1,12 @@
-pub fn check_length(value: &String) -> bool {
-    value.chars().count() <= 255
+// the limits are on octets on the wire (rfc 1035 2.3.4), not on characters
+pub fn check_length(value: &str) -> bool {
+    value.len() <= 255
 }
 
-pub fn check_token_length(value: &String) -> (&str, bool) {
+pub fn check_token_length(value: &str) -> (&str, bool) {
     let tokens = value.split('.');
     for t in tokens {
-        if t.chars().count() > 63 {
+        if t.len() > 63 {
             return (t, false);
         }
     }
@@ -82,4 +83,10 @@ mod tests {
 
         assert_eq!((expected.as_str(), false), check_token_length(&t1))
     }
+
+    #[test]
+    pub fn token_length_counts_octets() {
+        let label = "ü".repeat(32);
+        assert_eq!((label.as_str(), false), check_token_length(&label))
+    }
 }
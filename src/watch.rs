@@ -0,0 +1,513 @@
+use std::fmt::Display;
+use std::io::{self, Stdout};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::cookies::CookieJar;
+use crate::dns::message::Message;
+use crate::dns::record::Record;
+use crate::dns::tsig::TsigKey;
+use crate::layout::{wrap, Grid};
+use crate::{display_name, lookup, record_cells, Cli, BORDER_SIZE, VERSION};
+
+// the exit status once the value given with --until shows up
+pub const TARGET_SEEN: i32 = 2;
+const TTL_COLUMN: usize = 1;
+const TICK: Duration = Duration::from_millis(250);
+const KEYS: &str = "r poll now  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Kept,
+    Added,
+    Removed,
+}
+
+// a record of the answer with the ttl it had when it came in
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tracked {
+    cells: Vec<String>,
+    ttl: Duration,
+    mark: Mark,
+}
+
+impl Tracked {
+    fn new(record: &Record, concat_txt: bool, idn: bool) -> Tracked {
+        Tracked {
+            cells: record_cells(record, concat_txt, idn),
+            ttl: record.ttl,
+            mark: Mark::Kept,
+        }
+    }
+
+    // what is compared between polls, everything but the ttl
+    fn key(&self) -> String {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != TTL_COLUMN)
+            .map(|(_, cell)| cell.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// a record that came or went
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    time: DateTime<Local>,
+    added: bool,
+    record: String,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.added { '+' } else { '-' };
+        write!(
+            f,
+            "{} {} {}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            sign,
+            self.record
+        )
+    }
+}
+
+// The records of the last answer and everything that changed since the first one.
+#[derive(Debug, Default)]
+struct Tracker {
+    records: Vec<Tracked>,
+    timeline: Vec<Change>,
+    polls: u32,
+}
+
+impl Tracker {
+    // Takes the records of a new answer and returns how many came or went. The records of the
+    // last change stay marked until the answer changes again, a poll every few seconds would
+    // otherwise clear them before anyone looks.
+    fn update(&mut self, records: Vec<Tracked>, time: DateTime<Local>) -> usize {
+        self.polls += 1;
+        let previous = self
+            .records
+            .iter()
+            .filter(|r| r.mark != Mark::Removed)
+            .collect::<Vec<_>>();
+        let current = records.iter().map(|r| r.key()).collect::<Vec<_>>();
+        let removed = previous
+            .iter()
+            .filter(|r| !current.contains(&r.key()))
+            .map(|r| Tracked {
+                mark: Mark::Removed,
+                ..(*r).clone()
+            })
+            .collect::<Vec<_>>();
+        let added = records
+            .iter()
+            .filter(|r| !previous.iter().any(|p| p.key() == r.key()))
+            .count();
+
+        if self.polls == 1 {
+            self.records = records;
+            return 0;
+        }
+        if added == 0 && removed.is_empty() {
+            let removed = self
+                .records
+                .iter()
+                .filter(|r| r.mark == Mark::Removed)
+                .cloned()
+                .collect::<Vec<_>>();
+            let marks = previous
+                .iter()
+                .map(|r| (r.key(), r.mark))
+                .collect::<Vec<_>>();
+            self.records = records
+                .into_iter()
+                .map(|r| {
+                    let mark = marks
+                        .iter()
+                        .find(|(key, _)| *key == r.key())
+                        .map_or(Mark::Kept, |(_, mark)| *mark);
+                    Tracked { mark, ..r }
+                })
+                .chain(removed)
+                .collect();
+            return 0;
+        }
+
+        let records = records
+            .into_iter()
+            .map(|r| {
+                let mark = if previous.iter().any(|p| p.key() == r.key()) {
+                    Mark::Kept
+                } else {
+                    Mark::Added
+                };
+                Tracked { mark, ..r }
+            })
+            .chain(removed)
+            .collect::<Vec<_>>();
+        let changes = records
+            .iter()
+            .filter(|r| r.mark != Mark::Kept)
+            .map(|r| Change {
+                time,
+                added: r.mark == Mark::Added,
+                record: r.key(),
+            })
+            .collect::<Vec<_>>();
+        let count = changes.len();
+        self.timeline.extend(changes);
+        self.records = records;
+        count
+    }
+}
+
+// Whether a record holds the value given with --until. Names compare without case and trailing
+// dot, text without its quotes.
+pub fn shows(record: &Record, value: &str) -> bool {
+    let data = format!("{:#}", record.rdata);
+    let value = value.trim_end_matches('.');
+    data.eq_ignore_ascii_case(value) || data.trim_matches('"') == value
+}
+
+struct Watch<'a> {
+    cli: &'a Cli,
+    query: &'a Message,
+    server: SocketAddr,
+    interval: Duration,
+    until: Option<&'a str>,
+    tsig_key: Option<&'a TsigKey>,
+    cookies: CookieJar,
+    tracker: Tracker,
+    received: Instant,
+    next: Instant,
+    status: Result<String, String>,
+    seen: bool,
+    quit: bool,
+}
+
+impl<'a> Watch<'a> {
+    // asks again with a fresh id and takes in the answer
+    async fn poll(&mut self) {
+        let mut request = self.query.clone();
+        request.header.id = rand::random();
+        let answer = lookup(
+            self.cli,
+            &request,
+            self.server,
+            self.tsig_key,
+            &mut self.cookies,
+        )
+        .await;
+        self.next = Instant::now() + self.interval;
+        match answer {
+            Ok((message, stats)) => {
+                let records = message
+                    .records
+                    .iter()
+                    .map(|r| Tracked::new(r, self.cli.concat, self.cli.idn()))
+                    .collect();
+                let changes = self.tracker.update(records, stats.current_time);
+                self.received = Instant::now();
+                self.status = Ok(format!(
+                    "poll {}: {} in {} msec, {} changed",
+                    self.tracker.polls,
+                    message.header.r_code,
+                    stats.query_time.as_millis(),
+                    changes
+                ));
+                if let Some(value) = self.until {
+                    self.seen = message.records.iter().any(|r| shows(r, value));
+                }
+            }
+            Err(error) => self.status = Err(format!("{:#}", error)),
+        }
+    }
+
+    async fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        while !self.quit && !self.seen {
+            if Instant::now() >= self.next {
+                self.poll().await;
+                continue;
+            }
+            terminal.draw(|f| self.render(f))?;
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Release {
+                        match key.code {
+                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                self.quit = true
+                            }
+                            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+                            KeyCode::Char('r') => self.next = Instant::now(),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // prints every change as a line, for scripts and logs
+    async fn run_raw(&mut self) {
+        while !self.seen {
+            let shown = self.tracker.timeline.len();
+            self.poll().await;
+            match &self.status {
+                Ok(_) if self.tracker.polls == 1 => {
+                    for record in &self.tracker.records {
+                        println!(
+                            "{} = {}",
+                            Local::now().format("%Y-%m-%d %H:%M:%S"),
+                            record.key()
+                        );
+                    }
+                }
+                Ok(_) => {
+                    for change in &self.tracker.timeline[shown..] {
+                        println!("{}", change);
+                    }
+                }
+                Err(error) => eprintln!("{}", error),
+            }
+            if !self.seen {
+                tokio::time::sleep(self.interval).await;
+            }
+        }
+    }
+
+    fn title(&self) -> String {
+        let question = self
+            .query
+            .question()
+            .map(|q| format!("{} {}", display_name(&q.qname, self.cli.idn()), q.qtype))
+            .unwrap_or_default();
+        format!(
+            "== Who are you? == v{} == watching {} @{} every {}s ==",
+            VERSION,
+            question,
+            self.server,
+            self.interval.as_secs()
+        )
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let area = frame.size();
+        let elapsed = self.received.elapsed();
+        let cells = self
+            .tracker
+            .records
+            .iter()
+            .map(|r| {
+                let mut cells = r.cells.clone();
+                cells[TTL_COLUMN] = match r.mark {
+                    Mark::Removed => "-".to_owned(),
+                    _ => r.ttl.saturating_sub(elapsed).as_secs().to_string(),
+                };
+                cells
+            })
+            .collect();
+        let records = Grid::new(cells, area.width.saturating_sub(BORDER_SIZE), &[2, 1]);
+
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(1),
+                Constraint::Length(records.height() + BORDER_SIZE),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ])
+            .split(area);
+
+        frame.render_widget(Paragraph::new(self.title()).fg(Color::White), outer[0]);
+
+        // Records
+        let rows = records
+            .rows()
+            .into_iter()
+            .zip(&self.tracker.records)
+            .map(|(row, r)| match r.mark {
+                Mark::Kept => row,
+                Mark::Added => row.fg(Color::Green),
+                Mark::Removed => row.fg(Color::Red).add_modifier(Modifier::CROSSED_OUT),
+            });
+        frame.render_widget(
+            Table::new(rows)
+                .block(
+                    Block::new()
+                        .title("Records")
+                        .borders(Borders::ALL)
+                        .fg(Color::Green),
+                )
+                .widths(records.widths()),
+            outer[1],
+        );
+
+        // Timeline, the latest changes at the bottom
+        let width = outer[2].width.saturating_sub(BORDER_SIZE) as usize;
+        let lines = self
+            .tracker
+            .timeline
+            .iter()
+            .flat_map(|change| {
+                let color = if change.added {
+                    Color::Green
+                } else {
+                    Color::Red
+                };
+                wrap(&change.to_string(), width)
+                    .into_iter()
+                    .map(move |line| Line::styled(line, Style::default().fg(color)))
+            })
+            .collect::<Vec<_>>();
+        let height = outer[2].height.saturating_sub(BORDER_SIZE) as usize;
+        let scroll = lines.len().saturating_sub(height) as u16;
+        frame.render_widget(
+            Paragraph::new(lines).scroll((scroll, 0)).block(
+                Block::new()
+                    .title("Timeline")
+                    .borders(Borders::ALL)
+                    .fg(Color::Green),
+            ),
+            outer[2],
+        );
+
+        let next = self
+            .next
+            .saturating_duration_since(Instant::now())
+            .as_secs()
+            + 1;
+        let status = match &self.status {
+            Ok(status) => {
+                Paragraph::new(format!("{}, next in {}s  {}", status, next, KEYS)).fg(Color::White)
+            }
+            Err(error) => {
+                Paragraph::new(format!("{}, next in {}s  {}", error, next, KEYS)).fg(Color::Red)
+            }
+        };
+        frame.render_widget(status, outer[3]);
+    }
+}
+
+// Asks for the query every `interval` until the user quits, and returns whether it ended because
+// the value given with --until showed up. The changes are printed once the screen is gone.
+pub async fn run(
+    cli: &Cli,
+    query: &Message,
+    server: SocketAddr,
+    interval: Duration,
+    until: Option<&str>,
+    tsig_key: Option<&TsigKey>,
+) -> Result<bool> {
+    let mut watch = Watch {
+        cli,
+        query,
+        server,
+        interval,
+        until,
+        tsig_key,
+        cookies: cli.cookie_jar(),
+        tracker: Tracker::default(),
+        received: Instant::now(),
+        next: Instant::now(),
+        status: Ok(String::new()),
+        seen: false,
+        quit: false,
+    };
+
+    if cli.raw {
+        watch.run_raw().await;
+        watch.cookies.save();
+        return Ok(watch.seen);
+    }
+
+    enable_raw_mode().context("failed to enable raw mode")?;
+    execute!(io::stdout(), EnterAlternateScreen).context("unable to enter alternate screen")?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let result = watch.run(&mut terminal).await;
+    execute!(io::stdout(), LeaveAlternateScreen).context("unable to leave alternate screen")?;
+    disable_raw_mode().context("failed to disable raw mode")?;
+    terminal.show_cursor().context("unable to show cursor")?;
+    watch.cookies.save();
+
+    result?;
+    for change in &watch.tracker.timeline {
+        println!("{}", change);
+    }
+    Ok(watch.seen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn answer(data: &[&str]) -> Vec<Tracked> {
+        data.iter()
+            .map(|d| Tracked {
+                cells: ["example.com", "300", "IN", "A", d]
+                    .map(|c| c.to_owned())
+                    .to_vec(),
+                ttl: Duration::from_secs(300),
+                mark: Mark::Kept,
+            })
+            .collect()
+    }
+
+    fn marks(tracker: &Tracker) -> Vec<(String, Mark)> {
+        tracker
+            .records
+            .iter()
+            .map(|r| (r.cells[4].clone(), r.mark))
+            .collect()
+    }
+
+    #[test]
+    fn mark_records_that_come_and_go() {
+        let time = Local::now();
+        let mut tracker = Tracker::default();
+        assert_eq!(0, tracker.update(answer(&["192.0.2.1", "192.0.2.2"]), time));
+        assert_eq!(0, tracker.update(answer(&["192.0.2.2", "192.0.2.1"]), time));
+        assert_eq!(2, tracker.update(answer(&["192.0.2.2", "192.0.2.3"]), time));
+        let expected = vec![
+            ("192.0.2.2".to_owned(), Mark::Kept),
+            ("192.0.2.3".to_owned(), Mark::Added),
+            ("192.0.2.1".to_owned(), Mark::Removed),
+        ];
+        assert_eq!(expected, marks(&tracker));
+
+        // the marks stay until the answer changes again
+        assert_eq!(0, tracker.update(answer(&["192.0.2.3", "192.0.2.2"]), time));
+        let expected = vec![
+            ("192.0.2.3".to_owned(), Mark::Added),
+            ("192.0.2.2".to_owned(), Mark::Kept),
+            ("192.0.2.1".to_owned(), Mark::Removed),
+        ];
+        assert_eq!(expected, marks(&tracker));
+
+        assert_eq!(1, tracker.update(answer(&["192.0.2.3"]), time));
+        assert_eq!(
+            vec![
+                "+ example.com IN A 192.0.2.3",
+                "- example.com IN A 192.0.2.1",
+                "- example.com IN A 192.0.2.2",
+            ],
+            tracker
+                .timeline
+                .iter()
+                .map(|c| c.to_string()[20..].to_owned())
+                .collect::<Vec<_>>()
+        );
+    }
+}
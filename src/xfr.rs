@@ -0,0 +1,430 @@
+use std::iter::Peekable;
+use std::net::SocketAddr;
+use std::slice::Iter;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+use crate::client::TcpConnection;
+use crate::dns::header::ResponseCode;
+use crate::dns::message::Message;
+use crate::dns::name::Name;
+use crate::dns::record::{RData, Record};
+use crate::dns::tsig::{self, TsigKey, Verifier};
+use crate::dns::{QType, Serialize};
+use crate::json;
+
+// The result of a zone transfer (rfc 5936) or an incremental zone transfer (rfc 1995).
+#[derive(Debug, PartialEq, Eq)]
+pub enum Transfer {
+    // a full copy of the zone, starting and ending with its SOA record
+    Full(Vec<Record>),
+    // the changes made to the zone since the serial we asked for, oldest first
+    Incremental(Vec<Delta>),
+    // we already have the current version of the zone, only its SOA is returned
+    UpToDate(Record),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Delta {
+    pub from: u32,
+    pub to: u32,
+    pub removed: Vec<Record>,
+    pub added: Vec<Record>,
+}
+
+pub struct TransferStats {
+    pub messages: usize,
+    pub bytes: usize,
+    pub records: usize,
+    pub elapsed: Duration,
+}
+
+pub(crate) fn soa_serial(record: &Record) -> Option<u32> {
+    match record.rdata {
+        RData::SOA { serial, .. } => Some(serial),
+        _ => None,
+    }
+}
+
+// whether a serial is newer than another one, counting around the wrap (rfc 1982 3.2)
+fn newer(serial: u32, than: u32) -> bool {
+    serial != than && serial.wrapping_sub(than) < 1 << 31
+}
+
+// A transfer is finished when the SOA that started it shows up again at the end. An incremental
+// transfer repeats the new SOA once more, at the start of the last set of additions.
+fn is_complete(records: &[Record], incremental: bool) -> bool {
+    let (Some(first), Some(last)) = (records.first(), records.last()) else {
+        return false;
+    };
+    let Some(serial) = soa_serial(first) else {
+        return false;
+    };
+    if records.len() < 2 || soa_serial(last) != Some(serial) {
+        return false;
+    }
+    let seen = records[1..]
+        .iter()
+        .filter(|r| soa_serial(r) == Some(serial))
+        .count();
+    let incremental = incremental && soa_serial(&records[1]).is_some();
+    if incremental {
+        seen >= 2
+    } else {
+        seen >= 1
+    }
+}
+
+fn take_until_soa(records: &mut Peekable<Iter<Record>>) -> Vec<Record> {
+    let mut taken = Vec::new();
+    while let Some(record) = records.next_if(|r| soa_serial(r).is_none()) {
+        taken.push(record.clone());
+    }
+    taken
+}
+
+// Each difference sequence is the old SOA, the removed records, the new SOA and the added
+// records. The whole response is wrapped in the current SOA of the zone.
+fn deltas(records: &[Record]) -> Result<Vec<Delta>> {
+    let mut deltas = Vec::new();
+    let mut records = records[1..records.len() - 1].iter().peekable();
+    while let Some(from) = records.next() {
+        let from = soa_serial(from).ok_or_else(|| anyhow!("expected SOA, got: {}", from))?;
+        let removed = take_until_soa(&mut records);
+        let to = records
+            .next()
+            .and_then(soa_serial)
+            .ok_or_else(|| anyhow!("difference sequence from serial {} has no end", from))?;
+        let added = take_until_soa(&mut records);
+        deltas.push(Delta {
+            from,
+            to,
+            removed,
+            added,
+        });
+    }
+    Ok(deltas)
+}
+
+fn into_transfer(records: Vec<Record>, serial: Option<u32>) -> Result<Transfer> {
+    match serial {
+        Some(serial) if records.len() == 1 => {
+            let current = soa_serial(&records[0]).unwrap_or_default();
+            if current != serial {
+                return Err(anyhow!(
+                    "server answered serial {} with only its SOA at serial {}",
+                    serial,
+                    current
+                ));
+            }
+            Ok(Transfer::UpToDate(records[0].clone()))
+        }
+        Some(_) if soa_serial(&records[1]).is_some() => {
+            Ok(Transfer::Incremental(deltas(&records)?))
+        }
+        _ => Ok(Transfer::Full(records)),
+    }
+}
+
+// Performs an AXFR, or an IXFR when a serial is given, over tcp. The server sends the zone as
+// any number of messages, we keep reading until the closing SOA. With a key the request is signed
+// and the signatures of the responses are verified as they arrive.
+pub async fn transfer(
+    zone: &Name,
+    serial: Option<u32>,
+    server: SocketAddr,
+    key: Option<&TsigKey>,
+) -> Result<(Transfer, TransferStats)> {
+    let mut request = match serial {
+        Some(serial) => Message::ixfr(zone.clone(), serial),
+        None => Message::axfr(zone.clone()),
+    };
+    let mut verifier = match key {
+        Some(key) => Some(Verifier::new(key, tsig::sign(&mut request, key)?)),
+        None => None,
+    };
+    let bytes = request.serialize().context("Failed to serialize request")?;
+
+    let start = Instant::now();
+    let mut connection = TcpConnection::connect(server).await?;
+    connection.send(&bytes).await?;
+
+    let mut stats = TransferStats {
+        messages: 0,
+        bytes: 0,
+        records: 0,
+        elapsed: Duration::ZERO,
+    };
+    let mut records: Vec<Record> = Vec::new();
+    loop {
+        let response = connection.recv().await?;
+        if let Some(verifier) = verifier.as_mut() {
+            verifier.verify(&response)?;
+        }
+        let message =
+            Message::try_from(response.as_slice()).context("Failed to deserialize response")?;
+        if message.header.id != request.header.id {
+            return Err(anyhow!("response id {} does not match", message.header.id));
+        }
+        if message.header.r_code != ResponseCode::NoError {
+            return Err(anyhow!("transfer refused: {}", message.header.r_code));
+        }
+        stats.messages += 1;
+        stats.bytes += response.len();
+        records.extend(message.records);
+
+        if records.first().and_then(soa_serial).is_none() {
+            return Err(anyhow!("transfer did not start with an SOA record"));
+        }
+        // a lone SOA that is not newer than ours says we are up to date (rfc 1995 2), a newer
+        // one is only the start of a transfer sent one record to a message
+        let up_to_date = match (serial, records.as_slice()) {
+            (Some(serial), [soa]) => soa_serial(soa).is_some_and(|current| !newer(current, serial)),
+            _ => false,
+        };
+        if up_to_date || is_complete(&records, serial.is_some()) {
+            break;
+        }
+    }
+    if let Some(verifier) = verifier {
+        verifier.finish()?;
+    }
+    stats.records = records.len();
+    stats.elapsed = start.elapsed();
+
+    Ok((into_transfer(records, serial)?, stats))
+}
+
+pub fn print(transfer: &Transfer, stats: &TransferStats) {
+    match transfer {
+        Transfer::Full(records) => {
+            for record in records {
+                println!("{}", record);
+            }
+        }
+        Transfer::Incremental(deltas) => {
+            for delta in deltas {
+                println!("; serial {} -> {}", delta.from, delta.to);
+                for record in &delta.removed {
+                    println!("-{}", record);
+                }
+                for record in &delta.added {
+                    println!("+{}", record);
+                }
+            }
+        }
+        Transfer::UpToDate(soa) => {
+            println!("; zone is up to date");
+            println!("{}", soa);
+        }
+    }
+    println!(
+        "; {} records in {} messages, {} bytes, {} msec",
+        stats.records,
+        stats.messages,
+        stats.bytes,
+        stats.elapsed.as_millis()
+    );
+}
+
+pub fn to_json(zone: &Name, transfer: &Transfer) -> Value {
+    match transfer {
+        Transfer::Full(records) => json!({
+            "zone": zone.to_string(),
+            "type": QType::AXFR.to_string(),
+            "records": json::records(records),
+        }),
+        Transfer::Incremental(deltas) => json!({
+            "zone": zone.to_string(),
+            "type": QType::IXFR.to_string(),
+            "deltas": deltas.iter().map(|d| json!({
+                "from": d.from,
+                "to": d.to,
+                "removed": json::records(&d.removed),
+                "added": json::records(&d.added),
+            })).collect::<Vec<_>>(),
+        }),
+        Transfer::UpToDate(soa) => json!({
+            "zone": zone.to_string(),
+            "type": QType::IXFR.to_string(),
+            "up_to_date": true,
+            "records": json::records(std::slice::from_ref(soa)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use pretty_assertions::assert_eq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::dns::QClass;
+
+    fn record(name: &str, qtype: QType, rdata: RData) -> Record {
+        let rd_length = rdata.serialize().unwrap().len() as u16;
+        Record::new(
+            name.parse().unwrap(),
+            qtype,
+            QClass::IN,
+            Duration::from_secs(3600),
+            rd_length,
+            rdata,
+        )
+    }
+
+    fn soa(serial: u32) -> Record {
+        let rdata = RData::SOA {
+            mname: "ns1.example.com".parse().unwrap(),
+            rname: "hostmaster.example.com".parse().unwrap(),
+            serial,
+            refresh: 7200,
+            retry: 900,
+            expire: 1209600,
+            minimum: 300,
+        };
+        record("example.com", QType::SOA, rdata)
+    }
+
+    fn a(name: &str, last: u8) -> Record {
+        record(name, QType::A, RData::A(Ipv4Addr::new(192, 0, 2, last)))
+    }
+
+    // answers a single transfer request with the given answer sections, one message each
+    async fn serve(sections: Vec<Vec<Record>>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let length = stream.read_u16().await.unwrap();
+            let mut request = vec![0; length as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            let request = Message::try_from(request.as_slice()).unwrap();
+
+            for records in sections {
+                let mut response = request.clone();
+                response.header.qr = true;
+                response.records = records;
+                let bytes = response.serialize().unwrap();
+                stream.write_u16(bytes.len() as u16).await.unwrap();
+                stream.write_all(&bytes).await.unwrap();
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn axfr_over_multiple_messages() {
+        let server = serve(vec![
+            vec![soa(5), a("www.example.com", 1)],
+            vec![a("mail.example.com", 2), soa(5)],
+        ])
+        .await;
+
+        let (transfer, stats) = transfer(&"example.com".parse().unwrap(), None, server, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Transfer::Full(vec![
+                soa(5),
+                a("www.example.com", 1),
+                a("mail.example.com", 2),
+                soa(5)
+            ]),
+            transfer
+        );
+        assert_eq!(2, stats.messages);
+    }
+
+    #[tokio::test]
+    async fn ixfr_deltas() {
+        let server = serve(vec![vec![
+            soa(3),
+            soa(1),
+            a("www.example.com", 1),
+            soa(2),
+            a("www.example.com", 2),
+            soa(2),
+            soa(3),
+            a("mail.example.com", 3),
+            soa(3),
+        ]])
+        .await;
+
+        let (transfer, _) = transfer(&"example.com".parse().unwrap(), Some(1), server, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Transfer::Incremental(vec![
+                Delta {
+                    from: 1,
+                    to: 2,
+                    removed: vec![a("www.example.com", 1)],
+                    added: vec![a("www.example.com", 2)],
+                },
+                Delta {
+                    from: 2,
+                    to: 3,
+                    removed: vec![],
+                    added: vec![a("mail.example.com", 3)],
+                },
+            ]),
+            transfer
+        );
+    }
+
+    #[tokio::test]
+    async fn ixfr_up_to_date() {
+        let server = serve(vec![vec![soa(7)]]).await;
+
+        let (transfer, _) = transfer(&"example.com".parse().unwrap(), Some(7), server, None)
+            .await
+            .unwrap();
+
+        assert_eq!(Transfer::UpToDate(soa(7)), transfer);
+    }
+
+    #[tokio::test]
+    async fn one_record_per_message() {
+        let ixfr = [
+            soa(2),
+            soa(1),
+            a("www.example.com", 1),
+            soa(2),
+            a("www.example.com", 2),
+            soa(2),
+        ];
+        let server = serve(ixfr.iter().map(|r| vec![r.clone()]).collect()).await;
+        let (incremental, stats) = transfer(&"example.com".parse().unwrap(), Some(1), server, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            Transfer::Incremental(vec![Delta {
+                from: 1,
+                to: 2,
+                removed: vec![a("www.example.com", 1)],
+                added: vec![a("www.example.com", 2)],
+            }]),
+            incremental
+        );
+        assert_eq!(6, stats.messages);
+
+        // the server has no history back to our serial and falls back to the whole zone
+        let axfr = [soa(9), a("www.example.com", 9), soa(9)];
+        let server = serve(axfr.iter().map(|r| vec![r.clone()]).collect()).await;
+        let (full, _) = transfer(&"example.com".parse().unwrap(), Some(1), server, None)
+            .await
+            .unwrap();
+        assert_eq!(Transfer::Full(axfr.to_vec()), full);
+
+        assert!(newer(1, u32::MAX));
+        assert!(!newer(u32::MAX, 1));
+    }
+}